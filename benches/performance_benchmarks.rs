@@ -1,5 +1,5 @@
 use criterion::{criterion_group, criterion_main, Criterion, BenchmarkId};
-use git_warp::cow::{clone_directory, is_cow_supported};
+use git_warp::cow::{clone_directory, is_cow_supported, CloneSpec};
 use git_warp::git::GitRepository;
 use git_warp::process::ProcessManager;
 use git_warp::rewrite::PathRewriter;
@@ -29,7 +29,7 @@ fn bench_cow_vs_traditional_copy(c: &mut Criterion) {
             |b, repo_path| {
                 b.iter(|| {
                     let clone_path = repo_path.parent().unwrap().join(format!("cow_clone_{}", rand::random::<u32>()));
-                    let _ = clone_directory(repo_path, &clone_path);
+                    let _ = clone_directory(repo_path, &clone_path, &CloneSpec::new());
                     // Clean up
                     let _ = fs::remove_dir_all(&clone_path);
                 })
@@ -79,7 +79,7 @@ fn bench_git_operations(c: &mut Criterion) {
             let _ = git_repo.create_worktree_and_branch(&branch_name, &worktree_path, None);
             
             // Clean up
-            let _ = git_repo.remove_worktree(&worktree_path, false, true);
+            let _ = git_repo.remove_worktree(&worktree_path, false);
         })
     });
     
@@ -96,7 +96,7 @@ fn bench_git_operations(c: &mut Criterion) {
                     path: worktree_path,
                     branch: branch_name,
                     head: "HEAD".to_string(),
-                    is_primary: false,
+                    ..Default::default()
                 });
             }
         }
@@ -107,13 +107,82 @@ fn bench_git_operations(c: &mut Criterion) {
         
         // Clean up test worktrees
         for worktree in &test_worktrees {
-            let _ = git_repo.remove_worktree(&worktree.path, false, true);
+            let _ = git_repo.remove_worktree(&worktree.path, false);
         }
     });
     
     group.finish();
 }
 
+fn bench_branch_analysis(c: &mut Criterion) {
+    let mut group = c.benchmark_group("branch_analysis");
+    group.sample_size(10);
+    
+    // Agent workflows leave dozens of worktrees behind
+    for worktree_count in [12, 48] {
+        let temp_dir = setup_git_repository();
+        let repo_path = temp_dir.path();
+        let worktrees_dir = tempdir().unwrap();
+        std::env::set_current_dir(repo_path).unwrap();
+        
+        let git_repo = GitRepository::find().unwrap();
+        for i in 0..worktree_count {
+            let branch_name = format!("agent-{}", i);
+            let worktree_path = worktrees_dir.path().join(&branch_name);
+            git_repo.create_worktree_and_branch(&branch_name, &worktree_path, None).unwrap();
+            
+            // Half the branches have work that isn't merged yet
+            if i % 2 == 1 {
+                fs::write(worktree_path.join(format!("{}.txt", branch_name)), "work in progress").unwrap();
+                Command::new("git").args(["add", "."]).current_dir(&worktree_path).output().unwrap();
+                Command::new("git").args(["commit", "-m", "Agent work"]).current_dir(&worktree_path).output().unwrap();
+            }
+        }
+        let worktrees = git_repo.list_worktrees().unwrap();
+        
+        group.bench_with_input(
+            BenchmarkId::new("analyze_branches", worktree_count),
+            &worktrees,
+            |b, worktrees| {
+                b.iter(|| {
+                    let _ = git_repo.analyze_branches_for_cleanup(worktrees);
+                })
+            },
+        );
+    }
+    
+    // A long history behind a branch that diverged recently should cost what the branch adds
+    for commit_count in [100_000] {
+        let temp_dir = setup_git_repository();
+        let repo_path = temp_dir.path();
+        let worktrees_dir = tempdir().unwrap();
+        extend_history(repo_path, commit_count);
+        std::env::set_current_dir(repo_path).unwrap();
+        
+        let git_repo = GitRepository::find().unwrap();
+        let worktree_path = worktrees_dir.path().join("agent-deep");
+        git_repo.create_worktree_and_branch("agent-deep", &worktree_path, None).unwrap();
+        for (dir, file) in [(worktree_path.as_path(), "agent.txt"), (repo_path, "main.txt")] {
+            fs::write(dir.join(file), "diverged").unwrap();
+            Command::new("git").args(["add", "."]).current_dir(dir).output().unwrap();
+            Command::new("git").args(["commit", "-m", file]).current_dir(dir).output().unwrap();
+        }
+        let worktrees = git_repo.list_worktrees().unwrap();
+        
+        group.bench_with_input(
+            BenchmarkId::new("deep_history", commit_count),
+            &worktrees,
+            |b, worktrees| {
+                b.iter(|| {
+                    let _ = git_repo.analyze_branches_for_cleanup(worktrees);
+                })
+            },
+        );
+    }
+    
+    group.finish();
+}
+
 fn bench_process_detection(c: &mut Criterion) {
     let mut group = c.benchmark_group("process_detection");
     
@@ -158,10 +227,7 @@ fn bench_path_rewriting(c: &mut Criterion) {
             &clone_path,
             |b, clone_path| {
                 b.iter(|| {
-                    let rewriter = PathRewriter::new(
-                        repo_path.to_string_lossy(),
-                        clone_path.to_string_lossy()
-                    );
+                    let rewriter = PathRewriter::new(repo_path, clone_path);
                     let _ = rewriter.rewrite_paths();
                 })
             },
@@ -329,18 +395,54 @@ fn setup_git_repository() -> tempfile::TempDir {
     let temp_dir = tempdir().unwrap();
     let repo_path = temp_dir.path();
     
-    Command::new("git").args(&["init"]).current_dir(repo_path).output().unwrap();
-    Command::new("git").args(&["config", "user.email", "bench@example.com"]).current_dir(repo_path).output().unwrap();
-    Command::new("git").args(&["config", "user.name", "Bench User"]).current_dir(repo_path).output().unwrap();
+    Command::new("git").args(["init"]).current_dir(repo_path).output().unwrap();
+    Command::new("git").args(["config", "user.email", "bench@example.com"]).current_dir(repo_path).output().unwrap();
+    Command::new("git").args(["config", "user.name", "Bench User"]).current_dir(repo_path).output().unwrap();
     
     fs::write(repo_path.join("README.md"), "# Benchmark Repo").unwrap();
     
-    Command::new("git").args(&["add", "."]).current_dir(repo_path).output().unwrap();
-    Command::new("git").args(&["commit", "-m", "Initial commit"]).current_dir(repo_path).output().unwrap();
+    Command::new("git").args(["add", "."]).current_dir(repo_path).output().unwrap();
+    Command::new("git").args(["commit", "-m", "Initial commit"]).current_dir(repo_path).output().unwrap();
     
     temp_dir
 }
 
+/// Add `count` commits to the checked-out branch with a single `git fast-import` run
+fn extend_history(repo_path: &Path, count: usize) {
+    use std::fmt::Write as _;
+    use std::io::Write as _;
+    use std::process::Stdio;
+    
+    let git = |args: &[&str]| {
+        let output = Command::new("git").args(args).current_dir(repo_path).output().unwrap();
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    };
+    let branch = git(&["symbolic-ref", "HEAD"]);
+    let head = git(&["rev-parse", "HEAD"]);
+    
+    let mut stream = String::new();
+    for i in 0..count {
+        let content = format!("{}\n", i);
+        writeln!(stream, "commit {}", branch).unwrap();
+        writeln!(stream, "committer Bench User <bench@example.com> {} +0000", 1_600_000_000 + i).unwrap();
+        writeln!(stream, "data 0").unwrap();
+        if i == 0 {
+            writeln!(stream, "from {}", head).unwrap();
+        }
+        writeln!(stream, "M 644 inline history.txt\ndata {}\n{}", content.len(), content).unwrap();
+    }
+    
+    let mut import = Command::new("git")
+        .args(["fast-import", "--quiet"])
+        .current_dir(repo_path)
+        .stdin(Stdio::piped())
+        .spawn()
+        .unwrap();
+    import.stdin.take().unwrap().write_all(stream.as_bytes()).unwrap();
+    assert!(import.wait().unwrap().success());
+    git(&["reset", "--hard", "-q"]);
+}
+
 fn copy_directory_recursive<P: AsRef<Path>, Q: AsRef<Path>>(
     src: P, 
     dst: Q
@@ -386,6 +488,7 @@ criterion_group!(
     benches,
     bench_cow_vs_traditional_copy,
     bench_git_operations, 
+    bench_branch_analysis,
     bench_process_detection,
     bench_path_rewriting,
     bench_configuration_loading,
@@ -0,0 +1,506 @@
+use crate::error::Result;
+use crate::git::{BranchStatus, GitRepository, MergeMethod, WorktreeInfo};
+use crate::process::ProcessManager;
+use ignore::WalkBuilder;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Flags that decide what cleanup selects and how far it may go
+#[derive(Debug, Clone, Default)]
+pub struct CleanupOptions {
+    /// Name of the mode or policy, for reporting
+    pub mode: String,
+    /// Which worktrees to select
+    pub policy: CleanupPolicy,
+    /// Clean up worktrees with uncommitted changes or running processes, and force-delete branches
+    pub force: bool,
+    /// Terminate processes running in a worktree before removing it
+    pub kill: bool,
+    /// Don't look for processes at all
+    pub no_kill: bool,
+    pub include_locked: bool,
+}
+
+/// One step of cleaning up a worktree, in execution order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum CleanupAction {
+    KillProcesses { count: usize },
+    Unlock,
+    RemoveWorktree { force: bool },
+    DeleteBranch { force: bool },
+}
+
+impl std::fmt::Display for CleanupAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CleanupAction::KillProcesses { count } => write!(f, "kill {} process(es)", count),
+            CleanupAction::Unlock => write!(f, "unlock"),
+            CleanupAction::RemoveWorktree { force: false } => write!(f, "remove worktree"),
+            CleanupAction::RemoveWorktree { force: true } => write!(f, "force-remove worktree"),
+            CleanupAction::DeleteBranch { force: false } => write!(f, "delete branch"),
+            CleanupAction::DeleteBranch { force: true } => write!(f, "force-delete branch"),
+        }
+    }
+}
+
+/// A worktree the plan will clean up
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedCleanup {
+    #[serde(flatten)]
+    pub status: BranchStatus,
+    /// Why the worktree was selected
+    pub reason: String,
+    pub actions: Vec<CleanupAction>,
+}
+
+/// A worktree the mode selected but that is left alone
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedWorktree {
+    pub branch: String,
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// A worktree whose directory is gone; only its reference gets pruned
+#[derive(Debug, Clone, Serialize)]
+pub struct StaleWorktree {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// Everything a cleanup run would do, decided up front so it can be previewed
+#[derive(Debug, Clone, Serialize)]
+pub struct CleanupPlan {
+    /// The mode or policy name the plan was built with
+    pub mode: String,
+    pub stale: Vec<StaleWorktree>,
+    pub entries: Vec<PlannedCleanup>,
+    pub skipped: Vec<SkippedWorktree>,
+}
+
+/// What executing a plan achieved
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CleanupOutcome {
+    pub removed: usize,
+    pub failed: usize,
+}
+
+impl CleanupPlan {
+    /// Decide what cleanup would do. Reads state only; nothing is removed, killed or pruned.
+    pub fn build(
+        git_repo: &GitRepository,
+        worktrees: &[WorktreeInfo],
+        options: &CleanupOptions,
+        process_manager: &mut ProcessManager,
+    ) -> Result<Self> {
+        let mut plan = CleanupPlan {
+            mode: options.mode.clone(),
+            stale: Vec::new(),
+            entries: Vec::new(),
+            skipped: Vec::new(),
+        };
+        
+        for worktree in worktrees {
+            if let Some(reason) = &worktree.prunable {
+                plan.stale.push(StaleWorktree {
+                    path: worktree.path.clone(),
+                    reason: reason.clone(),
+                });
+            } else if let Some(reason) = &worktree.locked
+                && !options.include_locked
+                && !worktree.is_main
+                && !worktree.branch.is_empty()
+            {
+                let reason = if reason.is_empty() {
+                    "locked; use --include-locked".to_string()
+                } else {
+                    format!("locked ({}); use --include-locked", reason)
+                };
+                plan.skipped.push(SkippedWorktree {
+                    branch: worktree.branch.clone(),
+                    path: worktree.path.clone(),
+                    reason,
+                });
+            }
+        }
+        
+        let statuses = git_repo.analyze_branches_for_cleanup_with(worktrees, options.include_locked)?;
+        for status in statuses {
+            let Some(reason) = options.policy.evaluate(&status, &mut WorktreeFacts::new(&status))? else {
+                continue;
+            };
+            
+            let skip = |plan: &mut CleanupPlan, reason: String| {
+                plan.skipped.push(SkippedWorktree {
+                    branch: status.branch.clone(),
+                    path: status.path.clone(),
+                    reason,
+                });
+            };
+            
+            if status.has_uncommitted_changes && !options.force {
+                skip(&mut plan, "uncommitted changes; use --force".to_string());
+                continue;
+            }
+            
+            let mut actions = Vec::new();
+            if !options.no_kill {
+                let processes = match process_manager.find_processes_in_directory(&status.path) {
+                    Ok(processes) => processes.len(),
+                    Err(e) => {
+                        log::warn!("Failed to check processes in {}: {}", status.path.display(), e);
+                        0
+                    }
+                };
+                
+                if processes > 0 {
+                    if options.kill {
+                        actions.push(CleanupAction::KillProcesses { count: processes });
+                    } else if !options.force {
+                        skip(&mut plan, format!("{} process(es) running; use --kill or --force", processes));
+                        continue;
+                    }
+                }
+            }
+            
+            if status.locked.is_some() {
+                actions.push(CleanupAction::Unlock);
+            }
+            actions.push(CleanupAction::RemoveWorktree { force: options.force });
+            if status.is_merged || options.force {
+                // `git branch -d` only recognises ancestry; other merges were proven here
+                let proven = status.merged_via.is_some_and(|method| method != MergeMethod::Ancestor);
+                actions.push(CleanupAction::DeleteBranch { force: options.force || proven });
+            }
+            
+            plan.entries.push(PlannedCleanup { status, reason, actions });
+        }
+        
+        Ok(plan)
+    }
+    
+    /// Whether running the plan would change nothing
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty() && self.stale.is_empty()
+    }
+    
+    /// Keep only the entries for these branches, e.g. after an interactive selection
+    pub fn retain_branches(&mut self, branches: &[String]) {
+        self.entries.retain(|entry| branches.contains(&entry.status.branch));
+    }
+    
+    /// Carry out the plan, reporting each step as it goes
+    pub fn execute(
+        &self,
+        git_repo: &GitRepository,
+        process_manager: &mut ProcessManager,
+        auto_confirm: bool,
+    ) -> Result<CleanupOutcome> {
+        let mut outcome = CleanupOutcome::default();
+        
+        if !self.stale.is_empty() {
+            println!("🧹 Pruning {} stale worktree reference(s)", self.stale.len());
+            git_repo.prune_worktrees()?;
+        }
+        
+        'entries: for entry in &self.entries {
+            let branch = &entry.status.branch;
+            let path = &entry.status.path;
+            println!("🗑️  Removing worktree: {}", branch);
+            
+            for action in &entry.actions {
+                match action {
+                    CleanupAction::KillProcesses { .. } => {
+                        // Look again, the processes may have changed since planning
+                        let processes = match process_manager.find_processes_in_directory(path) {
+                            Ok(processes) => processes,
+                            Err(e) => {
+                                println!("❌ Failed to check processes in {}: {}", branch, e);
+                                outcome.failed += 1;
+                                continue 'entries;
+                            }
+                        };
+                        if !process_manager.terminate_processes(&processes, auto_confirm)? {
+                            println!("❌ Failed to terminate processes, skipping worktree");
+                            outcome.failed += 1;
+                            continue 'entries;
+                        }
+                    }
+                    CleanupAction::Unlock => {
+                        if let Err(e) = git_repo.unlock_worktree(path) {
+                            println!("❌ Failed to unlock worktree {}: {}", branch, e);
+                            outcome.failed += 1;
+                            continue 'entries;
+                        }
+                    }
+                    CleanupAction::RemoveWorktree { force } => {
+                        if let Err(e) = git_repo.remove_worktree(path, *force) {
+                            println!("❌ Failed to remove worktree {}: {}", branch, e);
+                            outcome.failed += 1;
+                            continue 'entries;
+                        }
+                        outcome.removed += 1;
+                    }
+                    CleanupAction::DeleteBranch { force } => {
+                        if let Err(e) = git_repo.delete_branch(branch, *force) {
+                            println!("⚠️  Removed worktree but failed to delete branch {}: {}", branch, e);
+                            outcome.failed += 1;
+                            continue 'entries;
+                        }
+                    }
+                }
+            }
+            
+            if entry.actions.iter().any(|action| matches!(action, CleanupAction::DeleteBranch { .. })) {
+                println!("✅ Removed worktree and branch: {}", branch);
+            } else {
+                println!("✅ Removed worktree: {} (branch kept)", branch);
+            }
+        }
+        
+        // Prune stale worktree references
+        if let Err(e) = git_repo.prune_worktrees() {
+            log::warn!("Failed to prune worktrees: {}", e);
+        }
+        
+        Ok(outcome)
+    }
+}
+
+/// Selects worktrees for cleanup. Every criterion that is set must hold; unset ones are ignored.
+///
+/// Policies are named in the `[cleanup.policies]` config section and picked with
+/// `warp cleanup --policy <name>`. The built-in modes are policies too.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CleanupPolicy {
+    /// Merged (or not) into the integration branch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merged: Option<bool>,
+    /// Has (or lacks) a remote branch configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_remote: Option<bool>,
+    /// The configured upstream branch was deleted on the remote
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upstream_gone: Option<bool>,
+    /// Has no changes compared to the integration branch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identical: Option<bool>,
+    /// Last commit on the branch is at least this many days old
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub older_than_days: Option<u64>,
+    /// No file in the worktree was modified for at least this many days
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inactive_days: Option<u64>,
+    /// The worktree takes up at least this much disk space
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub larger_than_gb: Option<f64>,
+    /// At least one of these policies must also match
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub any: Vec<CleanupPolicy>,
+}
+
+impl CleanupPolicy {
+    /// Names of the policies that exist without any configuration
+    pub const BUILTIN: [&'static str; 7] = ["all", "merged", "remoteless", "identical", "gone", "stale", "interactive"];
+    
+    /// The policy behind a built-in mode name
+    pub fn builtin(name: &str) -> Option<Self> {
+        let policy = match name {
+            // Interactive mode narrows the selection later
+            "all" | "interactive" => Self::default(),
+            "merged" => Self {
+                merged: Some(true),
+                ..Self::default()
+            },
+            "remoteless" => Self {
+                has_remote: Some(false),
+                ..Self::default()
+            },
+            "identical" => Self {
+                identical: Some(true),
+                ..Self::default()
+            },
+            "gone" => Self {
+                upstream_gone: Some(true),
+                ..Self::default()
+            },
+            "stale" => Self {
+                older_than_days: Some(30),
+                inactive_days: Some(14),
+                ..Self::default()
+            },
+            _ => return None,
+        };
+        Some(policy)
+    }
+    
+    /// Why the policy selects this worktree, or None if it doesn't
+    pub fn evaluate(&self, status: &BranchStatus, facts: &mut WorktreeFacts) -> Result<Option<String>> {
+        let mut reasons = Vec::new();
+        
+        if let Some(merged) = self.merged {
+            if status.is_merged != merged {
+                return Ok(None);
+            }
+            reasons.push(match status.merged_via {
+                Some(method) => format!("merged ({})", method),
+                None => "not merged".to_string(),
+            });
+        }
+        if let Some(has_remote) = self.has_remote {
+            if status.has_remote != has_remote {
+                return Ok(None);
+            }
+            reasons.push(if has_remote { "has a remote branch" } else { "no remote branch" }.to_string());
+        }
+        if let Some(identical) = self.identical {
+            if status.is_identical != identical {
+                return Ok(None);
+            }
+            reasons.push(if identical { "identical to the default branch" } else { "differs from the default branch" }.to_string());
+        }
+        if let Some(gone) = self.upstream_gone {
+            if status.upstream_gone != gone {
+                return Ok(None);
+            }
+            reasons.push(if gone { "upstream branch deleted" } else { "upstream branch exists" }.to_string());
+        }
+        if let Some(days) = self.older_than_days {
+            match facts.last_commit_days()? {
+                Some(age) if age >= days => reasons.push(format!("last commit {} days ago", age)),
+                _ => return Ok(None),
+            }
+        }
+        if let Some(days) = self.inactive_days {
+            match facts.inactive_days()? {
+                Some(idle) if idle >= days => reasons.push(format!("no changes for {} days", idle)),
+                _ => return Ok(None),
+            }
+        }
+        if let Some(gb) = self.larger_than_gb {
+            let bytes = facts.disk_usage()?;
+            if (bytes as f64) < gb * GB {
+                return Ok(None);
+            }
+            reasons.push(format!("uses {:.1} GB", bytes as f64 / GB));
+        }
+        if !self.any.is_empty() {
+            let mut matched = None;
+            for policy in &self.any {
+                if let Some(reason) = policy.evaluate(status, facts)? {
+                    matched = Some(reason);
+                    break;
+                }
+            }
+            match matched {
+                Some(reason) => reasons.push(reason),
+                None => return Ok(None),
+            }
+        }
+        
+        if reasons.is_empty() {
+            // A policy without criteria selects everything; still say what stands out
+            reasons.push(match status.merged_via {
+                Some(method) => format!("merged ({})", method),
+                None if status.upstream_gone => "upstream branch deleted".to_string(),
+                None if !status.has_remote => "no remote branch".to_string(),
+                None => "selected by policy".to_string(),
+            });
+        }
+        Ok(Some(reasons.join(", ")))
+    }
+}
+
+const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+const DAY: u64 = 24 * 60 * 60;
+
+/// Costlier facts about a worktree, looked up only when a policy asks for them
+pub struct WorktreeFacts<'a> {
+    status: &'a BranchStatus,
+    last_commit_days: Option<Option<u64>>,
+    inactive_days: Option<Option<u64>>,
+    disk_usage: Option<u64>,
+}
+
+impl<'a> WorktreeFacts<'a> {
+    pub fn new(status: &'a BranchStatus) -> Self {
+        Self {
+            status,
+            last_commit_days: None,
+            inactive_days: None,
+            disk_usage: None,
+        }
+    }
+    
+    /// Age of the branch's last commit in whole days
+    pub fn last_commit_days(&mut self) -> Result<Option<u64>> {
+        if let Some(days) = self.last_commit_days {
+            return Ok(days);
+        }
+        
+        let output = std::process::Command::new("git")
+            .args(["log", "-1", "--format=%ct", &self.status.branch])
+            .current_dir(&self.status.path)
+            .output()
+            .map_err(|e| anyhow::anyhow!("Failed to read last commit: {}", e))?;
+        let days = String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .map(|timestamp| now_secs().saturating_sub(timestamp) / DAY);
+        
+        self.last_commit_days = Some(days);
+        Ok(days)
+    }
+    
+    /// Days since any non-ignored file in the worktree was modified
+    pub fn inactive_days(&mut self) -> Result<Option<u64>> {
+        if let Some(days) = self.inactive_days {
+            return Ok(days);
+        }
+        
+        let newest = WalkBuilder::new(&self.status.path)
+            .hidden(false)
+            .filter_entry(|entry| entry.file_name() != ".git")
+            .build()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_some_and(|file_type| file_type.is_file()))
+            .filter_map(|entry| entry.metadata().ok()?.modified().ok())
+            .max();
+        let days = newest
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|modified| now_secs().saturating_sub(modified.as_secs()) / DAY);
+        
+        self.inactive_days = Some(days);
+        Ok(days)
+    }
+    
+    /// Bytes used by every file in the worktree, ignored ones included
+    pub fn disk_usage(&mut self) -> Result<u64> {
+        if let Some(bytes) = self.disk_usage {
+            return Ok(bytes);
+        }
+        
+        let bytes = WalkBuilder::new(&self.status.path)
+            .standard_filters(false)
+            .build()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_some_and(|file_type| file_type.is_file()))
+            .filter_map(|entry| entry.metadata().ok())
+            .map(|metadata| metadata.len())
+            .sum();
+        
+        self.disk_usage = Some(bytes);
+        Ok(bytes)
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or(0)
+}
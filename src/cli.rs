@@ -1,6 +1,11 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand, CommandFactory};
 use log::info;
+use crate::git::WorktreeHead;
+use crate::output::OutputFormat;
+use crate::progress::{CancelFlag, Progress, ProgressObserver};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 #[derive(Parser)]
 #[command(
@@ -35,6 +40,10 @@ pub struct Cli {
     /// Auto-confirm operations
     #[arg(long, short = 'y', global = true)]
     pub auto_confirm: bool,
+    
+    /// Output format for ls, cleanup --dry-run, hooks-status and config --show
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
 }
 
 #[derive(Subcommand)]
@@ -58,6 +67,12 @@ pub enum Commands {
         /// Force traditional worktree (skip CoW)
         #[arg(long)]
         no_cow: bool,
+        /// Create the new branch from this revision: a branch, remote branch, tag or SHA
+        #[arg(long, value_name = "REV")]
+        from: Option<String>,
+        /// Check out the branch argument, a tag or SHA, as a detached HEAD
+        #[arg(long, conflicts_with = "from")]
+        detach: bool,
     },
     
     /// List all worktrees
@@ -66,13 +81,20 @@ pub enum Commands {
         /// Show debug information
         #[arg(long)]
         debug: bool,
+        
+        /// Show upstream, ahead/behind, last commit and dirty files
+        #[arg(short, long)]
+        long: bool,
     },
     
     /// Clean up worktrees
     Cleanup {
-        /// Cleanup mode: all, merged, remoteless, interactive
+        /// Cleanup mode: all, merged, remoteless, gone, interactive
         #[arg(long, default_value = "merged")]
         mode: String,
+        /// Cleanup policy: a built-in one (identical, stale) or one named in the config
+        #[arg(long, conflicts_with = "mode")]
+        policy: Option<String>,
         /// Force removal even with uncommitted changes
         #[arg(long)]
         force: bool,
@@ -85,6 +107,48 @@ pub enum Commands {
         /// Interactive mode
         #[arg(long, short)]
         interactive: bool,
+        /// Also clean up locked worktrees
+        #[arg(long)]
+        include_locked: bool,
+    },
+    
+    /// Move a worktree, optionally renaming its branch
+    #[command(alias = "move")]
+    Mv {
+        /// Branch checked out in the worktree
+        branch: String,
+        /// New location (defaults to the usual path for the branch's new name)
+        path: Option<String>,
+        /// Rename the branch as well
+        #[arg(long)]
+        rename: Option<String>,
+    },
+    
+    /// Lock a worktree so cleanup never removes it
+    Lock {
+        /// Branch checked out in the worktree
+        branch: String,
+        /// Why the worktree is locked
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    
+    /// Unlock a worktree
+    Unlock {
+        /// Branch checked out in the worktree
+        branch: String,
+    },
+    
+    /// Rewrite paths copied from another worktree, or undo the last rewrite
+    Rewrite {
+        /// Branch checked out in the worktree (defaults to the current one)
+        branch: Option<String>,
+        /// Worktree the paths were copied from (defaults to the main worktree)
+        #[arg(long, value_name = "PATH", conflicts_with = "undo")]
+        from: Option<String>,
+        /// Restore the files the journaled rewrites changed
+        #[arg(long)]
+        undo: bool,
     },
     
     /// Configure git-warp settings
@@ -137,7 +201,7 @@ impl Cli {
             None => {
                 if let Some(branch) = &self.branch {
                     // Dynamic branch command - same as switch
-                    self.handle_switch(branch, None, None, false, false, false)
+                    self.handle_switch(WorktreeHead::Branch { name: branch, from: None }, None, None, false, false, false)
                 } else {
                     // No command or branch - show help
                     let mut cmd = Self::command();
@@ -150,13 +214,22 @@ impl Cli {
     
     fn handle_command(&self, command: &Commands) -> Result<()> {
         match command {
-            Commands::Switch { branch, path, init, latest, waiting, no_cow } => {
-                self.handle_switch(branch, path.as_deref(), init.as_deref(), *latest, *waiting, *no_cow)
+            Commands::Switch { branch, path, init, latest, waiting, no_cow, from, detach } => {
+                let head = if *detach {
+                    WorktreeHead::Detached(branch)
+                } else {
+                    WorktreeHead::Branch { name: branch, from: from.as_deref() }
+                };
+                self.handle_switch(head, path.as_deref(), init.as_deref(), *latest, *waiting, *no_cow)
             }
-            Commands::Ls { debug } => self.handle_ls(*debug),
-            Commands::Cleanup { mode, force, kill, no_kill, interactive } => {
-                self.handle_cleanup(mode, *force, *kill, *no_kill, *interactive)
+            Commands::Ls { debug, long } => self.handle_ls(*debug, *long),
+            Commands::Cleanup { mode, policy, force, kill, no_kill, interactive, include_locked } => {
+                self.handle_cleanup(policy.as_deref().unwrap_or(mode), *force, *kill, *no_kill, *interactive, *include_locked)
             }
+            Commands::Mv { branch, path, rename } => self.handle_mv(branch, path.as_deref(), rename.as_deref()),
+            Commands::Lock { branch, reason } => self.handle_lock(branch, reason.as_deref()),
+            Commands::Unlock { branch } => self.handle_unlock(branch),
+            Commands::Rewrite { branch, from, undo } => self.handle_rewrite(branch.as_deref(), from.as_deref(), *undo),
             Commands::Config { show, edit } => self.handle_config(*show, *edit),
             Commands::Agents => self.handle_agents(),
             Commands::HooksInstall { level } => self.handle_hooks_install(level.as_deref()),
@@ -166,18 +239,32 @@ impl Cli {
         }
     }
     
-    fn handle_switch(&self, branch: &str, path: Option<&str>, _init: Option<&str>, _latest: bool, _waiting: bool, no_cow: bool) -> Result<()> {
+    fn handle_switch(&self, head: WorktreeHead<'_>, path: Option<&str>, _init: Option<&str>, _latest: bool, _waiting: bool, no_cow: bool) -> Result<()> {
+        use crate::config::ConfigManager;
         use crate::git::GitRepository;
-        use crate::cow;
-        use crate::rewrite::PathRewriter;
+        use crate::cow::{self, CloneStrategy};
+        use crate::layout::WorktreeLayout;
+        use crate::progress;
         use crate::terminal::{TerminalManager, TerminalMode};
         use std::path::PathBuf;
         
-        info!("Switching to branch: {}", branch);
+        let branch = head.name();
+        info!("Switching to: {}", branch);
         
         // Find the Git repository
         let git_repo = GitRepository::find()
             .map_err(|_| anyhow::anyhow!("Not in a Git repository"))?;
+        let config = ConfigManager::for_repo(git_repo.main_root_path())?.config;
+        let git_repo = git_repo.with_layout(WorktreeLayout::from_config(&config)?);
+        
+        // A start point only makes sense for a branch that doesn't exist yet
+        if let WorktreeHead::Branch { name, from: Some(from) } = head {
+            git_repo.check_start_point(name, from)?;
+        }
+        let tracks_remote = match head {
+            WorktreeHead::Branch { name, from: None } => !git_repo.branch_exists(name)? && git_repo.remote_branch_exists(name)?,
+            _ => false,
+        };
             
         // Determine worktree path
         let worktree_path = if let Some(path) = path {
@@ -186,10 +273,31 @@ impl Cli {
             git_repo.get_worktree_path(branch)
         };
         
+        // Choose creation method based on filesystem capabilities and user preference
+        // The worktree path doesn't exist yet, so probe the filesystem we clone from; dry runs write nothing
+        let capabilities = match self.dry_run {
+            true => cow::guess_cow_support(git_repo.root_path()),
+            false => cow::is_cow_supported(git_repo.root_path()),
+        };
+        let strategy = capabilities
+            .map(|capabilities| capabilities.best_strategy())
+            .unwrap_or(CloneStrategy::Copy);
+        let use_clone = !no_cow && strategy != CloneStrategy::Copy;
+        
         if self.dry_run {
-            println!("Would switch to branch '{}' at path: {}", branch, worktree_path.display());
-            if !no_cow && cow::is_cow_supported(&worktree_path).unwrap_or(false) {
-                println!("Would use Copy-on-Write for fast worktree creation");
+            match head {
+                WorktreeHead::Detached(rev) => println!("Would check out '{}' detached at path: {}", rev, worktree_path.display()),
+                _ => println!("Would switch to branch '{}' at path: {}", branch, worktree_path.display()),
+            }
+            if let WorktreeHead::Branch { from: Some(from), .. } = head {
+                println!("Would create the branch from '{}'", from);
+            } else if tracks_remote {
+                println!("Would create the branch tracking 'origin/{}'", branch);
+            }
+            if use_clone && strategy.is_cow() {
+                println!("Would use Copy-on-Write ({}) for fast worktree creation", strategy);
+            } else if use_clone {
+                println!("Would hardlink dependency trees for fast worktree creation");
             } else {
                 println!("Would use traditional Git worktree creation");
             }
@@ -200,54 +308,35 @@ impl Cli {
         if worktree_path.exists() {
             println!("📁 Worktree already exists at: {}", worktree_path.display());
         } else {
-            println!("🚀 Creating worktree for branch '{}'", branch);
-            
-            // Choose creation method based on CoW support and user preference
-            let use_cow = !no_cow && cow::is_cow_supported(&worktree_path).unwrap_or(false);
+            match head {
+                WorktreeHead::Detached(rev) => println!("🚀 Creating detached worktree at '{}'", rev),
+                WorktreeHead::Branch { from: Some(from), .. } => println!("🚀 Creating worktree for branch '{}' from '{}'", branch, from),
+                _ if tracks_remote => println!("🚀 Creating worktree for branch '{}' tracking 'origin/{}'", branch, branch),
+                _ => println!("🚀 Creating worktree for branch '{}'", branch),
+            }
             
-            if use_cow {
-                println!("⚡ Using Copy-on-Write for instant creation...");
-                
-                // Create worktree using traditional method first
-                git_repo.create_worktree_and_branch(branch, &worktree_path, None)?;
-                
-                // If we have existing worktrees, try CoW enhancement
-                let worktrees = git_repo.list_worktrees()?;
-                if let Some(main_worktree) = worktrees.iter().find(|wt| wt.is_primary) {
-                    // Remove the traditionally created worktree files
-                    if worktree_path.exists() {
-                        std::fs::remove_dir_all(&worktree_path)?;
-                    }
-                    
-                    // Clone using CoW
-                    if let Err(e) = cow::clone_directory(&main_worktree.path, &worktree_path) {
-                        log::warn!("CoW failed, falling back to traditional method: {}", e);
-                        // Recreate using traditional method
-                        git_repo.create_worktree_and_branch(branch, &worktree_path, None)?;
-                    } else {
-                        // Rewrite paths in the CoW copy
-                        let rewriter = PathRewriter::new(&main_worktree.path, &worktree_path);
-                        if let Err(e) = rewriter.rewrite_paths() {
-                            log::warn!("Path rewriting failed: {}", e);
-                        }
-                        
-                        // Switch to the correct branch
-                        use std::process::Command;
-                        let output = Command::new("git")
-                            .args(&["checkout", branch])
-                            .current_dir(&worktree_path)
-                            .output()?;
-                            
-                        if !output.status.success() {
-                            let error = String::from_utf8_lossy(&output.stderr);
-                            log::warn!("Failed to checkout branch in CoW worktree: {}", error);
-                        }
-                    }
-                }
+            // Every step is recorded so a failure part way through leaves nothing behind
+            let mut transaction = git_repo.begin_worktree_creation();
+            let result = if use_clone {
+                self.create_cloned_worktree(&mut transaction, &git_repo, head, &worktree_path, strategy, &config)
             } else {
                 println!("📦 Using traditional Git worktree creation...");
-                git_repo.create_worktree_and_branch(branch, &worktree_path, None)?;
+                transaction.create_worktree(head, &worktree_path)
+            };
+            
+            if let Err(e) = result {
+                let report = transaction.rollback();
+                if progress::is_cancelled(&e) {
+                    println!("🛑 Cancelled, removed partial worktree at {}", worktree_path.display());
+                } else {
+                    println!("❌ Worktree creation failed, rolled back {} step(s)", report.undone.len());
+                }
+                for (step, reason) in &report.failed {
+                    println!("⚠️  Could not undo {}: {}", step, reason);
+                }
+                return Err(e);
             }
+            transaction.commit();
             
             println!("✅ Worktree created successfully!");
         }
@@ -274,7 +363,86 @@ impl Cli {
         Ok(())
     }
     
-    fn handle_ls(&self, debug: bool) -> Result<()> {
+    /// Create a worktree by cloning the primary checkout, recording each step in `transaction`
+    fn create_cloned_worktree(
+        &self,
+        transaction: &mut crate::git::WorktreeTransaction<'_>,
+        git_repo: &crate::git::GitRepository,
+        head: WorktreeHead<'_>,
+        worktree_path: &std::path::Path,
+        strategy: crate::cow::CloneStrategy,
+        config: &crate::config::Config,
+    ) -> Result<()> {
+        use crate::progress;
+        use crate::rewrite::PathRewriter;
+        
+        if strategy.is_cow() {
+            println!("⚡ Using Copy-on-Write for instant creation...");
+        } else {
+            println!("🔗 Using hardlinks for dependency trees...");
+        }
+        
+        // A bare repository has no checkout to clone
+        let worktrees = git_repo.list_worktrees()?;
+        let Some(main_worktree) = worktrees.iter().find(|wt| wt.is_main && !wt.is_bare) else {
+            return transaction.create_worktree(head, worktree_path);
+        };
+        
+        // Clone using CoW; Ctrl-C cancels and the caller rolls back the partial worktree
+        let cancel = CancelFlag::new();
+        let _ctrl_c = cancel.cancel_on_ctrl_c()?;
+        let progress = ProgressBar::new("⚡ Cloning", cancel.clone());
+        let result = transaction.clone_worktree(
+            head,
+            worktree_path,
+            main_worktree,
+            &config.cow.clone_spec(),
+            strategy,
+            &progress,
+        );
+        progress.finish();
+        
+        let report = match result {
+            Ok(report) => report,
+            Err(e) if progress::is_cancelled(&e) => return Err(e),
+            Err(e) => {
+                log::warn!("CoW failed, falling back to traditional method: {}", e);
+                // Undo the partial clone and check the branch out the usual way
+                for (step, reason) in transaction.rollback().failed {
+                    println!("⚠️  Could not undo {}: {}", step, reason);
+                }
+                return transaction.create_worktree(head, worktree_path);
+            }
+        };
+        
+        log::info!(
+            "Cloned {} files, linked {}, copied {}, excluded {}",
+            report.cloned, report.linked, report.copied, report.excluded.len()
+        );
+        for skipped in &report.skipped {
+            println!("⚠️  Skipped {}: {}", skipped.path.display(), skipped.reason);
+        }
+        
+        // Rewrite paths in the CoW copy
+        let rewriter = PathRewriter::new(&main_worktree.path, worktree_path).with_scope(config.cow.rewrite_scope);
+        let progress = ProgressBar::new("✏️  Rewriting paths", cancel.clone());
+        let result = rewriter.rewrite_paths_with_progress(&progress);
+        progress.finish();
+        let rewrites = result?;
+        
+        log::info!("Rewrote paths in {} files", rewrites.rewritten.len());
+        for (path, format) in &rewrites.binaries {
+            println!("⚠️  Not rewriting {} ({}): it still refers to {}", path.display(), format, main_worktree.path.display());
+        }
+        for (path, target) in &rewrites.dangling {
+            println!("⚠️  Dangling symlink {} -> {}", path.display(), target.display());
+        }
+        
+        Ok(())
+    }
+    
+    fn handle_ls(&self, debug: bool, long: bool) -> Result<()> {
+        use crate::config::ConfigManager;
         use crate::git::GitRepository;
         
         info!("Listing worktrees");
@@ -289,27 +457,91 @@ impl Cli {
         
         let worktrees = git_repo.list_worktrees()?;
         
-        if worktrees.is_empty() {
+        if worktrees.is_empty() && self.format.is_text() {
             println!("📭 No worktrees found");
             return Ok(());
         }
         
-        println!("📁 Git Worktrees:");
-        println!();
+        let details = if long {
+            let config = ConfigManager::for_repo(git_repo.main_root_path())?.config;
+            git_repo.with_config(&config.git).worktree_details(&worktrees)
+        } else {
+            Vec::new()
+        };
         
+        if !self.format.is_text() {
+            use crate::output::WorktreeEntry;
+            
+            let entries: Vec<_> = worktrees
+                .iter()
+                .enumerate()
+                .map(|(i, worktree)| WorktreeEntry {
+                    worktree,
+                    details: details.get(i),
+                })
+                .collect();
+            return self.print_output("worktrees", &entries, "worktree", &entries);
+        }
+        
+        let mut rows = Vec::new();
+        if long {
+            rows.push(["BRANCH", "UPSTREAM", "SYNC", "DEFAULT", "DIRTY", "AGE", "AUTHOR", "LAST COMMIT", "PATH"]
+                .map(str::to_string)
+                .to_vec());
+        }
         for (i, worktree) in worktrees.iter().enumerate() {
-            let status_icon = if worktree.is_primary { "🏠" } else { "🌿" };
-            let branch_display = if worktree.branch.is_empty() {
-                format!("(detached HEAD: {})", &worktree.head[..8])
+            let branch_display = if worktree.is_bare {
+                "(bare)".to_string()
+            } else if worktree.branch.is_empty() {
+                format!("(detached HEAD: {})", short_head(&worktree.head))
             } else {
                 worktree.branch.clone()
             };
             
-            println!("{}  {} {}", status_icon, branch_display, worktree.path.display());
+            let mut row = vec![branch_display];
+            if let Some(details) = details.get(i) {
+                let now = chrono::Utc::now().timestamp();
+                let commit = details.last_commit.as_ref();
+                row.extend([
+                    details.upstream.clone().unwrap_or_else(|| "-".to_string()),
+                    format_ahead_behind(details.upstream_status),
+                    format_ahead_behind(details.default_status),
+                    if details.dirty_files > 0 { details.dirty_files.to_string() } else { "-".to_string() },
+                    commit.map_or_else(|| "-".to_string(), |commit| format_age(now - commit.timestamp)),
+                    commit.map_or_else(|| "-".to_string(), |commit| commit.author.clone()),
+                    commit.map_or_else(|| "-".to_string(), |commit| truncate_chars(&commit.subject, 40)),
+                ]);
+            }
+            row.push(worktree.path.display().to_string());
+            rows.push(row);
+        }
+        
+        println!("📁 Git Worktrees:");
+        println!();
+        
+        let mut lines = format_table(&rows).into_iter();
+        if long && let Some(header) = lines.next() {
+            println!("    {}", header);
+        }
+        
+        for ((i, worktree), line) in worktrees.iter().enumerate().zip(lines) {
+            let status_icon = if worktree.is_main { "🏠" } else { "🌿" };
+            
+            let mut notes = String::new();
+            if let Some(reason) = &worktree.locked {
+                notes.push_str(&format!(" 🔒 locked{}", format_lock_reason(reason)));
+            }
+            if let Some(reason) = &worktree.prunable {
+                notes.push_str(&format!(" ⚠️  prunable: {}", reason));
+            }
+            
+            println!("{}  {}{}", status_icon, line, notes);
             
             if debug {
                 println!("     HEAD: {}", worktree.head);
-                println!("     Primary: {}", worktree.is_primary);
+                println!("     Main: {}", worktree.is_main);
+                println!("     Bare: {}", worktree.is_bare);
+                println!("     Detached: {}", worktree.is_detached);
                 if i < worktrees.len() - 1 {
                     println!();
                 }
@@ -322,7 +554,9 @@ impl Cli {
         Ok(())
     }
     
-    fn handle_cleanup(&self, mode: &str, force: bool, kill: bool, no_kill: bool, interactive: bool) -> Result<()> {
+    fn handle_cleanup(&self, mode: &str, force: bool, kill: bool, no_kill: bool, interactive: bool, include_locked: bool) -> Result<()> {
+        use crate::cleanup::{CleanupOptions, CleanupPlan};
+        use crate::config::ConfigManager;
         use crate::git::GitRepository;
         use crate::process::ProcessManager;
         
@@ -330,65 +564,62 @@ impl Cli {
         
         let git_repo = GitRepository::find()
             .map_err(|_| anyhow::anyhow!("Not in a Git repository"))?;
+        let config = ConfigManager::for_repo(git_repo.main_root_path())?.config;
+        let git_repo = git_repo.with_config(&config.git);
         let mut process_manager = ProcessManager::new();
-            
-        if self.dry_run {
-            println!("Would cleanup worktrees with mode: {}", mode);
-            return Ok(());
-        }
         
-        // Fetch latest changes for accurate analysis
-        println!("🔄 Fetching latest changes...");
-        if !git_repo.fetch_branches()? {
-            println!("⚠️  Fetch failed, analysis may be outdated");
+        if !self.format.is_text() && !self.dry_run {
+            anyhow::bail!("Machine-readable cleanup output needs --dry-run");
         }
         
-        let worktrees = git_repo.list_worktrees()?;
-        let branch_statuses = git_repo.analyze_branches_for_cleanup(&worktrees)?;
-        
-        if branch_statuses.is_empty() {
-            println!("✨ No worktrees to clean up");
-            return Ok(());
+        // A dry run changes nothing, not even the remote-tracking branches
+        if !self.dry_run {
+            println!("🔄 Fetching latest changes...");
+            if !git_repo.fetch_branches()? {
+                println!("⚠️  Fetch failed, analysis may be outdated");
+            }
         }
         
-        let mut candidates = Vec::new();
+        let worktrees = git_repo.list_worktrees()?;
+        let options = CleanupOptions {
+            mode: mode.to_string(),
+            policy: config.cleanup.policy(mode)?,
+            force,
+            kill,
+            no_kill,
+            include_locked,
+        };
+        let mut plan = CleanupPlan::build(&git_repo, &worktrees, &options, &mut process_manager)?;
         
-        // Filter based on mode
-        for status in &branch_statuses {
-            let should_include = match mode {
-                "all" => true,
-                "merged" => status.is_merged,
-                "remoteless" => !status.has_remote,
-                "interactive" => true, // Will be filtered in interactive mode
-                _ => {
-                    println!("❌ Unknown cleanup mode: {}", mode);
-                    return Ok(());
-                }
-            };
-            
-            if should_include && (!status.has_uncommitted_changes || force) {
-                candidates.push(status);
+        match self.format {
+            OutputFormat::Json => return self.print_output("cleanup_plan", &plan, "cleanup_entry", &plan.entries),
+            OutputFormat::Ndjson => {
+                use crate::output::to_ndjson;
+                
+                print!("{}", to_ndjson("cleanup_entry", &plan.entries)?);
+                print!("{}", to_ndjson("cleanup_skipped", &plan.skipped)?);
+                print!("{}", to_ndjson("cleanup_stale", &plan.stale)?);
+                return Ok(());
             }
+            OutputFormat::Text => print_cleanup_plan(&plan),
         }
         
-        if candidates.is_empty() {
-            println!("✨ No worktrees match cleanup criteria for mode: {}", mode);
+        if self.dry_run {
+            println!();
+            println!("🔍 Dry run, nothing was changed");
             return Ok(());
         }
         
-        // Show what would be cleaned up
-        println!("🧹 Cleanup candidates:");
-        for candidate in &candidates {
-            let uncommitted = if candidate.has_uncommitted_changes { " (⚠️  uncommitted)" } else { "" };
-            let merged = if candidate.is_merged { " [merged]" } else { "" };
-            println!("  • {} at {}{}{}", candidate.branch, candidate.path.display(), merged, uncommitted);
+        if plan.is_empty() {
+            return Ok(());
         }
         
-        if interactive {
+        // Only stale references means there's nothing to pick from
+        if interactive && !plan.entries.is_empty() {
             use crate::tui::CleanupTui;
             
             println!("\n🤖 Starting interactive cleanup...");
-            let cleanup_tui = CleanupTui::new();
+            let cleanup_tui = CleanupTui::new().include_locked(include_locked);
             let selected_branches = cleanup_tui.run()?;
             
             if selected_branches.is_empty() {
@@ -396,15 +627,15 @@ impl Cli {
                 return Ok(());
             }
             
-            // Update candidates to only include selected branches
-            candidates.retain(|c| selected_branches.contains(&c.branch));
+            // Update the plan to only include selected branches
+            plan.retain_branches(&selected_branches);
             
-            if candidates.is_empty() {
+            if plan.entries.is_empty() {
                 println!("✨ No matching candidates found");
                 return Ok(());
             }
             
-            println!("✅ Selected {} branches for cleanup", selected_branches.len());
+            println!("✅ Selected {} branches for cleanup", plan.entries.len());
         }
         
         // Confirm unless auto-confirmed
@@ -422,89 +653,200 @@ impl Cli {
             }
         }
         
-        // Perform cleanup
-        let mut cleaned = 0;
-        let mut failed = 0;
+        let outcome = plan.execute(&git_repo, &mut process_manager, self.auto_confirm)?;
         
-        for candidate in candidates {
-            println!("🗑️  Removing worktree: {}", candidate.branch);
-            
-            // Handle process management
-            if kill && !no_kill {
-                println!("🔍 Checking for processes in worktree...");
-                match process_manager.find_processes_in_directory(&candidate.path) {
-                    Ok(processes) if !processes.is_empty() => {
-                        println!("⚠️  Found {} processes in worktree", processes.len());
-                        if !process_manager.terminate_processes(&processes, self.auto_confirm)? {
-                            println!("❌ Failed to terminate processes, skipping worktree");
-                            failed += 1;
-                            continue;
-                        }
-                    }
-                    Ok(_) => {
-                        println!("✅ No processes found in worktree");
-                    }
-                    Err(e) => {
-                        println!("⚠️  Failed to check processes: {}", e);
-                    }
-                }
-            } else if !no_kill {
-                // Default behavior - check for processes but don't auto-kill
-                match process_manager.has_processes_in_directory(&candidate.path) {
-                    Ok(true) => {
-                        if force {
-                            println!("⚠️  Processes found but --force specified, continuing anyway");
-                        } else {
-                            println!("❌ Processes found in worktree, use --kill to terminate them or --force to ignore");
-                            failed += 1;
-                            continue;
-                        }
-                    }
-                    Ok(false) => {
-                        println!("✅ No processes found in worktree");
-                    }
-                    Err(e) => {
-                        println!("⚠️  Failed to check processes: {}", e);
-                    }
+        println!();
+        println!("📊 Cleanup complete: {} removed, {} failed", outcome.removed, outcome.failed);
+        
+        Ok(())
+    }
+    
+    fn handle_mv(&self, branch: &str, path: Option<&str>, rename: Option<&str>) -> Result<()> {
+        use crate::config::ConfigManager;
+        use crate::git::GitRepository;
+        use crate::layout::WorktreeLayout;
+        use std::path::PathBuf;
+        
+        info!("Moving worktree for branch: {}", branch);
+        
+        let git_repo = GitRepository::find()
+            .map_err(|_| anyhow::anyhow!("Not in a Git repository"))?;
+        let config = ConfigManager::for_repo(git_repo.main_root_path())?.config;
+        let git_repo = git_repo.with_layout(WorktreeLayout::from_config(&config)?);
+        let worktree = git_repo.find_worktree(branch)?;
+        
+        let new_path = match (path, rename) {
+            (Some(path), _) => PathBuf::from(path),
+            (None, Some(new_branch)) => git_repo.get_worktree_path(new_branch),
+            (None, None) => return Err(anyhow::anyhow!("Give a new path, --rename, or both")),
+        };
+        
+        if self.dry_run {
+            println!("Would move worktree from {} to {}", worktree.path.display(), new_path.display());
+            if let Some(new_branch) = rename {
+                println!("Would rename branch '{}' to '{}'", branch, new_branch);
+            }
+            return Ok(());
+        }
+        
+        git_repo.move_worktree(&worktree.path, &new_path, rename)?;
+        
+        println!("📦 Moved worktree to: {}", new_path.display());
+        if let Some(new_branch) = rename {
+            println!("🏷️  Renamed branch '{}' to '{}'", branch, new_branch);
+        }
+        
+        Ok(())
+    }
+    
+    fn handle_lock(&self, branch: &str, reason: Option<&str>) -> Result<()> {
+        use crate::git::GitRepository;
+        
+        info!("Locking worktree for branch: {}", branch);
+        
+        let git_repo = GitRepository::find()
+            .map_err(|_| anyhow::anyhow!("Not in a Git repository"))?;
+        let worktree = git_repo.find_worktree(branch)?;
+        
+        if worktree.is_main {
+            return Err(anyhow::anyhow!("The main worktree can't be locked"));
+        }
+        
+        if self.dry_run {
+            println!("Would lock worktree at: {}", worktree.path.display());
+            return Ok(());
+        }
+        
+        if let Some(existing) = &worktree.locked {
+            println!("🔒 Worktree '{}' is already locked{}", branch, format_lock_reason(existing));
+            return Ok(());
+        }
+        
+        git_repo.lock_worktree(&worktree.path, reason)?;
+        println!("🔒 Locked worktree '{}'{}", branch, format_lock_reason(reason.unwrap_or("")));
+        
+        Ok(())
+    }
+    
+    fn handle_unlock(&self, branch: &str) -> Result<()> {
+        use crate::git::GitRepository;
+        
+        info!("Unlocking worktree for branch: {}", branch);
+        
+        let git_repo = GitRepository::find()
+            .map_err(|_| anyhow::anyhow!("Not in a Git repository"))?;
+        let worktree = git_repo.find_worktree(branch)?;
+        
+        if self.dry_run {
+            println!("Would unlock worktree at: {}", worktree.path.display());
+            return Ok(());
+        }
+        
+        if worktree.locked.is_none() {
+            println!("🔓 Worktree '{}' is not locked", branch);
+            return Ok(());
+        }
+        
+        git_repo.unlock_worktree(&worktree.path)?;
+        println!("🔓 Unlocked worktree '{}'", branch);
+        
+        Ok(())
+    }
+    
+    fn handle_rewrite(&self, branch: Option<&str>, from: Option<&str>, undo: bool) -> Result<()> {
+        use crate::config::ConfigManager;
+        use crate::git::GitRepository;
+        use crate::journal::{self, Journal};
+        use crate::rewrite::PathRewriter;
+        use std::path::PathBuf;
+        
+        let git_repo = GitRepository::find()
+            .map_err(|_| anyhow::anyhow!("Not in a Git repository"))?;
+        let worktree_path = match branch {
+            Some(branch) => git_repo.find_worktree(branch)?.path,
+            None => git_repo.root_path().to_path_buf(),
+        };
+        
+        if undo {
+            info!("Undoing path rewrites in: {}", worktree_path.display());
+            if self.dry_run {
+                let journal = Journal::load(&worktree_path)?
+                    .ok_or_else(|| anyhow::anyhow!("No rewrite journal for {}", worktree_path.display()))?;
+                for entry in &journal.entries {
+                    println!("Would restore {}", entry.path().display());
                 }
+                return Ok(());
             }
             
-            // Remove worktree
-            match git_repo.remove_worktree(&candidate.path) {
-                Ok(()) => {
-                    // Try to delete the branch if it's safe
-                    if candidate.is_merged || force {
-                        match git_repo.delete_branch(&candidate.branch, force) {
-                            Ok(()) => println!("✅ Removed worktree and branch: {}", candidate.branch),
-                            Err(e) => {
-                                println!("⚠️  Removed worktree but failed to delete branch {}: {}", candidate.branch, e);
-                            }
-                        }
-                    } else {
-                        println!("✅ Removed worktree: {} (branch kept)", candidate.branch);
-                    }
-                    cleaned += 1;
-                }
-                Err(e) => {
-                    println!("❌ Failed to remove worktree {}: {}", candidate.branch, e);
-                    failed += 1;
-                }
+            let report = journal::undo(&worktree_path)?;
+            for (path, reason) in &report.skipped {
+                println!("⚠️  Not restoring {}: {}", path.display(), reason);
             }
+            println!("↩️  Restored {} file(s) in {}", report.restored.len(), worktree_path.display());
+            return Ok(());
         }
         
-        // Prune stale worktree references
-        if let Err(e) = git_repo.prune_worktrees() {
-            log::warn!("Failed to prune worktrees: {}", e);
+        let src_path = match from {
+            Some(from) => PathBuf::from(from),
+            None => git_repo.list_worktrees()?
+                .into_iter()
+                .find(|wt| wt.is_main && !wt.is_bare)
+                .map(|wt| wt.path)
+                .ok_or_else(|| anyhow::anyhow!("No main worktree to rewrite paths from"))?,
+        };
+        if src_path == worktree_path {
+            return Err(anyhow::anyhow!("{} would be rewritten into itself; pass --from", worktree_path.display()));
         }
+        info!("Rewriting paths from {} in: {}", src_path.display(), worktree_path.display());
         
-        println!();
-        println!("📊 Cleanup complete: {} removed, {} failed", cleaned, failed);
+        let config = ConfigManager::for_repo(git_repo.main_root_path())?.config;
+        let rewriter = PathRewriter::new(&src_path, &worktree_path)
+            .with_scope(config.cow.rewrite_scope)
+            .dry_run(self.dry_run);
+        
+        if self.dry_run {
+            let report = rewriter.rewrite_paths_with_progress(&crate::progress::NoProgress)?;
+            print!("{}", report.diff);
+            println!("Would rewrite paths in {} file(s)", report.rewritten.len());
+            return Ok(());
+        }
+        
+        let cancel = CancelFlag::new();
+        let _ctrl_c = cancel.cancel_on_ctrl_c()?;
+        let progress = ProgressBar::new("✏️  Rewriting paths", cancel);
+        let result = rewriter.rewrite_paths_with_progress(&progress);
+        progress.finish();
+        let report = result?;
+        
+        for (path, format) in &report.binaries {
+            println!("⚠️  Not rewriting {} ({}): it still refers to {}", path.display(), format, src_path.display());
+        }
+        for (path, target) in &report.dangling {
+            println!("⚠️  Dangling symlink {} -> {}", path.display(), target.display());
+        }
+        println!("✏️  Rewrote paths in {} file(s)", report.rewritten.len());
+        if let Some(journal) = &report.journal {
+            println!("📓 Journal: {} (undo with 'warp rewrite --undo')", journal.display());
+        }
         
         Ok(())
     }
     
+    /// Print `data` as one JSON document, or `items` as one NDJSON line each
+    fn print_output<T: serde::Serialize, I: serde::Serialize>(&self, kind: &str, data: &T, item_kind: &str, items: &[I]) -> Result<()> {
+        use crate::output;
+        
+        match self.format {
+            OutputFormat::Json => println!("{}", output::to_json(kind, data)?),
+            OutputFormat::Ndjson => print!("{}", output::to_ndjson(item_kind, items)?),
+            OutputFormat::Text => unreachable!("text output is printed by each command"),
+        }
+        Ok(())
+    }
+    
     fn handle_config(&self, show: bool, edit: bool) -> Result<()> {
-        use crate::config::ConfigManager;
+        use crate::config::{ConfigManager, PROJECT_CONFIG_FILE};
+        use crate::git::GitRepository;
         
         info!("Config command");
         if self.dry_run {
@@ -512,12 +854,34 @@ impl Cli {
             return Ok(());
         }
         
-        let config_manager = ConfigManager::new()?;
+        // Inside a repository, show what commands there actually use
+        let repo_root = GitRepository::find().ok().map(|repo| repo.main_root_path());
+        let config_manager = match &repo_root {
+            Some(root) => ConfigManager::for_repo(root)?,
+            None => ConfigManager::new()?,
+        };
+        let project_config_path = repo_root
+            .map(|root| root.join(PROJECT_CONFIG_FILE))
+            .filter(|path| path.is_file());
+        
+        if show && !self.format.is_text() {
+            use crate::output::ConfigReport;
+            
+            let report = ConfigReport {
+                config_path: config_manager.config_path().to_path_buf(),
+                project_config_path,
+                config: config_manager.get(),
+            };
+            return self.print_output("config", &report, "config", &[&report]);
+        }
         
         if show {
             // Show current configuration
             println!("📋 Current Git-Warp Configuration:");
             println!("Config file: {}", config_manager.config_path().display());
+            if let Some(path) = &project_config_path {
+                println!("Project overrides: {}", path.display());
+            }
             println!();
             
             let config = config_manager.get();
@@ -529,10 +893,16 @@ impl Cli {
             if let Some(path) = &config.worktrees_path {
                 println!("  Worktrees path: {}", path.display());
             }
+            println!("  Worktree template: {}", config.worktree_template);
             println!();
             
             println!("🔧 Git Settings:");
-            println!("  Default branch: {}", config.git.default_branch);
+            if config.git.default_branch.is_empty() {
+                println!("  Default branch: (from origin/HEAD, then main/master)");
+            } else {
+                println!("  Default branch: {}", config.git.default_branch);
+            }
+            println!("  Compare with remote: {}", config.git.compare_remote);
             println!("  Auto-fetch: {}", config.git.auto_fetch);
             println!("  Auto-prune: {}", config.git.auto_prune);
             println!();
@@ -556,6 +926,24 @@ impl Cli {
             println!("  Refresh rate: {}ms", config.agent.refresh_rate);
             println!("  Max activities: {}", config.agent.max_activities);
             println!("  Claude hooks: {}", config.agent.claude_hooks);
+            println!();
+            
+            println!("⚡ CoW Clone Settings:");
+            if !config.cow.include.is_empty() {
+                println!("  Include: {:?}", config.cow.include);
+            }
+            println!("  Exclude: {:?}", config.cow.exclude);
+            if !config.cow.hardlink.is_empty() {
+                println!("  Hardlink: {:?}", config.cow.hardlink);
+            }
+            
+            if !config.cleanup.policies.is_empty() {
+                println!();
+                println!("🧹 Cleanup Policies:");
+                for (name, policy) in &config.cleanup.policies {
+                    println!("  {}: {}", name, serde_json::to_string(policy)?);
+                }
+            }
             
         } else if edit {
             // Interactive config editing (for now, show sample config)
@@ -610,8 +998,8 @@ impl Cli {
             return Ok(());
         }
         
-        // Find the Git repository
-        let git_repo = GitRepository::find()
+        // Make sure we're inside a Git repository
+        GitRepository::find()
             .map_err(|_| anyhow::anyhow!("Not in a Git repository"))?;
             
         println!("🤖 Starting Agent Activity Monitor...");
@@ -656,6 +1044,10 @@ impl Cli {
         use crate::hooks::HooksManager;
         
         info!("Checking hooks status");
+        if !self.format.is_text() {
+            let status = HooksManager::hooks_status()?;
+            return self.print_output("hooks_status", &status, "hooks_settings", &status.settings);
+        }
         HooksManager::show_hooks_status()
     }
     
@@ -664,4 +1056,251 @@ impl Cli {
         println!("🚧 Shell config not yet implemented");
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Single-line progress bar on stderr for clones and path rewriting
+struct ProgressBar {
+    label: &'static str,
+    cancel: CancelFlag,
+    enabled: bool,
+    last_draw: Mutex<Option<Instant>>,
+}
+
+impl ProgressBar {
+    const WIDTH: usize = 30;
+    const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+    
+    fn new(label: &'static str, cancel: CancelFlag) -> Self {
+        use std::io::IsTerminal;
+        
+        Self {
+            label,
+            cancel,
+            enabled: std::io::stderr().is_terminal(),
+            last_draw: Mutex::new(None),
+        }
+    }
+    
+    /// Move past the bar once the operation is over
+    fn finish(&self) {
+        if self.enabled && self.last_draw.lock().unwrap().is_some() {
+            eprintln!();
+        }
+    }
+}
+
+impl ProgressObserver for ProgressBar {
+    fn on_progress(&self, progress: &Progress) {
+        if !self.enabled {
+            return;
+        }
+        
+        // Redraw at most every REDRAW_INTERVAL, but always show the final state
+        let mut last_draw = self.last_draw.lock().unwrap();
+        let done = progress.files_done == progress.files_total;
+        if !done && last_draw.is_some_and(|at| at.elapsed() < Self::REDRAW_INTERVAL) {
+            return;
+        }
+        *last_draw = Some(Instant::now());
+        
+        let filled = ((progress.fraction() * Self::WIDTH as f64) as usize).min(Self::WIDTH);
+        // Keep the tail of long paths, it's the part that changes
+        let current = progress.current.display().to_string();
+        let skip = current.chars().count().saturating_sub(40);
+        let current: String = current.chars().skip(skip).collect();
+        
+        eprint!(
+            "\r\x1b[2K{} [{}{}] {}/{} files, {} {}",
+            self.label,
+            "#".repeat(filled),
+            "-".repeat(Self::WIDTH - filled),
+            progress.files_done,
+            progress.files_total,
+            format_bytes(progress.bytes_done),
+            current,
+        );
+    }
+    
+    fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+}
+
+/// Render a lock reason as a `: reason` suffix, or nothing when there isn't one
+fn format_lock_reason(reason: &str) -> String {
+    if reason.is_empty() {
+        String::new()
+    } else {
+        format!(": {}", reason)
+    }
+}
+
+fn print_cleanup_plan(plan: &crate::cleanup::CleanupPlan) {
+    if !plan.stale.is_empty() {
+        println!("🧹 Stale worktree references to prune:");
+        for stale in &plan.stale {
+            println!("  • {} ({})", stale.path.display(), stale.reason);
+        }
+    }
+    
+    if !plan.skipped.is_empty() {
+        println!("⏭️  Skipped:");
+        for skipped in &plan.skipped {
+            println!("  • {} at {}: {}", skipped.branch, skipped.path.display(), skipped.reason);
+        }
+    }
+    
+    if plan.entries.is_empty() {
+        println!("✨ No worktrees match cleanup criteria for mode: {}", plan.mode);
+        return;
+    }
+    
+    println!("🧹 Cleanup plan:");
+    for entry in &plan.entries {
+        let status = &entry.status;
+        let locked = if status.locked.is_some() { " 🔒" } else { "" };
+        let uncommitted = if status.has_uncommitted_changes { " (⚠️  uncommitted)" } else { "" };
+        println!("  • {} at {}{}{}", status.branch, status.path.display(), uncommitted, locked);
+        println!("      why: {}", entry.reason);
+        let actions: Vec<String> = entry.actions.iter().map(ToString::to_string).collect();
+        println!("      do:  {}", actions.join(", "));
+    }
+}
+
+/// Pad every column but the last to its widest cell
+fn format_table(rows: &[Vec<String>]) -> Vec<String> {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|column| {
+            rows.iter()
+                .filter_map(|row| row.get(column))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    
+    rows.iter()
+        .map(|row| {
+            let mut line = String::new();
+            for (column, cell) in row.iter().enumerate() {
+                if column + 1 == row.len() {
+                    line.push_str(cell);
+                } else {
+                    line.push_str(cell);
+                    line.push_str(&" ".repeat(widths[column] - cell.chars().count() + 2));
+                }
+            }
+            line
+        })
+        .collect()
+}
+
+/// The first 8 characters of a commit id; shorter or empty ids are shown as they are
+fn short_head(head: &str) -> &str {
+    match head {
+        "" => "unknown",
+        head => head.get(..8).unwrap_or(head),
+    }
+}
+
+fn format_ahead_behind(status: Option<crate::git::AheadBehind>) -> String {
+    match status {
+        None => "-".to_string(),
+        Some(status) if status.ahead == 0 && status.behind == 0 => "=".to_string(),
+        Some(status) => format!("↑{} ↓{}", status.ahead, status.behind),
+    }
+}
+
+/// Compact age such as `5m`, `3h` or `2w`
+fn format_age(seconds: i64) -> String {
+    const UNITS: [(i64, &str); 6] = [
+        (365 * 24 * 3600, "y"),
+        (30 * 24 * 3600, "mo"),
+        (7 * 24 * 3600, "w"),
+        (24 * 3600, "d"),
+        (3600, "h"),
+        (60, "m"),
+    ];
+    
+    UNITS.iter()
+        .find(|(unit, _)| seconds >= *unit)
+        .map(|(unit, suffix)| format!("{}{}", seconds / unit, suffix))
+        .unwrap_or_else(|| "now".to_string())
+}
+
+fn truncate_chars(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        text.to_string()
+    } else {
+        let truncated: String = text.chars().take(max.saturating_sub(1)).collect();
+        format!("{}…", truncated)
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_short_head() {
+        assert_eq!(short_head(""), "unknown");
+        assert_eq!(short_head("abc123"), "abc123");
+        assert_eq!(short_head("abcdef12"), "abcdef12");
+        assert_eq!(short_head("abcdef1234567890abcdef1234567890abcdef12"), "abcdef12");
+    }
+    
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(-5), "now");
+        assert_eq!(format_age(59), "now");
+        assert_eq!(format_age(60), "1m");
+        assert_eq!(format_age(3 * 3600 + 59), "3h");
+        assert_eq!(format_age(14 * 24 * 3600), "2w");
+        assert_eq!(format_age(45 * 24 * 3600), "1mo");
+        assert_eq!(format_age(800 * 24 * 3600), "2y");
+    }
+    
+    #[test]
+    fn test_truncate_chars() {
+        assert_eq!(truncate_chars("short", 10), "short");
+        assert_eq!(truncate_chars("exactly", 7), "exactly");
+        assert_eq!(truncate_chars("a longer subject", 8), "a longe…");
+        // Counted and cut by character, never inside a multi-byte one
+        assert_eq!(truncate_chars("héllo wörld", 11), "héllo wörld");
+        assert_eq!(truncate_chars("héllo wörld", 8), "héllo w…");
+        assert_eq!(truncate_chars("日本語のコミット", 4), "日本語…");
+        assert_eq!(truncate_chars("anything", 0), "…");
+    }
+    
+    #[test]
+    fn test_format_table() {
+        let rows = vec![
+            vec!["main".to_string(), "=".to_string(), "Initial commit".to_string()],
+            vec!["feature/é".to_string(), "↑2 ↓1".to_string(), "Work".to_string()],
+        ];
+        let lines = format_table(&rows);
+        assert_eq!(lines, vec![
+            "main       =      Initial commit",
+            "feature/é  ↑2 ↓1  Work",
+        ]);
+        assert!(format_table(&[]).is_empty());
+    }
+}
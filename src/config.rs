@@ -1,7 +1,10 @@
+use crate::cleanup::CleanupPolicy;
 use crate::error::{GitWarpError, Result};
+use crate::rewrite::RewriteScope;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use figment::{Figment, providers::{Format, Toml, Env}};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use figment::{Figment, Provider, providers::{Format, Toml, Env, Serialized}, value::Value};
 use dirs::config_dir;
 use std::fs;
 
@@ -14,6 +17,10 @@ pub struct Config {
     /// Default worktree base directory
     pub worktrees_path: Option<PathBuf>,
     
+    /// Where new worktrees go, e.g. `{worktrees_path}/{repo}/{branch_slug}`
+    #[serde(default = "default_worktree_template")]
+    pub worktree_template: String,
+    
     /// Whether to use Copy-on-Write by default
     #[serde(default = "default_true")]
     pub use_cow: bool,
@@ -37,14 +44,26 @@ pub struct Config {
     /// Agent monitoring settings
     #[serde(default)]
     pub agent: AgentConfig,
+    
+    /// Copy-on-Write clone settings
+    #[serde(default)]
+    pub cow: CowConfig,
+    
+    /// Cleanup policies
+    #[serde(default)]
+    pub cleanup: CleanupConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GitConfig {
-    /// Default branch name (main, master, develop)
-    #[serde(default = "default_main_branch")]
+    /// Branch that merges are checked against; empty to use origin/HEAD, then main/master
+    #[serde(default)]
     pub default_branch: String,
     
+    /// Compare against the remote-tracking branch (origin/main) when it exists
+    #[serde(default = "default_true")]
+    pub compare_remote: bool,
+    
     /// Whether to auto-fetch before operations
     #[serde(default = "default_true")]
     pub auto_fetch: bool,
@@ -103,17 +122,89 @@ pub struct AgentConfig {
     pub claude_hooks: bool,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CowConfig {
+    /// Gitignore-style patterns; when set, only matching files are cloned
+    #[serde(default)]
+    pub include: Vec<String>,
+    
+    /// Gitignore-style patterns for caches that must not be shared between branches
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    
+    /// Extra trees the hardlink fallback shares instead of copying, e.g. `vendor`
+    #[serde(default)]
+    pub hardlink: Vec<String>,
+    
+    /// Which cloned files get absolute paths rewritten; tracked files only with `all`
+    #[serde(default)]
+    pub rewrite_scope: RewriteScope,
+}
+
+impl CowConfig {
+    /// Build the clone spec described by this section
+    pub fn clone_spec(&self) -> crate::cow::CloneSpec {
+        crate::cow::CloneSpec {
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+            hardlink: self.hardlink.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CleanupConfig {
+    /// Named policies for `warp cleanup --policy`; names must not collide with built-ins
+    #[serde(default)]
+    pub policies: BTreeMap<String, CleanupPolicy>,
+}
+
+impl CleanupConfig {
+    /// Reject configured policies that would shadow a built-in one
+    pub fn validate(&self) -> Result<()> {
+        match self.policies.keys().find(|name| CleanupPolicy::BUILTIN.contains(&name.as_str())) {
+            Some(name) => Err(GitWarpError::ConfigError {
+                message: format!(
+                    "Cleanup policy '{}' is built-in and cannot be redefined; give [cleanup.policies.{}] another name",
+                    name, name
+                )
+            }.into()),
+            None => Ok(()),
+        }
+    }
+    
+    /// Look up a built-in or configured policy by name
+    pub fn policy(&self, name: &str) -> Result<CleanupPolicy> {
+        if let Some(policy) = CleanupPolicy::builtin(name) {
+            return Ok(policy);
+        }
+        
+        self.policies.get(name).cloned().ok_or_else(|| {
+            let mut names: Vec<&str> = CleanupPolicy::BUILTIN.to_vec();
+            names.extend(self.policies.keys().map(String::as_str));
+            anyhow::anyhow!("Unknown cleanup policy '{}', expected one of: {}", name, names.join(", "))
+        })
+    }
+}
+
+/// Per-repository overrides, read from the repository root
+pub const PROJECT_CONFIG_FILE: &str = ".git-warp.toml";
+
+/// The only settings `PROJECT_CONFIG_FILE` may change; it comes with the repository, so
+/// it must not decide what cleanup deletes or where worktrees are created
+pub const PROJECT_CONFIG_KEYS: [&str; 4] = ["cow.include", "cow.exclude", "cow.hardlink", "cow.rewrite_scope"];
+
 // Default value functions
 fn default_terminal_mode() -> String {
     "tab".to_string()
 }
 
-fn default_true() -> bool {
-    true
+fn default_worktree_template() -> String {
+    crate::layout::DEFAULT_TEMPLATE.to_string()
 }
 
-fn default_main_branch() -> String {
-    "main".to_string()
+fn default_true() -> bool {
+    true
 }
 
 fn default_kill_timeout() -> u64 {
@@ -138,12 +229,15 @@ impl Default for Config {
         Self {
             terminal_mode: default_terminal_mode(),
             worktrees_path: None,
+            worktree_template: default_worktree_template(),
             use_cow: true,
             auto_confirm: false,
             git: GitConfig::default(),
             process: ProcessConfig::default(),
             terminal: TerminalConfig::default(),
             agent: AgentConfig::default(),
+            cow: CowConfig::default(),
+            cleanup: CleanupConfig::default(),
         }
     }
 }
@@ -151,7 +245,8 @@ impl Default for Config {
 impl Default for GitConfig {
     fn default() -> Self {
         Self {
-            default_branch: default_main_branch(),
+            default_branch: String::new(),
+            compare_remote: true,
             auto_fetch: true,
             auto_prune: true,
         }
@@ -238,10 +333,18 @@ auto_confirm = {}
 # Custom worktrees directory (optional)
 # worktrees_path = "/custom/path/to/worktrees"
 
+# Where new worktrees go. Variables: {{worktrees_path}}, {{repo}}, {{owner}} (of the
+# origin remote), {{branch}} (slashes kept as directories) and {{branch_slug}}.
+# Paths that already exist get a short, stable suffix.
+# worktree_template = "{{worktrees_path}}/{{repo}}/{{branch_slug}}"
+
 [git]
-# Default main branch name
+# Branch that merges are checked against; empty to use origin/HEAD, then main/master
 default_branch = "{}"
 
+# Compare against origin/<branch> rather than a possibly stale local branch
+compare_remote = {}
+
 # Auto-fetch before operations
 auto_fetch = {}
 
@@ -280,11 +383,37 @@ max_activities = {}
 
 # Enable Claude Code hooks integration
 claude_hooks = {}
+
+[cow]
+# Skip caches that must not be shared between branches (gitignore syntax)
+# exclude = ["target/debug/incremental", "*.log"]
+
+# Only clone matching paths (clones everything when empty)
+# include = ["node_modules", "target"]
+
+# Without CoW, trees besides node_modules, venvs and the Cargo registry to hardlink
+# rather than copy. Only list trees nobody edits in place; edits show up in every worktree.
+# hardlink = ["vendor"]
+
+# Files whose absolute paths are rewritten after cloning: ignored-only, untracked or all.
+# Tracked files are only rewritten with "all".
+# rewrite_scope = "untracked"
+
+# Named policies for `warp cleanup --policy <name>`; every criterion set must hold.
+# Built-in: all, merged, remoteless, identical, gone, stale (30 days old, 14 days inactive)
+# Criteria: merged, has_remote, upstream_gone, identical (true/false),
+# older_than_days, inactive_days, larger_than_gb, and any = [{{ ... }}, ...]
+# [cleanup.policies.abandoned]
+# older_than_days = 90
+# any = [{{ merged = true }}, {{ upstream_gone = true }}]
+
+# The [cow] settings can be overridden per repository in {}; other keys there are ignored
 "#,
             config.terminal_mode,
             config.use_cow,
             config.auto_confirm,
             config.git.default_branch,
+            config.git.compare_remote,
             config.git.auto_fetch,
             config.git.auto_prune,
             config.process.check_processes,
@@ -296,6 +425,7 @@ claude_hooks = {}
             config.agent.refresh_rate,
             config.agent.max_activities,
             config.agent.claude_hooks,
+            PROJECT_CONFIG_FILE,
         )
     }
 }
@@ -309,24 +439,68 @@ pub struct ConfigManager {
 impl ConfigManager {
     /// Create a new config manager with default or loaded configuration
     pub fn new() -> Result<Self> {
-        let config_path = get_config_path()?;
-        let config = Self::load_config(&config_path)?;
+        Self::from_paths(get_config_path()?, None)
+    }
+    
+    /// Create a config manager that also applies the repository's project-level overrides
+    pub fn for_repo<P: AsRef<Path>>(repo_root: P) -> Result<Self> {
+        Self::from_paths(get_config_path()?, Some(repo_root.as_ref()))
+    }
+    
+    /// Load the configuration at `config_path`, layering `PROJECT_CONFIG_FILE` from `repo_root` on top
+    pub fn from_paths(config_path: PathBuf, repo_root: Option<&Path>) -> Result<Self> {
+        let project_path = repo_root.map(|root| root.join(PROJECT_CONFIG_FILE));
+        let config = Self::load_config(&config_path, project_path.as_deref())?;
         Ok(Self { config, config_path })
     }
     
     /// Load configuration from file, environment, and defaults
-    fn load_config(config_path: &PathBuf) -> Result<Config> {
-        let figment = Figment::new()
+    fn load_config(config_path: &Path, project_path: Option<&Path>) -> Result<Config> {
+        let mut figment = Figment::new()
             // Override with config file if it exists
-            .merge(Toml::file(config_path))
-            // Override with environment variables
-            .merge(Env::prefixed("GIT_WARP_"));
+            .merge(Toml::file(config_path));
             
-        figment.extract().map_err(|e| {
+        // Override with the repository's own settings, limited to the harmless ones
+        if let Some(project_path) = project_path {
+            figment = figment.merge(Self::project_overrides(project_path)?);
+        }
+        
+        // Override with environment variables
+        let figment = figment.merge(Env::prefixed("GIT_WARP_"));
+            
+        let config: Config = figment.extract().map_err(|e| {
             GitWarpError::ConfigError { 
                 message: format!("Failed to load configuration: {}", e) 
-            }.into()
-        })
+            }
+        })?;
+        
+        config.cleanup.validate()?;
+        Ok(config)
+    }
+    
+    /// The allowed settings from a project file, warning about the ones that are ignored
+    fn project_overrides(project_path: &Path) -> Result<Figment> {
+        let data = Toml::file(project_path).data().map_err(|e| GitWarpError::ConfigError {
+            message: format!("Failed to load {}: {}", project_path.display(), e)
+        })?;
+        
+        let mut overrides = Figment::new();
+        for (key, value) in data.values().flatten() {
+            let sections = match value {
+                Value::Dict(_, dict) => dict.iter()
+                    .map(|(inner, value)| (format!("{}.{}", key, inner), value))
+                    .collect(),
+                value => vec![(key.clone(), value)],
+            };
+            for (key, value) in sections {
+                if PROJECT_CONFIG_KEYS.contains(&key.as_str()) {
+                    overrides = overrides.merge(Serialized::default(&key, value));
+                } else {
+                    log::warn!("Ignoring '{}' in {}; only {} can be set there", key, project_path.display(), PROJECT_CONFIG_KEYS.join(", "));
+                }
+            }
+        }
+        Ok(overrides)
     }
     
     /// Get the current configuration
@@ -405,9 +579,10 @@ mod tests {
     fn test_config_defaults() {
         let config = Config::default();
         assert_eq!(config.terminal_mode, "tab");
-        assert_eq!(config.use_cow, true);
-        assert_eq!(config.auto_confirm, false);
-        assert_eq!(config.git.default_branch, "main");
+        assert!(config.use_cow);
+        assert!(!config.auto_confirm);
+        assert_eq!(config.git.default_branch, "");
+        assert!(config.git.compare_remote);
         assert_eq!(config.process.kill_timeout, 5);
     }
     
@@ -448,7 +623,7 @@ mod tests {
         config.apply_env_overrides();
         
         assert_eq!(config.terminal_mode, "window");
-        assert_eq!(config.auto_confirm, true);
+        assert!(config.auto_confirm);
         
         // Clean up
         unsafe {
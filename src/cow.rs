@@ -1,28 +1,297 @@
 use crate::error::{GitWarpError, Result};
-use std::path::Path;
+use crate::progress::{NoProgress, Progress, ProgressObserver};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
 
-/// Check if Copy-on-Write is supported for the given path
-pub fn is_cow_supported<P: AsRef<Path>>(path: P) -> Result<bool> {
+/// Selects which entries of the source tree a clone reproduces.
+///
+/// Patterns use gitignore syntax relative to the source root. The source's own `.git`
+/// is never cloned, whatever the patterns say.
+#[derive(Debug, Clone, Default)]
+pub struct CloneSpec {
+    /// When non-empty, only files matching (or living under a match of) these patterns are cloned
+    pub include: Vec<String>,
+    /// Entries matching these patterns are left out, along with everything beneath them
+    pub exclude: Vec<String>,
+    /// Trees the hardlink strategy shares on top of [`HARDLINK_TREES`], e.g. `vendor`
+    pub hardlink: Vec<String>,
+}
+
+impl CloneSpec {
+    /// A spec that clones everything except the source's `.git`
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Add an include pattern
+    pub fn include(mut self, pattern: impl Into<String>) -> Self {
+        self.include.push(pattern.into());
+        self
+    }
+    
+    /// Add an exclude pattern
+    pub fn exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.exclude.push(pattern.into());
+        self
+    }
+    
+    /// Add a tree for the hardlink strategy to share rather than copy
+    pub fn hardlink(mut self, pattern: impl Into<String>) -> Self {
+        self.hardlink.push(pattern.into());
+        self
+    }
+    
+    fn filter(&self, root: &Path) -> Result<CloneFilter> {
+        let include = if self.include.is_empty() {
+            None
+        } else {
+            Some(build_matcher(root, &self.include)?)
+        };
+        
+        Ok(CloneFilter {
+            include,
+            exclude: build_matcher(root, &self.exclude)?,
+            destination: None,
+        })
+    }
+}
+
+fn build_matcher<S: AsRef<str>>(root: &Path, patterns: &[S]) -> Result<Gitignore> {
+    let mut builder = GitignoreBuilder::new(root);
+    for pattern in patterns {
+        let pattern = pattern.as_ref();
+        builder.add_line(None, pattern).map_err(|e| GitWarpError::ConfigError {
+            message: format!("Invalid clone pattern '{}': {}", pattern, e),
+        })?;
+    }
+    
+    builder.build().map_err(|e| {
+        GitWarpError::ConfigError {
+            message: format!("Invalid clone patterns: {}", e),
+        }.into()
+    })
+}
+
+/// Compiled form of a `CloneSpec`
+struct CloneFilter {
+    include: Option<Gitignore>,
+    exclude: Gitignore,
+    /// The clone destination, when it lives inside the source
+    destination: Option<PathBuf>,
+}
+
+impl CloneFilter {
+    /// Whether an entry (relative to the source root) belongs in the clone
+    fn allows(&self, relative: &Path, is_dir: bool) -> bool {
+        if relative == Path::new(".git") || self.destination.as_deref() == Some(relative) {
+            return false;
+        }
+        
+        if self.exclude.matched(relative, is_dir).is_ignore() {
+            return false;
+        }
+        
+        // Directories are always walked so includes can match files deeper down
+        match &self.include {
+            Some(include) if !is_dir => include.matched_path_or_any_parents(relative, false).is_ignore(),
+            _ => true,
+        }
+    }
+}
+
+/// Outcome of a directory clone
+#[derive(Debug, Clone, Default)]
+pub struct CloneReport {
+    /// Files cloned with the filesystem's native CoW primitive
+    pub cloned: usize,
+    /// Files that fell back to a byte copy, plus recreated symlinks
+    pub copied: usize,
+    /// Files hardlinked to the source
+    pub linked: usize,
+    /// Entries that could not be reproduced in the destination
+    pub skipped: Vec<SkippedEntry>,
+    /// Entries left out on purpose by the clone spec, relative to the source root
+    pub excluded: Vec<PathBuf>,
+}
+
+/// An entry left out of a clone, relative to the source root
+#[derive(Debug, Clone)]
+pub struct SkippedEntry {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+impl CloneReport {
+    /// True when every entry made it into the destination
+    pub fn is_complete(&self) -> bool {
+        self.skipped.is_empty()
+    }
+    
+    fn skip(&mut self, path: PathBuf, reason: impl std::fmt::Display) {
+        log::debug!("Skipping {}: {}", path.display(), reason);
+        self.skipped.push(SkippedEntry {
+            path,
+            reason: reason.to_string(),
+        });
+    }
+}
+
+/// How regular files are reproduced in a clone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloneStrategy {
+    /// Share extents with the FICLONE ioctl (btrfs, XFS, bcachefs)
+    Reflink,
+    /// Share extents with clonefile(2) (APFS)
+    Clonefile,
+    /// Hardlink immutable-by-convention trees such as `node_modules`, copy everything else
+    Hardlink,
+    /// Copy every byte
+    Copy,
+}
+
+impl CloneStrategy {
+    /// Whether the strategy shares data copy-on-write
+    pub fn is_cow(&self) -> bool {
+        matches!(self, CloneStrategy::Reflink | CloneStrategy::Clonefile)
+    }
+    
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CloneStrategy::Reflink => "reflink",
+            CloneStrategy::Clonefile => "clonefile",
+            CloneStrategy::Hardlink => "hardlink",
+            CloneStrategy::Copy => "copy",
+        }
+    }
+}
+
+impl std::fmt::Display for CloneStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Trees whose files are replaced rather than edited in place, so sharing inodes between
+/// worktrees is safe (gitignore syntax). `vendor` is left out: it's often tracked source that
+/// gets edited like any other, so it's opt-in through [`CloneSpec::hardlink`].
+pub const HARDLINK_TREES: &[&str] = &[
+    "node_modules",
+    ".venv/lib",
+    "venv/lib",
+    ".cargo/registry",
+    ".cargo/git",
+];
+
+/// What the filesystem under a path offers for cloning
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CowCapabilities {
+    /// Filesystem type as reported by statfs
+    pub filesystem: String,
+    /// The native copy-on-write primitive, if the filesystem has one
+    pub native: Option<CloneStrategy>,
+    /// Whether the filesystem supports hardlinks
+    pub hardlink: bool,
+}
+
+impl CowCapabilities {
+    /// True when files can be cloned copy-on-write
+    pub fn is_cow(&self) -> bool {
+        self.native.is_some()
+    }
+    
+    /// The fastest strategy available, falling back to hardlinks and then plain copies
+    pub fn best_strategy(&self) -> CloneStrategy {
+        match self.native {
+            Some(strategy) => strategy,
+            None if self.hardlink => CloneStrategy::Hardlink,
+            None => CloneStrategy::Copy,
+        }
+    }
+}
+
+/// Report which clone strategies the filesystem holding `path` supports
+pub fn is_cow_supported<P: AsRef<Path>>(path: P) -> Result<CowCapabilities> {
+    capabilities(path.as_ref(), true)
+}
+
+/// Like [`is_cow_supported`], but judged from the filesystem type alone so nothing is written, e.g. on dry runs
+pub fn guess_cow_support<P: AsRef<Path>>(path: P) -> Result<CowCapabilities> {
+    capabilities(path.as_ref(), false)
+}
+
+fn capabilities(path: &Path, probe: bool) -> Result<CowCapabilities> {
     #[cfg(target_os = "macos")]
     {
+        let _ = probe;
         // Check if the path is on an APFS filesystem
-        is_apfs(path)
+        let filesystem = filesystem_name(path)?;
+        let native = (filesystem == "apfs").then_some(CloneStrategy::Clonefile);
+        let hardlink = filesystem != "msdos" && filesystem != "exfat";
+        Ok(CowCapabilities { filesystem, native, hardlink })
     }
     
     #[cfg(target_os = "linux")]
     {
-        // TODO: Check for overlayfs support
-        Ok(false)
+        // Check for a reflink-capable filesystem (btrfs, XFS, bcachefs)
+        let filesystem = filesystem_name(path)?;
+        let native = is_reflink_supported(path, probe)?.then_some(CloneStrategy::Reflink);
+        let hardlink = filesystem != "vfat";
+        Ok(CowCapabilities { filesystem, native, hardlink })
     }
     
     #[cfg(not(any(target_os = "macos", target_os = "linux")))]
     {
-        Ok(false)
+        let _ = probe;
+        if !path.exists() {
+            return Err(GitWarpError::WorktreeNotFound {
+                path: path.display().to_string(),
+            }.into());
+        }
+        
+        Ok(CowCapabilities {
+            filesystem: "unknown".to_string(),
+            native: None,
+            hardlink: true,
+        })
     }
 }
 
-/// Clone a directory using Copy-on-Write
-pub fn clone_directory<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dest: Q) -> Result<()> {
+/// Clone a directory using the platform's native Copy-on-Write primitive, reproducing only
+/// the entries selected by `spec`
+pub fn clone_directory<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dest: Q, spec: &CloneSpec) -> Result<CloneReport> {
+    let native = if cfg!(target_os = "macos") {
+        CloneStrategy::Clonefile
+    } else {
+        CloneStrategy::Reflink
+    };
+    
+    clone_directory_with(src, dest, spec, native)
+}
+
+/// Clone a directory with an explicit strategy.
+///
+/// CoW strategies fail with `CoWNotSupported` when the source filesystem can't provide them;
+/// `Hardlink` and `Copy` work everywhere.
+pub fn clone_directory_with<P: AsRef<Path>, Q: AsRef<Path>>(
+    src: P,
+    dest: Q,
+    spec: &CloneSpec,
+    strategy: CloneStrategy,
+) -> Result<CloneReport> {
+    clone_directory_with_progress(src, dest, spec, strategy, &NoProgress)
+}
+
+/// Clone a directory with an explicit strategy, reporting each placed file to `observer`.
+///
+/// If the observer cancels, the partial destination is removed and `Cancelled` is returned.
+pub fn clone_directory_with_progress<P: AsRef<Path>, Q: AsRef<Path>>(
+    src: P,
+    dest: Q,
+    spec: &CloneSpec,
+    strategy: CloneStrategy,
+    observer: &dyn ProgressObserver,
+) -> Result<CloneReport> {
     let src = src.as_ref();
     let dest = dest.as_ref();
     
@@ -32,21 +301,69 @@ pub fn clone_directory<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dest: Q) -> Resul
         }.into());
     }
     
-    #[cfg(target_os = "macos")]
-    {
-        clone_directory_apfs(src, dest)
+    match strategy {
+        CloneStrategy::Reflink => {
+            #[cfg(target_os = "linux")]
+            {
+                if !is_reflink_supported(src, true)? {
+                    return Err(GitWarpError::CoWNotSupported.into());
+                }
+                
+                prepare_destination(dest)?;
+                clone_tree(src, dest, spec, observer, |_, src, dest| clone_or_copy(src, dest, &reflink_file))
+            }
+            
+            #[cfg(not(target_os = "linux"))]
+            Err(GitWarpError::CoWNotSupported.into())
+        }
+        CloneStrategy::Clonefile => {
+            #[cfg(target_os = "macos")]
+            {
+                if !is_apfs(src)? {
+                    return Err(GitWarpError::CoWNotSupported.into());
+                }
+                
+                prepare_destination(dest)?;
+                clone_tree(src, dest, spec, observer, |_, src, dest| clone_or_copy(src, dest, &clonefile))
+            }
+            
+            #[cfg(not(target_os = "macos"))]
+            Err(GitWarpError::CoWNotSupported.into())
+        }
+        CloneStrategy::Hardlink => {
+            let shared: Vec<&str> = HARDLINK_TREES
+                .iter()
+                .copied()
+                .chain(spec.hardlink.iter().map(String::as_str))
+                .collect();
+            let shared = build_matcher(src, &shared)?;
+            prepare_destination(dest)?;
+            clone_tree(src, dest, spec, observer, |relative, src, dest| {
+                if shared.matched_path_or_any_parents(relative, false).is_ignore() {
+                    hardlink_or_copy(src, dest)
+                } else {
+                    copy_file(src, dest)
+                }
+            })
+        }
+        CloneStrategy::Copy => {
+            prepare_destination(dest)?;
+            clone_tree(src, dest, spec, observer, |_, src, dest| copy_file(src, dest))
+        }
     }
-    
-    #[cfg(target_os = "linux")]
-    {
-        // TODO: Implement overlayfs cloning
-        Err(GitWarpError::CoWNotSupported.into())
+}
+
+/// Clear out any previous destination and make sure its parent exists
+fn prepare_destination(dest: &Path) -> Result<()> {
+    if dest.exists() {
+        std::fs::remove_dir_all(dest)?;
     }
     
-    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
-    {
-        Err(GitWarpError::CoWNotSupported.into())
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)?;
     }
+    
+    Ok(())
 }
 
 #[cfg(target_os = "macos")]
@@ -63,34 +380,407 @@ fn is_apfs<P: AsRef<Path>>(path: P) -> Result<bool> {
 }
 
 #[cfg(target_os = "macos")]
-fn clone_directory_apfs<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dest: Q) -> Result<()> {
-    use std::process::Command;
+fn filesystem_name(path: &Path) -> Result<String> {
+    use nix::sys::statfs::statfs;
+    
+    let statfs = statfs(path).map_err(|e| {
+        anyhow::anyhow!("Failed to check filesystem: {}", e)
+    })?;
+    
+    Ok(statfs.filesystem_type_name().to_string())
+}
+
+/// Clone a single file with fclonefileat(2); APFS carries over mode and timestamps itself
+#[cfg(target_os = "macos")]
+fn clonefile(src: &Path, dest: &Path) -> std::io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::io::AsRawFd;
+    
+    let src_file = std::fs::File::open(src)?;
+    let dest_c = CString::new(dest.as_os_str().as_bytes())?;
+    
+    // SAFETY: the source descriptor is open and `dest_c` is a valid NUL-terminated path
+    let rc = unsafe { libc::fclonefileat(src_file.as_raw_fd(), libc::AT_FDCWD, dest_c.as_ptr(), 0) };
     
-    // Ensure we're on APFS
-    if !is_apfs(&src)? {
-        return Err(GitWarpError::CoWNotSupported.into());
+    if rc != 0 {
+        return Err(std::io::Error::last_os_error());
     }
     
-    // Remove destination if it exists
-    if dest.as_ref().exists() {
-        std::fs::remove_dir_all(&dest)?;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+nix::ioctl_write_int!(ficlone, 0x94, 9);
+
+/// bcachefs is not yet covered by the statfs constants in nix/libc
+#[cfg(target_os = "linux")]
+const BCACHEFS_SUPER_MAGIC: nix::sys::statfs::FsType = nix::sys::statfs::FsType(0xca45_1a4e);
+
+#[cfg(target_os = "linux")]
+fn filesystem_name(path: &Path) -> Result<String> {
+    use nix::sys::statfs::*;
+    
+    let statfs = statfs(path).map_err(|e| {
+        anyhow::anyhow!("Failed to check filesystem: {}", e)
+    })?;
+    
+    let name = match statfs.filesystem_type() {
+        BTRFS_SUPER_MAGIC => "btrfs",
+        XFS_SUPER_MAGIC => "xfs",
+        BCACHEFS_SUPER_MAGIC => "bcachefs",
+        // ext2, ext3 and ext4 share a magic number
+        EXT4_SUPER_MAGIC => "ext4",
+        TMPFS_MAGIC => "tmpfs",
+        OVERLAYFS_SUPER_MAGIC => "overlayfs",
+        F2FS_SUPER_MAGIC => "f2fs",
+        NFS_SUPER_MAGIC => "nfs",
+        FUSE_SUPER_MAGIC => "fuse",
+        MSDOS_SUPER_MAGIC => "vfat",
+        other => return Ok(format!("unknown ({:#x})", other.0)),
+    };
+    
+    Ok(name.to_string())
+}
+
+/// Whether files under `path` can be reflinked; without `probe` the filesystem type is taken at its word
+#[cfg(target_os = "linux")]
+fn is_reflink_supported(path: &Path, probe: bool) -> Result<bool> {
+    use nix::sys::statfs::{statfs, BTRFS_SUPER_MAGIC, XFS_SUPER_MAGIC};
+    use std::collections::HashMap;
+    use std::os::unix::fs::MetadataExt;
+    use std::sync::{Mutex, OnceLock};
+    
+    // Probe results are cached per device so we only pay for the probe clone once
+    static PROBED_DEVICES: OnceLock<Mutex<HashMap<u64, bool>>> = OnceLock::new();
+    
+    let statfs = statfs(path).map_err(|e| {
+        anyhow::anyhow!("Failed to check filesystem: {}", e)
+    })?;
+    
+    let fs_type = statfs.filesystem_type();
+    if fs_type != BTRFS_SUPER_MAGIC && fs_type != XFS_SUPER_MAGIC && fs_type != BCACHEFS_SUPER_MAGIC {
+        return Ok(false);
+    }
+    if !probe {
+        return Ok(true);
     }
     
-    // Use cp with APFS clone flags
-    let output = Command::new("cp")
-        .arg("-c")  // Clone files (CoW) if possible
-        .arg("-R")  // Recursive
-        .arg(src.as_ref())
-        .arg(dest.as_ref())
-        .output()
-        .map_err(|e| anyhow::anyhow!("Failed to execute cp command: {}", e))?;
+    // XFS only supports reflinks when formatted with reflink=1, so confirm with a real clone
+    let dir = if path.is_dir() {
+        path
+    } else {
+        path.parent().unwrap_or(path)
+    };
+    let device = std::fs::metadata(dir)?.dev();
     
-    if !output.status.success() {
-        let error = String::from_utf8_lossy(&output.stderr);
-        return Err(anyhow::anyhow!("Failed to clone directory with CoW: {}", error).into());
+    let probed = PROBED_DEVICES.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Some(&supported) = probed.lock().unwrap().get(&device) {
+        return Ok(supported);
     }
     
-    Ok(())
+    let Some(scratch) = probe_scratch_dir(dir, device) else {
+        log::debug!("Nowhere to probe reflinks for {} without touching it, assuming none", dir.display());
+        return Ok(false);
+    };
+    let supported = probe_reflink(&scratch);
+    probed.lock().unwrap().insert(device, supported);
+    Ok(supported)
+}
+
+/// Somewhere on `device` to write probe files that isn't the user's checkout: the git dir, or the temp dir
+#[cfg(target_os = "linux")]
+fn probe_scratch_dir(dir: &Path, device: u64) -> Option<std::path::PathBuf> {
+    use std::os::unix::fs::MetadataExt;
+    
+    crate::git::worktree_git_dir(dir)
+        .into_iter()
+        .chain(std::iter::once(std::env::temp_dir()))
+        .find(|candidate| std::fs::metadata(candidate).is_ok_and(|metadata| metadata.is_dir() && metadata.dev() == device))
+}
+
+/// Attempt a single reflink clone of a scratch file inside `dir`
+#[cfg(target_os = "linux")]
+fn probe_reflink(dir: &Path) -> bool {
+    let probe_src = dir.join(format!(".git-warp-probe-{}", std::process::id()));
+    let probe_dest = dir.join(format!(".git-warp-probe-{}.clone", std::process::id()));
+    
+    let result = std::fs::write(&probe_src, b"git-warp reflink probe")
+        .and_then(|_| reflink_file(&probe_src, &probe_dest));
+    
+    let _ = std::fs::remove_file(&probe_src);
+    let _ = std::fs::remove_file(&probe_dest);
+    
+    match result {
+        Ok(()) => true,
+        Err(e) => {
+            log::debug!("Reflink probe failed in {}: {}", dir.display(), e);
+            false
+        }
+    }
+}
+
+/// Clone a single file with the FICLONE ioctl, preserving its permissions and timestamps
+#[cfg(target_os = "linux")]
+fn reflink_file(src: &Path, dest: &Path) -> std::io::Result<()> {
+    use std::fs::{File, OpenOptions};
+    use std::os::unix::io::AsRawFd;
+    
+    let src_file = File::open(src)?;
+    let dest_file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dest)?;
+    
+    // SAFETY: both descriptors are valid open files for the duration of the call
+    let result = unsafe { ficlone(dest_file.as_raw_fd(), src_file.as_raw_fd() as _) };
+    
+    if let Err(errno) = result {
+        drop(dest_file);
+        let _ = std::fs::remove_file(dest);
+        return Err(errno.into());
+    }
+    
+    let metadata = src_file.metadata()?;
+    dest_file.set_permissions(metadata.permissions())?;
+    copy_times(&metadata, &dest_file)
+}
+
+/// Give `dest` the access and modification times in `metadata`.
+///
+/// FICLONE and copies only carry data, and build tools such as cargo, make and ninja would
+/// rebuild everything whose mtime moved.
+fn copy_times(metadata: &std::fs::Metadata, dest: &std::fs::File) -> std::io::Result<()> {
+    let mut times = std::fs::FileTimes::new().set_modified(metadata.modified()?);
+    if let Ok(accessed) = metadata.accessed() {
+        times = times.set_accessed(accessed);
+    }
+    dest.set_times(times)
+}
+
+/// Recreate the directory tree of `src` under `dest`, handing every regular file selected by
+/// `spec` to `place_file` along with its path relative to `src`.
+///
+/// Directories and symlinks are recreated in a single sequential pass, then files are placed
+/// in parallel. A file that can't be placed is recorded in the report rather than aborting
+/// the clone.
+fn clone_tree<F>(
+    src: &Path,
+    dest: &Path,
+    spec: &CloneSpec,
+    observer: &dyn ProgressObserver,
+    place_file: F,
+) -> Result<CloneReport>
+where
+    F: Fn(&Path, &Path, &Path) -> std::io::Result<FileOutcome> + Sync,
+{
+    use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+    
+    let mut filter = spec.filter(src)?;
+    filter.destination = dest.strip_prefix(src).ok().map(Path::to_path_buf);
+    
+    let mut walk = TreeWalk {
+        src_root: src,
+        dest_root: dest,
+        filter: &filter,
+        observer,
+        files: Vec::new(),
+        dirs: Vec::new(),
+        report: CloneReport::default(),
+    };
+    if let Err(e) = walk.create_dir_tree(Path::new("")) {
+        return Err(discard_if_cancelled(dest, e));
+    }
+    let TreeWalk { files, dirs, mut report, .. } = walk;
+    
+    let files_total = files.len();
+    let bytes_total = files.iter().map(|(_, size)| size).sum();
+    let files_done = AtomicUsize::new(0);
+    let bytes_done = AtomicU64::new(0);
+    let cancelled = AtomicBool::new(false);
+    
+    let outcomes: Vec<_> = files
+        .par_iter()
+        .filter_map(|(relative, size)| {
+            if cancelled.load(Ordering::Relaxed) || observer.is_cancelled() {
+                cancelled.store(true, Ordering::Relaxed);
+                return None;
+            }
+            
+            let outcome = place_file(relative, &src.join(relative), &dest.join(relative));
+            
+            observer.on_progress(&Progress {
+                files_done: files_done.fetch_add(1, Ordering::Relaxed) + 1,
+                files_total,
+                bytes_done: bytes_done.fetch_add(*size, Ordering::Relaxed) + size,
+                bytes_total,
+                current: relative.clone(),
+            });
+            
+            Some((relative, outcome))
+        })
+        .collect();
+    
+    // A cancel that lands after the last file still means the caller doesn't want the clone
+    if cancelled.load(Ordering::Relaxed) || observer.is_cancelled() {
+        return Err(discard_if_cancelled(dest, GitWarpError::Cancelled.into()));
+    }
+    
+    for (relative, outcome) in outcomes {
+        match outcome {
+            Ok(FileOutcome::Cloned) => report.cloned += 1,
+            Ok(FileOutcome::Copied) => report.copied += 1,
+            Ok(FileOutcome::Linked) => report.linked += 1,
+            Err(e) => report.skip(relative.clone(), e),
+        }
+    }
+    
+    // Apply directory permissions last (deepest first) so read-only directories can still be populated
+    for (relative, permissions) in dirs.into_iter().rev() {
+        std::fs::set_permissions(dest.join(relative), permissions)?;
+    }
+    
+    Ok(report)
+}
+
+/// Remove a half-built destination when the clone was cancelled, passing the error through
+fn discard_if_cancelled(dest: &Path, error: anyhow::Error) -> anyhow::Error {
+    if crate::progress::is_cancelled(&error)
+        && let Err(e) = std::fs::remove_dir_all(dest)
+    {
+        log::warn!("Failed to remove partial clone at {}: {}", dest.display(), e);
+    }
+    
+    error
+}
+
+/// How a regular file ended up in the destination
+enum FileOutcome {
+    Cloned,
+    Copied,
+    Linked,
+}
+
+/// State for the sequential pass that lays out directories and collects files to clone
+struct TreeWalk<'a> {
+    src_root: &'a Path,
+    dest_root: &'a Path,
+    filter: &'a CloneFilter,
+    observer: &'a dyn ProgressObserver,
+    /// Regular files to place, with their sizes
+    files: Vec<(PathBuf, u64)>,
+    dirs: Vec<(PathBuf, std::fs::Permissions)>,
+    report: CloneReport,
+}
+
+impl TreeWalk<'_> {
+    fn create_dir_tree(&mut self, relative: &Path) -> Result<()> {
+        use std::fs;
+        
+        if self.observer.is_cancelled() {
+            return Err(GitWarpError::Cancelled.into());
+        }
+        
+        let src = self.src_root.join(relative);
+        let dest = self.dest_root.join(relative);
+        
+        fs::create_dir(&dest)?;
+        self.dirs.push((relative.to_path_buf(), fs::metadata(&src)?.permissions()));
+        
+        let entries = match fs::read_dir(&src) {
+            Ok(entries) => entries,
+            // The root must be readable; unreadable subdirectories are reported and left empty
+            Err(e) if !relative.as_os_str().is_empty() => {
+                self.report.skip(relative.to_path_buf(), e);
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        
+        for entry in entries {
+            let entry = entry?;
+            let entry_relative = relative.join(entry.file_name());
+            
+            let file_type = match entry.file_type() {
+                Ok(file_type) => file_type,
+                Err(e) => {
+                    self.report.skip(entry_relative, e);
+                    continue;
+                }
+            };
+            
+            if !self.filter.allows(&entry_relative, file_type.is_dir()) {
+                self.report.excluded.push(entry_relative);
+                continue;
+            }
+            
+            if file_type.is_dir() {
+                self.create_dir_tree(&entry_relative)?;
+            } else if file_type.is_symlink() {
+                match copy_symlink(&entry.path(), &self.dest_root.join(&entry_relative)) {
+                    Ok(()) => self.report.copied += 1,
+                    Err(e) => self.report.skip(entry_relative, e),
+                }
+            } else if file_type.is_file() {
+                let size = entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+                self.files.push((entry_relative, size));
+            } else {
+                self.report.skip(entry_relative, "not a regular file, directory or symlink");
+            }
+        }
+        
+        Ok(())
+    }
+}
+
+/// Clone with a CoW primitive, copying instead when it refuses the file (cross-device, unsupported)
+fn clone_or_copy<F>(src: &Path, dest: &Path, clone_file: &F) -> std::io::Result<FileOutcome>
+where
+    F: Fn(&Path, &Path) -> std::io::Result<()>,
+{
+    use std::io::ErrorKind;
+    
+    match clone_file(src, dest) {
+        Ok(()) => Ok(FileOutcome::Cloned),
+        Err(e) if matches!(e.kind(), ErrorKind::Unsupported | ErrorKind::CrossesDevices | ErrorKind::InvalidInput) => {
+            log::debug!("Falling back to copy for {}: {}", src.display(), e);
+            let _ = std::fs::remove_file(dest);
+            copy_file(src, dest)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn hardlink_or_copy(src: &Path, dest: &Path) -> std::io::Result<FileOutcome> {
+    match std::fs::hard_link(src, dest) {
+        Ok(()) => Ok(FileOutcome::Linked),
+        Err(e) => {
+            log::debug!("Falling back to copy for {}: {}", src.display(), e);
+            copy_file(src, dest)
+        }
+    }
+}
+
+/// Copy a file with its permissions and timestamps; `fs::copy` alone resets the mtime
+fn copy_file(src: &Path, dest: &Path) -> std::io::Result<FileOutcome> {
+    std::fs::copy(src, dest)?;
+    // The owner may set times through a read-only handle, so read-only copies work too
+    copy_times(&std::fs::metadata(src)?, &std::fs::File::open(dest)?)?;
+    Ok(FileOutcome::Copied)
+}
+
+#[cfg(unix)]
+fn copy_symlink(src: &Path, dest: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(std::fs::read_link(src)?, dest)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(src: &Path, dest: &Path) -> std::io::Result<()> {
+    let _ = dest;
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("cannot recreate symlink {}", src.display()),
+    ))
 }
 
 #[cfg(test)]
@@ -100,15 +790,15 @@ mod tests {
     use std::fs;
     
     #[test]
-    #[cfg(target_os = "macos")]
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
     fn test_cow_support_check() {
         let result = is_cow_supported(".");
         assert!(result.is_ok());
-        // Result depends on whether we're on APFS
+        // Result depends on whether we're on APFS/btrfs/XFS
     }
     
     #[test]
-    #[cfg(target_os = "macos")]
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
     fn test_cow_clone() {
         let temp_dir = tempdir().unwrap();
         let src_dir = temp_dir.path().join("src");
@@ -119,9 +809,9 @@ mod tests {
         fs::write(src_dir.join("test.txt"), "Hello, World!").unwrap();
         
         // Only test if CoW is supported
-        if is_cow_supported(&src_dir).unwrap_or(false) {
-            let result = clone_directory(&src_dir, &dest_dir);
-            assert!(result.is_ok());
+        if is_cow_supported(&src_dir).is_ok_and(|capabilities| capabilities.is_cow()) {
+            let report = clone_directory(&src_dir, &dest_dir, &CloneSpec::new()).unwrap();
+            assert_eq!(report.cloned, 1);
             
             // Verify content was copied
             assert!(dest_dir.join("test.txt").exists());
@@ -129,4 +819,328 @@ mod tests {
             assert_eq!(content, "Hello, World!");
         }
     }
-}
\ No newline at end of file
+    
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_reflink_probe_stays_out_of_the_checkout() {
+        use std::os::unix::fs::MetadataExt;
+        
+        let temp_dir = tempdir().unwrap();
+        let checkout = temp_dir.path().join("checkout");
+        fs::create_dir_all(checkout.join(".git")).unwrap();
+        let linked = temp_dir.path().join("linked");
+        fs::create_dir_all(&linked).unwrap();
+        fs::write(linked.join(".git"), "gitdir: ../checkout/.git/worktrees/linked\n").unwrap();
+        fs::create_dir_all(checkout.join(".git/worktrees/linked")).unwrap();
+        let plain = temp_dir.path().join("plain");
+        fs::create_dir_all(&plain).unwrap();
+        let device = fs::metadata(temp_dir.path()).unwrap().dev();
+        
+        assert_eq!(probe_scratch_dir(&checkout, device), Some(checkout.join(".git")));
+        assert_eq!(probe_scratch_dir(&linked, device), Some(linked.join("../checkout/.git/worktrees/linked")));
+        // Never the directory itself, and nothing on another device
+        assert_ne!(probe_scratch_dir(&plain, device).as_deref(), Some(plain.as_path()));
+        assert_eq!(probe_scratch_dir(&plain, device ^ 1), None);
+        
+        // A dry run only looks at the filesystem type
+        guess_cow_support(&plain).unwrap();
+        is_cow_supported(&plain).unwrap();
+        assert_eq!(fs::read_dir(&plain).unwrap().count(), 0);
+    }
+    
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_reflink_clone_preserves_symlinks() {
+        let temp_dir = tempdir().unwrap();
+        let src_dir = temp_dir.path().join("src");
+        let dest_dir = temp_dir.path().join("dest");
+        
+        fs::create_dir_all(src_dir.join("nested")).unwrap();
+        fs::write(src_dir.join("nested").join("file.txt"), "content").unwrap();
+        let modified = backdate(&src_dir.join("nested").join("file.txt"));
+        std::os::unix::fs::symlink("nested/file.txt", src_dir.join("link")).unwrap();
+        
+        // tmpfs/ext4 fall back to CoWNotSupported; btrfs/XFS perform a real clone
+        match clone_directory(&src_dir, &dest_dir, &CloneSpec::new()) {
+            Ok(report) => {
+                assert!(report.is_complete());
+                let link = fs::read_link(dest_dir.join("link")).unwrap();
+                assert_eq!(link, Path::new("nested/file.txt"));
+                assert_eq!(fs::read_to_string(dest_dir.join("link")).unwrap(), "content");
+                assert_eq!(mtime(&dest_dir.join("nested").join("file.txt")), modified);
+            }
+            Err(e) => {
+                assert!(!is_cow_supported(&src_dir).unwrap().is_cow());
+                assert!(e.downcast_ref::<GitWarpError>().is_some());
+                assert!(!dest_dir.exists());
+            }
+        }
+    }
+    
+    /// Stand-in CoW primitive: a copy that keeps metadata like a clone and counts as one
+    fn fake_clone(_relative: &Path, src: &Path, dest: &Path) -> std::io::Result<FileOutcome> {
+        copy_file(src, dest)?;
+        Ok(FileOutcome::Cloned)
+    }
+    
+    /// A fixed time in the past, so a preserved mtime can't match by accident
+    fn backdate(path: &Path) -> std::time::SystemTime {
+        let time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_600_000_000);
+        let times = fs::FileTimes::new().set_modified(time).set_accessed(time);
+        fs::File::options().write(true).open(path).unwrap().set_times(times).unwrap();
+        time
+    }
+    
+    fn mtime(path: &Path) -> std::time::SystemTime {
+        fs::metadata(path).unwrap().modified().unwrap()
+    }
+    
+    fn sample_tree(root: &Path) {
+        fs::create_dir_all(root.join("nested").join("deeper")).unwrap();
+        fs::write(root.join("top.txt"), "top").unwrap();
+        backdate(&root.join("top.txt"));
+        fs::write(root.join("nested").join("file.txt"), "nested").unwrap();
+        fs::write(root.join("nested").join("deeper").join("skip.me"), "deep").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("nested/file.txt", root.join("link")).unwrap();
+    }
+    
+    #[test]
+    fn test_clone_tree_reports_cloned_files() {
+        let temp_dir = tempdir().unwrap();
+        let src_dir = temp_dir.path().join("src");
+        let dest_dir = temp_dir.path().join("dest");
+        sample_tree(&src_dir);
+        
+        let report = clone_tree(&src_dir, &dest_dir, &CloneSpec::new(), &NoProgress, fake_clone).unwrap();
+        
+        assert_eq!(report.cloned, 3);
+        assert!(report.is_complete());
+        assert_eq!(fs::read_to_string(dest_dir.join("nested").join("deeper").join("skip.me")).unwrap(), "deep");
+        assert_eq!(mtime(&dest_dir.join("top.txt")), mtime(&src_dir.join("top.txt")));
+        
+        #[cfg(unix)]
+        {
+            assert_eq!(report.copied, 1);
+            assert_eq!(fs::read_link(dest_dir.join("link")).unwrap(), Path::new("nested/file.txt"));
+        }
+    }
+    
+    #[test]
+    fn test_clone_tree_falls_back_to_copy() {
+        let temp_dir = tempdir().unwrap();
+        let src_dir = temp_dir.path().join("src");
+        let dest_dir = temp_dir.path().join("dest");
+        sample_tree(&src_dir);
+        
+        let unsupported = |_: &Path, _: &Path| Err(std::io::Error::from(std::io::ErrorKind::Unsupported));
+        let report = clone_tree(&src_dir, &dest_dir, &CloneSpec::new(), &NoProgress, |_, src, dest| clone_or_copy(src, dest, &unsupported)).unwrap();
+        
+        assert_eq!(report.cloned, 0);
+        assert!(report.copied >= 3);
+        assert!(report.is_complete());
+        assert_eq!(fs::read_to_string(dest_dir.join("top.txt")).unwrap(), "top");
+        assert_eq!(mtime(&dest_dir.join("top.txt")), mtime(&src_dir.join("top.txt")));
+    }
+    
+    #[test]
+    fn test_clone_tree_records_failures_as_skipped() {
+        let temp_dir = tempdir().unwrap();
+        let src_dir = temp_dir.path().join("src");
+        let dest_dir = temp_dir.path().join("dest");
+        sample_tree(&src_dir);
+        
+        let report = clone_tree(&src_dir, &dest_dir, &CloneSpec::new(), &NoProgress, |relative, src, dest| {
+            if src.extension().is_some_and(|ext| ext == "me") {
+                Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
+            } else {
+                fake_clone(relative, src, dest)
+            }
+        }).unwrap();
+        
+        assert_eq!(report.cloned, 2);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].path, Path::new("nested").join("deeper").join("skip.me"));
+        assert!(!dest_dir.join("nested").join("deeper").join("skip.me").exists());
+        assert!(dest_dir.join("nested").join("deeper").is_dir());
+    }
+    
+    #[test]
+    #[cfg(unix)]
+    fn test_clone_tree_preserves_directory_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        
+        let temp_dir = tempdir().unwrap();
+        let src_dir = temp_dir.path().join("src");
+        let dest_dir = temp_dir.path().join("dest");
+        sample_tree(&src_dir);
+        fs::set_permissions(src_dir.join("nested"), fs::Permissions::from_mode(0o555)).unwrap();
+        
+        let result = clone_tree(&src_dir, &dest_dir, &CloneSpec::new(), &NoProgress, fake_clone);
+        
+        // Restore permissions for cleanup
+        fs::set_permissions(src_dir.join("nested"), fs::Permissions::from_mode(0o755)).unwrap();
+        let mode = fs::metadata(dest_dir.join("nested")).unwrap().permissions().mode();
+        fs::set_permissions(dest_dir.join("nested"), fs::Permissions::from_mode(0o755)).unwrap();
+        
+        assert!(result.unwrap().is_complete());
+        assert_eq!(mode & 0o777, 0o555);
+    }
+    
+    #[test]
+    fn test_clone_spec_skips_primary_git_and_excludes() {
+        let temp_dir = tempdir().unwrap();
+        let src_dir = temp_dir.path().join("src");
+        let dest_dir = temp_dir.path().join("dest");
+        sample_tree(&src_dir);
+        fs::create_dir_all(src_dir.join(".git").join("objects")).unwrap();
+        fs::write(src_dir.join(".git").join("HEAD"), "ref: refs/heads/main").unwrap();
+        fs::create_dir_all(src_dir.join("target").join("debug").join("incremental")).unwrap();
+        fs::write(src_dir.join("target").join("debug").join("incremental").join("dep.bin"), "cache").unwrap();
+        fs::write(src_dir.join("target").join("debug").join("app"), "binary").unwrap();
+        fs::write(src_dir.join("nested").join("build.log"), "log").unwrap();
+        
+        let spec = CloneSpec::new()
+            .exclude("target/debug/incremental")
+            .exclude("*.log");
+        let report = clone_tree(&src_dir, &dest_dir, &spec, &NoProgress, fake_clone).unwrap();
+        
+        assert!(!dest_dir.join(".git").exists());
+        assert!(!dest_dir.join("target").join("debug").join("incremental").exists());
+        assert!(!dest_dir.join("nested").join("build.log").exists());
+        assert!(dest_dir.join("target").join("debug").join("app").exists());
+        assert!(dest_dir.join("nested").join("file.txt").exists());
+        
+        assert_eq!(report.excluded.len(), 3);
+        assert!(report.excluded.contains(&PathBuf::from(".git")));
+    }
+    
+    #[test]
+    fn test_clone_spec_include_only() {
+        let temp_dir = tempdir().unwrap();
+        let src_dir = temp_dir.path().join("src");
+        let dest_dir = temp_dir.path().join("dest");
+        sample_tree(&src_dir);
+        
+        let spec = CloneSpec::new().include("nested/deeper");
+        let report = clone_tree(&src_dir, &dest_dir, &spec, &NoProgress, fake_clone).unwrap();
+        
+        assert_eq!(report.cloned, 1);
+        assert!(dest_dir.join("nested").join("deeper").join("skip.me").exists());
+        assert!(!dest_dir.join("top.txt").exists());
+        assert!(!dest_dir.join("nested").join("file.txt").exists());
+    }
+    
+    #[test]
+    fn test_clone_into_own_subdirectory() {
+        let temp_dir = tempdir().unwrap();
+        let src_dir = temp_dir.path().join("src");
+        let dest_dir = src_dir.join("worktrees").join("copy");
+        sample_tree(&src_dir);
+        fs::create_dir_all(dest_dir.parent().unwrap()).unwrap();
+        
+        let report = clone_tree(&src_dir, &dest_dir, &CloneSpec::new(), &NoProgress, fake_clone).unwrap();
+        
+        assert!(report.excluded.contains(&PathBuf::from("worktrees").join("copy")));
+        assert!(!dest_dir.join("worktrees").join("copy").exists());
+        assert!(dest_dir.join("top.txt").exists());
+    }
+    
+    #[test]
+    fn test_clone_spec_rejects_invalid_patterns() {
+        let temp_dir = tempdir().unwrap();
+        let spec = CloneSpec::new().exclude("src/[unclosed");
+        let result = clone_tree(temp_dir.path(), &temp_dir.path().join("dest"), &spec, &NoProgress, |_, _, _| Ok(FileOutcome::Cloned));
+        
+        assert!(result.is_err());
+        assert!(!temp_dir.path().join("dest").exists());
+    }
+    
+    #[test]
+    fn test_clone_tree_reports_progress() {
+        use std::sync::Mutex;
+        
+        let temp_dir = tempdir().unwrap();
+        let src_dir = temp_dir.path().join("src");
+        let dest_dir = temp_dir.path().join("dest");
+        sample_tree(&src_dir);
+        
+        let updates = Mutex::new(Vec::new());
+        let observer = |progress: &Progress| updates.lock().unwrap().push(progress.clone());
+        clone_tree(&src_dir, &dest_dir, &CloneSpec::new(), &observer, fake_clone).unwrap();
+        
+        let updates = updates.into_inner().unwrap();
+        assert_eq!(updates.len(), 3);
+        
+        let last = updates.iter().max_by_key(|progress| progress.files_done).unwrap();
+        assert_eq!(last.files_done, 3);
+        assert_eq!(last.files_total, 3);
+        assert_eq!(last.bytes_total, "top".len() as u64 + "nested".len() as u64 + "deep".len() as u64);
+        assert!(updates.iter().all(|progress| progress.current.is_relative()));
+    }
+    
+    /// Cancels once a file matching the condition has been placed
+    struct CancelWhen(crate::progress::CancelFlag, fn(&Progress) -> bool);
+    
+    impl ProgressObserver for CancelWhen {
+        fn on_progress(&self, progress: &Progress) {
+            if (self.1)(progress) {
+                self.0.cancel();
+            }
+        }
+        
+        fn is_cancelled(&self) -> bool {
+            self.0.is_cancelled()
+        }
+    }
+    
+    #[test]
+    fn test_cancelled_clone_removes_destination() {
+        use crate::progress::CancelFlag;
+        
+        // On the first file, and after the last one when no task is left to notice
+        let first: fn(&Progress) -> bool = |_| true;
+        let last: fn(&Progress) -> bool = |progress| progress.files_done == progress.files_total;
+        for when in [first, last] {
+            let temp_dir = tempdir().unwrap();
+            let src_dir = temp_dir.path().join("src");
+            let dest_dir = temp_dir.path().join("dest");
+            sample_tree(&src_dir);
+            
+            let observer = CancelWhen(CancelFlag::new(), when);
+            let result = clone_tree(&src_dir, &dest_dir, &CloneSpec::new(), &observer, fake_clone);
+            
+            assert!(crate::progress::is_cancelled(&result.unwrap_err()));
+            assert!(!dest_dir.exists());
+            assert!(src_dir.join("top.txt").exists());
+        }
+    }
+    
+    #[test]
+    fn test_cancel_stops_placing_files() {
+        use crate::progress::CancelFlag;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        
+        let temp_dir = tempdir().unwrap();
+        let src_dir = temp_dir.path().join("src");
+        let dest_dir = temp_dir.path().join("dest");
+        fs::create_dir_all(&src_dir).unwrap();
+        for i in 0..400 {
+            fs::write(src_dir.join(format!("file-{}.txt", i)), "data").unwrap();
+        }
+        
+        let placed = AtomicUsize::new(0);
+        let observer = CancelWhen(CancelFlag::new(), |progress| progress.files_done >= 10);
+        let result = clone_tree(&src_dir, &dest_dir, &CloneSpec::new(), &observer, |relative, src, dest| {
+            placed.fetch_add(1, Ordering::Relaxed);
+            fake_clone(relative, src, dest)
+        });
+        
+        assert!(crate::progress::is_cancelled(&result.unwrap_err()));
+        assert!(!dest_dir.exists());
+        
+        // Each worker finishes at most the file it was on when the cancel came
+        let placed = placed.into_inner();
+        assert!(placed < 10 + rayon::current_num_threads(), "placed {} of 400 files", placed);
+    }
+}
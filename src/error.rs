@@ -31,9 +31,15 @@ pub enum GitWarpError {
     #[error("No processes found in directory '{path}'")]
     NoProcessesFound { path: String },
     
+    #[error("{count} process(es) still running in '{path}'")]
+    ProcessesRunning { path: String, count: usize },
+    
     #[error("Failed to terminate processes: {reason}")]
     ProcessTerminationFailed { reason: String },
     
     #[error("Configuration error: {message}")]
     ConfigError { message: String },
+    
+    #[error("Operation cancelled")]
+    Cancelled,
 }
\ No newline at end of file
@@ -1,28 +1,155 @@
+use crate::config::GitConfig;
+use crate::cow::{self, CloneReport, CloneSpec, CloneStrategy};
 use crate::error::{GitWarpError, Result};
+use crate::layout::{self, WorktreeLayout};
+use crate::progress::ProgressObserver;
 use gix::Repository;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct WorktreeInfo {
     pub path: PathBuf,
+    /// Checked out branch, empty when detached or bare
     pub branch: String,
     pub head: String,
-    pub is_primary: bool,
+    /// The repository's own checkout, or the repository itself when bare
+    pub is_main: bool,
+    pub is_bare: bool,
+    pub is_detached: bool,
+    /// Lock reason, empty if the worktree was locked without one
+    pub locked: Option<String>,
+    /// Why git would prune this worktree, usually because its directory is gone
+    pub prunable: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+impl WorktreeInfo {
+    /// Whether this is the main worktree; the field used to be called `is_primary`
+    #[deprecated(note = "use the `is_main` field; `is_primary` will be removed in the next release")]
+    pub fn is_primary(&self) -> bool {
+        self.is_main
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct BranchStatus {
     pub branch: String,
     pub path: PathBuf,
     pub has_remote: bool,
+    /// The branch tracks an upstream that no longer resolves, typically deleted after merging
+    pub upstream_gone: bool,
     pub is_merged: bool,
+    /// How the branch was found to be merged
+    pub merged_via: Option<MergeMethod>,
     pub is_identical: bool,
     pub has_uncommitted_changes: bool,
+    /// Lock reason when the worktree is locked
+    pub locked: Option<String>,
+}
+
+/// Commits on each side of a comparison
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct AheadBehind {
+    pub ahead: usize,
+    pub behind: usize,
+}
+
+/// The newest commit of a worktree
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CommitSummary {
+    pub subject: String,
+    pub author: String,
+    /// Committer time in seconds since the Unix epoch
+    pub timestamp: i64,
+}
+
+/// Status of a worktree beyond what `git worktree list` knows, for `warp ls --long`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WorktreeDetails {
+    /// Upstream of the checked out branch, e.g. `origin/feature`
+    pub upstream: Option<String>,
+    /// Position relative to the upstream
+    pub upstream_status: Option<AheadBehind>,
+    /// Position relative to the integration branch
+    pub default_status: Option<AheadBehind>,
+    pub last_commit: Option<CommitSummary>,
+    /// Number of changed or untracked paths
+    pub dirty_files: usize,
+}
+
+/// How a branch was found to be merged into its target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeMethod {
+    /// The branch tip is reachable from the target (merge commit or fast-forward)
+    Ancestor,
+    /// Every commit on the branch has a patch-equivalent commit on the target
+    Rebase,
+    /// The branch's combined diff matches a single commit on the target
+    Squash,
+    /// Merging the branch would leave the target's tree unchanged
+    IdenticalTree,
+}
+
+impl MergeMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MergeMethod::Ancestor => "ancestor",
+            MergeMethod::Rebase => "rebase",
+            MergeMethod::Squash => "squash",
+            MergeMethod::IdenticalTree => "identical tree",
+        }
+    }
+}
+
+impl std::fmt::Display for MergeMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 pub struct GitRepository {
     repo: Repository,
     repo_path: PathBuf,
+    config: GitConfig,
+    layout: WorktreeLayout,
+}
+
+/// The branch that other branches are checked against for merges
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegrationTarget {
+    /// Local branch name, e.g. `main`
+    pub branch: String,
+    /// What to compare against: `origin/main` when preferred and present, otherwise `main`
+    pub reference: String,
+}
+
+/// What a new worktree checks out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorktreeHead<'a> {
+    /// A branch; one that doesn't exist yet is created from `from`, `origin/<name>` or HEAD
+    Branch { name: &'a str, from: Option<&'a str> },
+    /// A detached HEAD at a tag, SHA or other revision
+    Detached(&'a str),
+}
+
+impl<'a> WorktreeHead<'a> {
+    /// The branch the worktree is on, if any
+    pub fn branch(&self) -> Option<&'a str> {
+        match self {
+            WorktreeHead::Branch { name, .. } => Some(name),
+            WorktreeHead::Detached(_) => None,
+        }
+    }
+    
+    /// The branch name or revision, for messages and path names
+    pub fn name(&self) -> &'a str {
+        match self {
+            WorktreeHead::Branch { name, .. } => name,
+            WorktreeHead::Detached(rev) => rev,
+        }
+    }
 }
 
 impl GitRepository {
@@ -36,7 +163,12 @@ impl GitRepository {
             .ok_or(GitWarpError::NotInGitRepository)?
             .to_path_buf();
         
-        Ok(Self { repo, repo_path })
+        Ok(Self {
+            repo,
+            repo_path,
+            config: GitConfig::default(),
+            layout: WorktreeLayout::default(),
+        })
     }
     
     /// Open a specific Git repository
@@ -45,7 +177,24 @@ impl GitRepository {
         let repo = gix::open(&repo_path)
             .map_err(|_| GitWarpError::NotInGitRepository)?;
         
-        Ok(Self { repo, repo_path })
+        Ok(Self {
+            repo,
+            repo_path,
+            config: GitConfig::default(),
+            layout: WorktreeLayout::default(),
+        })
+    }
+    
+    /// Use the given git settings for branch analysis
+    pub fn with_config(mut self, config: &GitConfig) -> Self {
+        self.config = config.clone();
+        self
+    }
+    
+    /// Derive new worktree paths from the given layout
+    pub fn with_layout(mut self, layout: WorktreeLayout) -> Self {
+        self.layout = layout;
+        self
     }
     
     /// Get the repository root path
@@ -53,60 +202,99 @@ impl GitRepository {
         &self.repo_path
     }
     
-    /// List all worktrees
+    /// Get the main worktree's root, where the project's settings live.
+    ///
+    /// From a linked worktree this is the parent of the common git dir rather than the
+    /// worktree itself. A bare repository has no main worktree, so it's [`Self::root_path`].
+    pub fn main_root_path(&self) -> PathBuf {
+        self.repo
+            .main_repo()
+            .ok()
+            .and_then(|repo| repo.work_dir().map(Path::to_path_buf))
+            .unwrap_or_else(|| self.repo_path.clone())
+    }
+    
+    /// List all worktrees, main worktree first
     pub fn list_worktrees(&self) -> Result<Vec<WorktreeInfo>> {
-        use std::process::Command;
+        match self.list_worktrees_native() {
+            Ok(worktrees) => Ok(worktrees),
+            Err(e) => {
+                log::debug!("Native worktree enumeration failed, falling back to git: {}", e);
+                self.list_worktrees_porcelain()
+            }
+        }
+    }
+    
+    /// Enumerate worktrees by reading the repository's admin directories with gix
+    fn list_worktrees_native(&self) -> Result<Vec<WorktreeInfo>> {
+        let main_repo = self.repo.main_repo()?;
+        let mut main = WorktreeInfo {
+            is_main: true,
+            ..Default::default()
+        };
         
-        // Use git command to list worktrees since gix doesn't have full worktree support yet
-        let output = Command::new("git")
-            .args(&["worktree", "list", "--porcelain"])
-            .current_dir(&self.repo_path)
-            .output()
-            .map_err(|e| anyhow::anyhow!("Failed to list worktrees: {}", e))?;
-            
-        if !output.status.success() {
-            return Err(anyhow::anyhow!("Git worktree list failed").into());
+        match main_repo.work_dir() {
+            Some(work_dir) => {
+                main.path = work_dir.to_path_buf();
+                describe_head(&main_repo, &mut main)?;
+            }
+            None => {
+                // Reached through a linked worktree this is `<common dir>/worktrees/<id>/../..`
+                let git_dir = main_repo.git_dir();
+                main.path = std::fs::canonicalize(git_dir).unwrap_or_else(|_| git_dir.to_path_buf());
+                main.is_bare = true;
+            }
         }
         
-        let output_str = String::from_utf8_lossy(&output.stdout);
-        let mut worktrees = Vec::new();
-        let mut current_worktree: Option<WorktreeInfo> = None;
+        let mut worktrees = vec![main];
         
-        for line in output_str.lines() {
-            if line.starts_with("worktree ") {
-                // Save previous worktree if exists
-                if let Some(wt) = current_worktree.take() {
-                    worktrees.push(wt);
-                }
-                
-                let path = line.strip_prefix("worktree ").unwrap_or("");
-                current_worktree = Some(WorktreeInfo {
-                    path: PathBuf::from(path),
-                    branch: String::new(),
-                    head: String::new(),
-                    is_primary: false,
-                });
-            } else if line.starts_with("HEAD ") {
-                if let Some(ref mut wt) = current_worktree {
-                    wt.head = line.strip_prefix("HEAD ").unwrap_or("").to_string();
-                }
-            } else if line.starts_with("branch refs/heads/") {
-                if let Some(ref mut wt) = current_worktree {
-                    wt.branch = line.strip_prefix("branch refs/heads/").unwrap_or("").to_string();
+        for proxy in self.repo.worktrees()? {
+            let mut info = WorktreeInfo {
+                locked: proxy.lock_reason().map(|reason| reason.to_string()),
+                ..Default::default()
+            };
+            
+            match proxy.base() {
+                Ok(base) => {
+                    if !base.is_dir() {
+                        info.prunable = Some("gitdir file points to non-existent location".to_string());
+                    }
+                    info.path = base;
                 }
-            } else if line == "bare" {
-                if let Some(ref mut wt) = current_worktree {
-                    wt.is_primary = true;
+                Err(_) => {
+                    info.prunable = Some("gitdir file is unreadable".to_string());
+                    info.path = proxy.git_dir().to_path_buf();
                 }
             }
+            
+            // Locked worktrees are never pruned
+            if info.locked.is_some() {
+                info.prunable = None;
+            }
+            
+            let repo = proxy.into_repo_with_possibly_inaccessible_worktree()?;
+            describe_head(&repo, &mut info)?;
+            worktrees.push(info);
         }
         
-        // Add the last worktree
-        if let Some(wt) = current_worktree {
-            worktrees.push(wt);
+        Ok(worktrees)
+    }
+    
+    /// Enumerate worktrees by parsing `git worktree list --porcelain`
+    fn list_worktrees_porcelain(&self) -> Result<Vec<WorktreeInfo>> {
+        use std::process::Command;
+        
+        let output = Command::new("git")
+            .args(["worktree", "list", "--porcelain"])
+            .current_dir(&self.repo_path)
+            .output()
+            .map_err(|e| anyhow::anyhow!("Failed to list worktrees: {}", e))?;
+            
+        if !output.status.success() {
+            return Err(anyhow::anyhow!("Git worktree list failed"));
         }
         
-        Ok(worktrees)
+        Ok(parse_worktree_porcelain(&String::from_utf8_lossy(&output.stdout)))
     }
     
     /// Create a new worktree and branch
@@ -116,61 +304,106 @@ impl GitRepository {
         worktree_path: P,
         from_commit: Option<&str>,
     ) -> Result<()> {
+        self.create_worktree(WorktreeHead::Branch { name: branch_name, from: from_commit }, worktree_path)
+    }
+    
+    /// Create a new worktree on a branch or at a detached revision
+    pub fn create_worktree<P: AsRef<Path>>(&self, head: WorktreeHead<'_>, worktree_path: P) -> Result<()> {
+        self.add_worktree(head, worktree_path.as_ref(), true)
+    }
+    
+    /// Register a new worktree and branch without checking out any files.
+    ///
+    /// The directory only holds the `.git` gitfile afterwards; the admin directory under
+    /// `.git/worktrees/<name>` has HEAD set but no index yet.
+    pub fn register_worktree<P: AsRef<Path>>(
+        &self,
+        branch_name: &str,
+        worktree_path: P,
+        from_commit: Option<&str>,
+    ) -> Result<()> {
+        self.add_worktree(WorktreeHead::Branch { name: branch_name, from: from_commit }, worktree_path.as_ref(), false)
+    }
+    
+    fn add_worktree(&self, head: WorktreeHead<'_>, worktree_path: &Path, checkout: bool) -> Result<()> {
         use std::process::Command;
         
-        let worktree_path = worktree_path.as_ref();
+        let checkout_flag = if checkout { "--checkout" } else { "--no-checkout" };
+        let mut cmd = Command::new("git");
+        cmd.args(["worktree", "add", checkout_flag]);
         
-        // Check if branch already exists
-        if self.branch_exists(branch_name)? {
-            // Create worktree from existing branch
-            let mut cmd = Command::new("git");
-            cmd.args(&["worktree", "add"])
-                .arg(worktree_path)
-                .arg(branch_name)
-                .current_dir(&self.repo_path);
-                
-            let output = cmd.output()
-                .map_err(|e| anyhow::anyhow!("Failed to create worktree: {}", e))?;
-                
-            if !output.status.success() {
-                let error = String::from_utf8_lossy(&output.stderr);
-                return Err(anyhow::anyhow!("Failed to create worktree: {}", error).into());
+        match head {
+            WorktreeHead::Detached(rev) => {
+                cmd.arg("--detach").arg(worktree_path).arg(rev);
             }
-        } else {
-            // Create new branch and worktree
-            let mut cmd = Command::new("git");
-            cmd.args(&["worktree", "add", "-b", branch_name])
-                .arg(worktree_path);
-                
-            if let Some(commit) = from_commit {
-                cmd.arg(commit);
-            } else {
-                cmd.arg("HEAD");
+            WorktreeHead::Branch { name, from } if self.branch_exists(name)? => {
+                // An existing branch is checked out as it is
+                if let Some(from) = from {
+                    self.check_start_point(name, from)?;
+                }
+                cmd.arg(worktree_path).arg(name);
             }
-            
-            cmd.current_dir(&self.repo_path);
-            
-            let output = cmd.output()
-                .map_err(|e| anyhow::anyhow!("Failed to create worktree and branch: {}", e))?;
-                
-            if !output.status.success() {
-                let error = String::from_utf8_lossy(&output.stderr);
-                return Err(anyhow::anyhow!("Failed to create worktree and branch: {}", error).into());
+            WorktreeHead::Branch { name, from: Some(from) } => {
+                cmd.args(["-b", name]).arg(worktree_path).arg(from);
             }
+            WorktreeHead::Branch { name, from: None } => {
+                // A branch that so far only exists on the remote gets a local branch tracking it
+                if self.remote_branch_exists(name)? {
+                    cmd.args(["--track", "-b", name]).arg(worktree_path).arg(format!("origin/{}", name));
+                } else {
+                    cmd.args(["-b", name]).arg(worktree_path).arg("HEAD");
+                }
+            }
+        }
+        
+        let output = cmd.current_dir(&self.repo_path)
+            .output()
+            .map_err(|e| anyhow::anyhow!("Failed to create worktree: {}", e))?;
+            
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow::anyhow!("Failed to create worktree: {}", error));
         }
         
         Ok(())
     }
     
-    /// Remove a worktree
-    pub fn remove_worktree<P: AsRef<Path>>(&self, worktree_path: P) -> Result<()> {
+    /// Fail if `branch` already exists somewhere other than `from`.
+    ///
+    /// A start point can't move an existing branch, so asking for a different one is an error
+    /// rather than being silently ignored.
+    pub fn check_start_point(&self, branch: &str, from: &str) -> Result<()> {
+        if !self.branch_exists(branch)? {
+            return Ok(());
+        }
+        
+        let resolve = |rev: &str| {
+            run_git(&self.repo_path, &["rev-parse", "--verify", "--quiet", &format!("{}^{{commit}}", rev)])
+                .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+                .map_err(|_| anyhow::anyhow!("Unknown revision '{}'", rev))
+        };
+        if resolve(branch)? == resolve(from)? {
+            return Ok(());
+        }
+        Err(GitWarpError::BranchAlreadyExists { branch: branch.to_string() }.into())
+    }
+    
+    /// Remove a worktree.
+    ///
+    /// Without `force`, git refuses one with untracked or modified files. Locked worktrees
+    /// are refused either way.
+    pub fn remove_worktree<P: AsRef<Path>>(&self, worktree_path: P, force: bool) -> Result<()> {
         use std::process::Command;
         
         let worktree_path = worktree_path.as_ref();
         
         // Remove the worktree using git
-        let output = Command::new("git")
-            .args(&["worktree", "remove"])
+        let mut cmd = Command::new("git");
+        cmd.args(["worktree", "remove"]);
+        if force {
+            cmd.arg("--force");
+        }
+        let output = cmd
             .arg(worktree_path)
             .current_dir(&self.repo_path)
             .output()
@@ -178,12 +411,196 @@ impl GitRepository {
             
         if !output.status.success() {
             let error = String::from_utf8_lossy(&output.stderr);
-            return Err(anyhow::anyhow!("Failed to remove worktree: {}", error).into());
+            return Err(anyhow::anyhow!("Failed to remove worktree: {}", error));
+        }
+        
+        Ok(())
+    }
+    
+    /// Lock a worktree so it is never pruned, moved or cleaned up
+    pub fn lock_worktree<P: AsRef<Path>>(&self, worktree_path: P, reason: Option<&str>) -> Result<()> {
+        use std::process::Command;
+        
+        let mut cmd = Command::new("git");
+        cmd.args(["worktree", "lock"]);
+        if let Some(reason) = reason {
+            cmd.args(["--reason", reason]);
+        }
+        
+        let output = cmd
+            .arg(worktree_path.as_ref())
+            .current_dir(&self.repo_path)
+            .output()
+            .map_err(|e| anyhow::anyhow!("Failed to lock worktree: {}", e))?;
+            
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow::anyhow!("Failed to lock worktree: {}", error));
+        }
+        
+        Ok(())
+    }
+    
+    /// Unlock a worktree
+    pub fn unlock_worktree<P: AsRef<Path>>(&self, worktree_path: P) -> Result<()> {
+        use std::process::Command;
+        
+        let output = Command::new("git")
+            .args(["worktree", "unlock"])
+            .arg(worktree_path.as_ref())
+            .current_dir(&self.repo_path)
+            .output()
+            .map_err(|e| anyhow::anyhow!("Failed to unlock worktree: {}", e))?;
+            
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow::anyhow!("Failed to unlock worktree: {}", error));
+        }
+        
+        Ok(())
+    }
+    
+    /// Move a worktree to `new_path`, optionally renaming its branch on the way.
+    ///
+    /// Absolute paths to the old location in the worktree's files are rewritten. The main
+    /// worktree, locked worktrees and worktrees with processes still running in them are refused.
+    /// If rewriting or renaming fails, the rewrite is undone and the worktree moved back.
+    pub fn move_worktree<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        worktree_path: P,
+        new_path: Q,
+        new_branch: Option<&str>,
+    ) -> Result<()> {
+        use crate::journal::JournalAside;
+        use crate::process::ProcessManager;
+        use crate::rewrite::PathRewriter;
+        
+        let worktree_path = worktree_path.as_ref();
+        let new_path = new_path.as_ref();
+        
+        let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let worktree = self.list_worktrees()?
+            .into_iter()
+            .find(|wt| canonical(&wt.path) == canonical(worktree_path))
+            .ok_or_else(|| GitWarpError::WorktreeNotFound { path: worktree_path.display().to_string() })?;
+        
+        if worktree.is_main {
+            return Err(anyhow::anyhow!("The main worktree can't be moved"));
+        }
+        if worktree.locked.is_some() {
+            return Err(anyhow::anyhow!("Worktree '{}' is locked, unlock it first", worktree_path.display()));
+        }
+        if new_path.exists() {
+            return Err(GitWarpError::WorktreeAlreadyExists { path: new_path.display().to_string() }.into());
+        }
+        if let Some(new_branch) = new_branch {
+            if worktree.branch.is_empty() {
+                return Err(anyhow::anyhow!("Worktree '{}' has no branch to rename", worktree_path.display()));
+            }
+            if self.branch_exists(new_branch)? {
+                return Err(GitWarpError::BranchAlreadyExists { branch: new_branch.to_string() }.into());
+            }
+        }
+        
+        let processes = ProcessManager::new().find_processes_in_directory(worktree_path)?;
+        if !processes.is_empty() {
+            return Err(GitWarpError::ProcessesRunning {
+                path: worktree_path.display().to_string(),
+                count: processes.len(),
+            }.into());
+        }
+        
+        if let Some(parent) = new_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        
+        self.git_worktree_move(worktree_path, new_path)?;
+        
+        // git only fixes its own links; files that embed the old location need rewriting too.
+        // The rewrite is journaled on its own so it can be undone without touching earlier ones.
+        let aside = match JournalAside::set(new_path) {
+            Ok(aside) => aside,
+            Err(e) => return Err(self.undo_move(worktree_path, new_path, None, e)),
+        };
+        if let Err(e) = PathRewriter::new(worktree_path, new_path).rewrite_paths() {
+            return Err(self.undo_move(worktree_path, new_path, Some(aside), e));
+        }
+        
+        if let Some(new_branch) = new_branch
+            && let Err(e) = self.rename_branch(&worktree.branch, new_branch)
+        {
+            return Err(self.undo_move(worktree_path, new_path, Some(aside), e));
+        }
+        
+        aside.merge().map_err(|e| anyhow::anyhow!("Moved worktree, but failed to update its rewrite journal: {}", e))
+    }
+    
+    fn git_worktree_move(&self, from: &Path, to: &Path) -> Result<()> {
+        use std::process::Command;
+        
+        let output = Command::new("git")
+            .args(["worktree", "move"])
+            .arg(from)
+            .arg(to)
+            .current_dir(&self.repo_path)
+            .output()
+            .map_err(|e| anyhow::anyhow!("Failed to move worktree: {}", e))?;
+            
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow::anyhow!("Failed to move worktree: {}", error.trim()));
+        }
+        Ok(())
+    }
+    
+    /// Put a worktree moved to `new_path` back, saying in the returned error where things were left
+    fn undo_move(
+        &self,
+        worktree_path: &Path,
+        new_path: &Path,
+        aside: Option<crate::journal::JournalAside>,
+        error: anyhow::Error,
+    ) -> anyhow::Error {
+        if let Some(aside) = aside
+            && let Err(e) = aside.restore(new_path)
+        {
+            return anyhow::anyhow!(
+                "{:#}; the worktree is left at {} with paths partly rewritten ({})",
+                error, new_path.display(), e
+            );
+        }
+        if let Err(e) = self.git_worktree_move(new_path, worktree_path) {
+            return anyhow::anyhow!("{:#}; the worktree is left at {} ({:#})", error, new_path.display(), e);
+        }
+        anyhow::anyhow!("{:#}; moved the worktree back to {}", error, worktree_path.display())
+    }
+    
+    /// Rename a local branch, following it in any worktree that has it checked out
+    pub fn rename_branch(&self, branch_name: &str, new_name: &str) -> Result<()> {
+        use std::process::Command;
+        
+        let output = Command::new("git")
+            .args(["branch", "-m", branch_name, new_name])
+            .current_dir(&self.repo_path)
+            .output()
+            .map_err(|e| anyhow::anyhow!("Failed to rename branch: {}", e))?;
+            
+        if !output.status.success() {
+            let error = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow::anyhow!("Failed to rename branch {}: {}", branch_name, error));
         }
         
         Ok(())
     }
     
+    /// Find the worktree that has `branch_name` checked out
+    pub fn find_worktree(&self, branch_name: &str) -> Result<WorktreeInfo> {
+        self.list_worktrees()?
+            .into_iter()
+            .find(|wt| wt.branch == branch_name)
+            .ok_or_else(|| GitWarpError::WorktreeNotFound { path: branch_name.to_string() }.into())
+    }
+    
     /// Delete a local branch
     pub fn delete_branch(&self, branch_name: &str, force: bool) -> Result<()> {
         use std::process::Command;
@@ -191,14 +608,14 @@ impl GitRepository {
         let delete_flag = if force { "-D" } else { "-d" };
         
         let output = Command::new("git")
-            .args(&["branch", delete_flag, branch_name])
+            .args(["branch", delete_flag, branch_name])
             .current_dir(&self.repo_path)
             .output()
             .map_err(|e| anyhow::anyhow!("Failed to delete branch: {}", e))?;
             
         if !output.status.success() {
             let error = String::from_utf8_lossy(&output.stderr);
-            return Err(anyhow::anyhow!("Failed to delete branch {}: {}", branch_name, error).into());
+            return Err(anyhow::anyhow!("Failed to delete branch {}: {}", branch_name, error));
         }
         
         Ok(())
@@ -209,96 +626,130 @@ impl GitRepository {
         use std::process::Command;
         
         let output = Command::new("git")
-            .args(&["worktree", "prune"])
+            .args(["worktree", "prune"])
             .current_dir(&self.repo_path)
             .output()
             .map_err(|e| anyhow::anyhow!("Failed to prune worktrees: {}", e))?;
             
         if !output.status.success() {
             let error = String::from_utf8_lossy(&output.stderr);
-            return Err(anyhow::anyhow!("Failed to prune worktrees: {}", error).into());
+            return Err(anyhow::anyhow!("Failed to prune worktrees: {}", error));
         }
         
         Ok(())
     }
     
-    /// Analyze branches for cleanup
-    pub fn analyze_branches_for_cleanup(&self, worktrees: &[WorktreeInfo]) -> Result<Vec<BranchStatus>> {
-        use std::process::Command;
+    /// Bring a cloned working tree in line with its worktree's HEAD.
+    ///
+    /// `cloned_from` is the commit checked out in the tree that was cloned. The index is built
+    /// from HEAD, tracked files that differ are rewritten, and files tracked at `cloned_from`
+    /// but not at HEAD are removed. Untracked and ignored files are left alone.
+    fn sync_cloned_worktree(&self, worktree_path: &Path, cloned_from: &str) -> Result<()> {
+        let git = |args: &[&str]| run_git(worktree_path, args);
         
-        let mut branch_statuses = Vec::new();
+        // Build the index from HEAD and record stat data for files the clone got right
+        git(&["reset", "--mixed", "HEAD"])?;
         
-        for worktree in worktrees {
-            if worktree.is_primary || worktree.branch.is_empty() {
-                continue;
-            }
-            
-            let branch = &worktree.branch;
-            let path = &worktree.path;
-            
-            // Check if branch has a remote
-            let has_remote = {
-                let output = Command::new("git")
-                    .args(&["config", &format!("branch.{}.remote", branch)])
-                    .current_dir(&self.repo_path)
-                    .output()
-                    .map_err(|e| anyhow::anyhow!("Failed to check remote: {}", e))?;
-                    
-                output.status.success() && !output.stdout.is_empty()
-            };
-            
-            // Check if branch is merged to main/master
-            let is_merged = {
-                let main_branches = ["main", "master", "develop"];
-                let mut merged = false;
-                
-                for main_branch in &main_branches {
-                    let output = Command::new("git")
-                        .args(&["merge-base", "--is-ancestor", branch, main_branch])
-                        .current_dir(&self.repo_path)
-                        .output();
-                        
-                    if let Ok(output) = output {
-                        if output.status.success() {
-                            merged = true;
-                            break;
-                        }
-                    }
+        // Files the clone brought along that don't belong to this branch
+        if !cloned_from.is_empty() {
+            let output = git(&["diff", "--name-only", "-z", "--no-renames", "--diff-filter=A", "HEAD", cloned_from])?;
+            for path in output.stdout.split(|&b| b == 0).filter(|path| !path.is_empty()) {
+                let path = worktree_path.join(String::from_utf8_lossy(path).as_ref());
+                if path.symlink_metadata().is_ok() {
+                    std::fs::remove_file(&path)?;
                 }
-                merged
-            };
-            
-            // Check if branch is identical to main
-            let is_identical = {
-                let output = Command::new("git")
-                    .args(&["diff", "--quiet", "main", branch])
-                    .current_dir(&self.repo_path)
-                    .output();
-                    
-                output.map(|o| o.status.success()).unwrap_or(false)
-            };
-            
-            // Check for uncommitted changes
-            let has_uncommitted_changes = {
-                let output = Command::new("git")
-                    .args(&["status", "--porcelain"])
-                    .current_dir(path)
-                    .output();
-                    
-                output.map(|o| !o.stdout.is_empty()).unwrap_or(false)
-            };
-            
-            branch_statuses.push(BranchStatus {
-                branch: branch.clone(),
-                path: path.clone(),
-                has_remote,
-                is_merged,
-                is_identical,
-                has_uncommitted_changes,
-            });
+            }
+        }
+        
+        // Only entries that don't match the index are written, so cloned files stay shared
+        git(&["checkout-index", "--all", "--force"])?;
+        
+        Ok(())
+    }
+    
+    /// Start recording a worktree creation so it can be rolled back on failure
+    pub fn begin_worktree_creation(&self) -> WorktreeTransaction<'_> {
+        WorktreeTransaction {
+            repo: self,
+            steps: Vec::new(),
+        }
+    }
+    
+    /// Collect upstream, ahead/behind, last commit and dirty state for each worktree.
+    ///
+    /// Runs in parallel; the result is in the same order as `worktrees`. Fields that can't be
+    /// determined, such as the upstream of a detached worktree, are left empty.
+    pub fn worktree_details(&self, worktrees: &[WorktreeInfo]) -> Vec<WorktreeDetails> {
+        use rayon::prelude::*;
+        
+        let target = match self.integration_target() {
+            Ok(target) => Some(target.reference),
+            Err(e) => {
+                log::debug!("Not comparing worktrees with a default branch: {}", e);
+                None
+            }
+        };
+        
+        worktrees
+            .par_iter()
+            .map(|worktree| describe_worktree(worktree, target.as_deref()))
+            .collect()
+    }
+    
+    /// Analyze branches for cleanup, skipping locked worktrees
+    pub fn analyze_branches_for_cleanup(&self, worktrees: &[WorktreeInfo]) -> Result<Vec<BranchStatus>> {
+        self.analyze_branches_for_cleanup_with(worktrees, false)
+    }
+    
+    /// Analyze branches for cleanup, including locked worktrees when asked to
+    ///
+    /// Refs, config, ancestry and dirty state are read through gix in parallel, and the patch
+    /// ids for rebase and squash detection come from one git pipeline for all worktrees. The
+    /// result is in the same order as `worktrees`.
+    pub fn analyze_branches_for_cleanup_with(&self, worktrees: &[WorktreeInfo], include_locked: bool) -> Result<Vec<BranchStatus>> {
+        use rayon::prelude::*;
+        
+        let target = self.integration_target()?;
+        
+        // Only linked worktrees with a branch and a directory on disk can be cleaned up
+        let candidates: Vec<&WorktreeInfo> = worktrees
+            .iter()
+            .filter(|worktree| worktree.locked.is_none() || include_locked)
+            .filter(|worktree| !(worktree.is_main || worktree.is_bare || worktree.is_detached || worktree.prunable.is_some()))
+            .filter(|worktree| !worktree.branch.is_empty())
+            .collect();
+        if candidates.is_empty() {
+            return Ok(Vec::new());
+        }
+        
+        // Reopen so config and refs written since `find()`, e.g. by a fetch, are seen
+        let repo = gix::open(&self.repo_path).map_err(|_| GitWarpError::NotInGitRepository)?;
+        let merges = MergeAnalysis::new(&repo, &self.repo_path, &target.reference);
+        if merges.is_none() {
+            log::debug!("Could not resolve {} for cleanup analysis", target.reference);
         }
         
-        Ok(branch_statuses)
+        // gix handles can't be shared across threads, so each worker opens its own
+        let repo_path = self.repo_path.as_path();
+        let inspected: Vec<(BranchStatus, Ancestry)> = candidates
+            .par_iter()
+            .map_init(
+                || gix::open(repo_path).ok(),
+                |repo, worktree| match repo {
+                    Some(repo) => inspect_worktree(repo, worktree, &target.branch, merges.as_ref()),
+                    None => Err(GitWarpError::NotInGitRepository.into()),
+                },
+            )
+            .collect::<Result<_>>()?;
+        let (mut statuses, ancestries): (Vec<_>, Vec<_>) = inspected.into_iter().unzip();
+        
+        if let Some(merges) = &merges {
+            for (status, merged_via) in statuses.iter_mut().zip(merges.merge_methods(ancestries)?) {
+                status.is_merged = merged_via.is_some();
+                status.merged_via = merged_via;
+            }
+        }
+        Ok(statuses)
     }
     
     /// Fetch from remote
@@ -306,7 +757,7 @@ impl GitRepository {
         use std::process::Command;
         
         let output = Command::new("git")
-            .args(&["fetch", "--all", "--prune"])
+            .args(["fetch", "--all", "--prune"])
             .current_dir(&self.repo_path)
             .output()
             .map_err(|e| anyhow::anyhow!("Failed to fetch: {}", e))?;
@@ -325,7 +776,7 @@ impl GitRepository {
         use std::process::Command;
         
         let output = Command::new("git")
-            .args(&["show-ref", "--verify", "--quiet", &format!("refs/heads/{}", branch_name)])
+            .args(["show-ref", "--verify", "--quiet", &format!("refs/heads/{}", branch_name)])
             .current_dir(&self.repo_path)
             .output()
             .map_err(|e| anyhow::anyhow!("Failed to check branch existence: {}", e))?;
@@ -338,14 +789,14 @@ impl GitRepository {
         use std::process::Command;
         
         let output = Command::new("git")
-            .args(&["rev-parse", "HEAD"])
+            .args(["rev-parse", "HEAD"])
             .current_dir(&self.repo_path)
             .output()
             .map_err(|e| anyhow::anyhow!("Failed to get HEAD commit: {}", e))?;
             
         if !output.status.success() {
             let error = String::from_utf8_lossy(&output.stderr);
-            return Err(anyhow::anyhow!("Failed to get HEAD commit: {}", error).into());
+            return Err(anyhow::anyhow!("Failed to get HEAD commit: {}", error));
         }
         
         let commit_hash = String::from_utf8_lossy(&output.stdout)
@@ -355,67 +806,1080 @@ impl GitRepository {
         Ok(commit_hash)
     }
     
-    /// Get the default worktree path for a branch
+    /// Get the worktree path for a branch: where it's checked out, or where it would be created.
+    ///
+    /// New paths follow the configured layout; one that's already taken on disk or by another
+    /// worktree gets a deterministic suffix.
     pub fn get_worktree_path(&self, branch_name: &str) -> PathBuf {
-        self.repo_path.join("../worktrees").join(branch_name)
+        let worktrees = self.list_worktrees().unwrap_or_default();
+        if let Some(existing) = worktrees.iter().find(|wt| wt.branch == branch_name) {
+            return existing.path.clone();
+        }
+        
+        let repo_root = worktrees
+            .iter()
+            .find(|wt| wt.is_main)
+            .map(|wt| wt.path.as_path())
+            .unwrap_or(&self.repo_path);
+        let owner = self.remote_owner().unwrap_or_else(|| "local".to_string());
+        let path = self.layout.render(repo_root, &owner, branch_name);
+        
+        layout::disambiguate(path, branch_name, |candidate| {
+            candidate.exists() || worktrees.iter().any(|wt| wt.path == candidate)
+        })
+    }
+    
+    /// The owner in the `origin` remote's URL, e.g. `owner` for `git@host:owner/repo.git`
+    fn remote_owner(&self) -> Option<String> {
+        let remote = self.repo.find_remote("origin").ok()?;
+        let url = remote.url(gix::remote::Direction::Fetch)?;
+        layout::remote_owner(&url.path.to_string())
     }
     
     /// Get the main branch name (main or master)
     pub fn get_main_branch(&self) -> Result<String> {
+        Ok(self.integration_target()?.branch)
+    }
+    
+    /// Resolve the branch that merges are checked against.
+    ///
+    /// Tries the configured `default_branch`, then `origin/HEAD`, then a local `main` or
+    /// `master`. With `compare_remote` set, `origin/<branch>` is compared against when it exists.
+    pub fn integration_target(&self) -> Result<IntegrationTarget> {
         use std::process::Command;
         
-        // Try to get the default branch from remote
-        let output = Command::new("git")
-            .args(&["symbolic-ref", "refs/remotes/origin/HEAD"])
-            .current_dir(&self.repo_path)
-            .output();
-            
-        if let Ok(output) = output {
-            if output.status.success() {
-                let branch_ref = String::from_utf8_lossy(&output.stdout);
-                if let Some(branch) = branch_ref.trim().strip_prefix("refs/remotes/origin/") {
-                    return Ok(branch.to_string());
-                }
-            }
+        let configured = Some(self.config.default_branch.as_str())
+            .filter(|branch| !branch.is_empty())
+            .map(str::to_string);
+        if let Some(branch) = &configured
+            && !self.branch_exists(branch)?
+            && !self.remote_branch_exists(branch)?
+        {
+            log::warn!("Configured default branch '{}' doesn't exist, detecting it instead", branch);
         }
         
-        // Fallback: check if main exists, otherwise use master
-        if self.branch_exists("main")? {
-            Ok("main".to_string())
+        let from_config = configured.filter(|branch| {
+            self.branch_exists(branch).unwrap_or(false) || self.remote_branch_exists(branch).unwrap_or(false)
+        });
+        
+        let from_remote_head = || {
+            let output = Command::new("git")
+                .args(["symbolic-ref", "--quiet", "refs/remotes/origin/HEAD"])
+                .current_dir(&self.repo_path)
+                .output()
+                .ok()?;
+            
+            let branch_ref = String::from_utf8_lossy(&output.stdout);
+            branch_ref.trim().strip_prefix("refs/remotes/origin/").map(str::to_string)
+        };
+        
+        let from_local = || {
+            ["main", "master"]
+                .into_iter()
+                .find(|branch| self.branch_exists(branch).unwrap_or(false))
+                .map(str::to_string)
+        };
+        
+        let branch = from_config
+            .or_else(from_remote_head)
+            .or_else(from_local)
+            .ok_or_else(|| anyhow::anyhow!(
+                "Could not determine the default branch; set git.default_branch in the config"
+            ))?;
+        
+        let remote_ref = format!("origin/{}", branch);
+        let reference = if (self.config.compare_remote || !self.branch_exists(&branch)?)
+            && self.remote_branch_exists(&branch)?
+        {
+            remote_ref
         } else {
-            Ok("master".to_string())
-        }
+            branch.clone()
+        };
+        
+        Ok(IntegrationTarget { branch, reference })
     }
-
-    /// Check if a directory has uncommitted changes
-    pub fn has_uncommitted_changes<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+    
+    /// Check if `origin/<branch>` exists
+    pub fn remote_branch_exists(&self, branch_name: &str) -> Result<bool> {
         use std::process::Command;
         
         let output = Command::new("git")
-            .args(&["status", "--porcelain"])
-            .current_dir(path.as_ref())
+            .args(["show-ref", "--verify", "--quiet", &format!("refs/remotes/origin/{}", branch_name)])
+            .current_dir(&self.repo_path)
             .output()
-            .map_err(|e| anyhow::anyhow!("Failed to check git status: {}", e))?;
+            .map_err(|e| anyhow::anyhow!("Failed to check branch existence: {}", e))?;
             
-        if !output.status.success() {
-            let error = String::from_utf8_lossy(&output.stderr);
-            return Err(anyhow::anyhow!("Git status failed: {}", error).into());
-        }
-        
-        Ok(!output.stdout.is_empty())
+        Ok(output.status.success())
+    }
+
+    /// Check if a directory has uncommitted changes
+    pub fn has_uncommitted_changes<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        worktree_is_dirty(path.as_ref())
     }
 
     /// Check if a branch is merged into a target branch
     pub fn is_branch_merged(&self, branch: &str, target_branch: &str) -> Result<bool> {
+        Ok(self.merge_method(branch, target_branch)?.is_some())
+    }
+    
+    /// Check whether `branch` has been merged into `target`, and how.
+    ///
+    /// Besides plain ancestry this recognises rebased and squashed branches by patch-id, the
+    /// way `git cherry` does, and branches whose changes the target already has in some other
+    /// shape by test-merging them.
+    pub fn merge_method(&self, branch: &str, target: &str) -> Result<Option<MergeMethod>> {
+        // Reopen so refs and objects written since `find()` are seen
+        let repo = gix::open(&self.repo_path).map_err(|_| GitWarpError::NotInGitRepository)?;
+        let Some(merges) = MergeAnalysis::new(&repo, &self.repo_path, target) else {
+            return Ok(None);
+        };
+        
+        let tip = repo
+            .rev_parse_single(branch)
+            .ok()
+            .and_then(|id| find_commit(&repo, id))
+            .map(|commit| commit.id);
+        let ancestry = merges.ancestry(&repo, branch, tip)?;
+        Ok(merges.merge_methods(vec![ancestry])?.pop().flatten())
+    }
+}
+
+/// Where a branch stands relative to a merge target
+enum Ancestry {
+    /// The target reaches the branch tip
+    Merged,
+    /// Both sides have commits of their own since the branch forked
+    Diverged(Divergence),
+    /// Nothing to compare: no common history, or the branch is the target itself
+    Unrelated,
+}
+
+/// The commits that tell whether a diverged branch landed on the target some other way
+struct Divergence {
+    tip: gix::ObjectId,
+    /// Where the branch forked; the newest fork point when there are several
+    base: gix::ObjectId,
+    /// Non-merge commits only the branch has
+    branch_commits: Vec<gix::ObjectId>,
+    /// Commits only the target has
+    target_commits: Vec<gix::ObjectId>,
+}
+
+/// Decides how branches merged into one target
+struct MergeAnalysis {
+    dir: PathBuf,
+    tip: gix::ObjectId,
+    tree: Option<gix::ObjectId>,
+}
+
+impl MergeAnalysis {
+    /// Resolve `reference` in `repo`, or `None` when it doesn't name a commit
+    fn new(repo: &Repository, dir: &Path, reference: &str) -> Option<Self> {
+        let target = repo.rev_parse_single(reference).ok().and_then(|id| find_commit(repo, id))?;
+        Some(Self {
+            dir: dir.to_path_buf(),
+            tip: target.id,
+            tree: target.tree_id().ok().map(|id| id.detach()),
+        })
+    }
+    
+    /// Where `branch`, whose tip is `tip`, stands relative to the target
+    fn ancestry(&self, repo: &Repository, branch: &str, tip: Option<gix::ObjectId>) -> Result<Ancestry> {
+        if let Some(tip) = tip {
+            match self.native_ancestry(repo, tip) {
+                Ok(ancestry) => return Ok(ancestry),
+                Err(e) => log::debug!("Falling back to git for the ancestry of {}: {}", branch, e),
+            }
+        }
+        self.git_ancestry(branch)
+    }
+    
+    /// [`Self::ancestry`] from one walk down the branch and the target to where they meet
+    fn native_ancestry(&self, repo: &Repository, tip: gix::ObjectId) -> Result<Ancestry> {
+        if tip == self.tip {
+            return Ok(Ancestry::Merged);
+        }
+        
+        let mut paint = Paint::new(repo);
+        paint.mark(tip, Paint::BRANCH)?;
+        paint.mark(self.tip, Paint::TARGET)?;
+        
+        let mut visited = Vec::new();
+        while let Some((id, flags, parents)) = paint.next() {
+            let mut spread = flags;
+            if flags == Paint::BOTH {
+                if id == tip {
+                    return Ok(Ancestry::Merged);
+                }
+                spread |= Paint::STALE;
+            }
+            visited.push(id);
+            for parent in parents {
+                paint.mark(parent, spread)?;
+            }
+        }
+        
+        // Commits dated alike can be visited before a side reaches them, so classify them by
+        // where the walk left their flags, as `git rev-list` does
+        if paint.flags(&tip) & Paint::TARGET != 0 {
+            return Ok(Ancestry::Merged);
+        }
+        visited.sort();
+        visited.dedup();
+        let mut branch_commits = Vec::new();
+        let mut target_commits = Vec::new();
+        let mut bases = Vec::new();
+        for id in visited {
+            match paint.flags(&id) {
+                Paint::BOTH => bases.push(id),
+                Paint::BRANCH if paint.parent_count(&id) < 2 => branch_commits.push(id),
+                Paint::TARGET => target_commits.push(id),
+                _ => {}
+            }
+        }
+        
+        // The newest fork point, when there are several
+        let Some(base) = bases.into_iter().max_by_key(|id| paint.order(id)) else {
+            return Ok(Ancestry::Unrelated);
+        };
+        Ok(Ancestry::Diverged(Divergence { tip, base, branch_commits, target_commits }))
+    }
+    
+    /// [`Self::ancestry`] answered by git, for histories gix can't walk
+    fn git_ancestry(&self, branch: &str) -> Result<Ancestry> {
+        let dir = self.dir.as_path();
+        let target = self.tip.to_string();
+        let rev_list = |args: &[&str]| -> Result<Vec<gix::ObjectId>> {
+            let output = run_git(dir, args)?;
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(|line| Ok(gix::ObjectId::from_hex(line.trim().as_bytes())?))
+                .collect()
+        };
+        
+        let Some(tip) = rev_list(&["rev-parse", "--verify", "--quiet", &format!("{}^{{commit}}", branch)])
+            .ok()
+            .and_then(|ids| ids.first().copied())
+        else {
+            return Ok(Ancestry::Unrelated);
+        };
+        let tip = tip.to_string();
+        
+        if run_git(dir, &["merge-base", "--is-ancestor", &tip, &target]).is_ok() {
+            return Ok(Ancestry::Merged);
+        }
+        // Unrelated histories
+        let Ok(base) = rev_list(&["merge-base", &target, &tip]) else {
+            return Ok(Ancestry::Unrelated);
+        };
+        
+        Ok(Ancestry::Diverged(Divergence {
+            tip: gix::ObjectId::from_hex(tip.as_bytes())?,
+            base: base[0],
+            branch_commits: rev_list(&["rev-list", "--no-merges", &format!("{}..{}", target, tip)])?,
+            target_commits: rev_list(&["rev-list", &format!("{}..{}", tip, target)])?,
+        }))
+    }
+    
+    /// How each branch merged, given the [`ancestry`](Self::ancestry) of each, in the same order.
+    ///
+    /// Patch ids for every diverged branch come from one `git diff-tree | git patch-id` run for
+    /// single commits and one for whole branches, rather than a few git processes per branch.
+    fn merge_methods(&self, ancestries: Vec<Ancestry>) -> Result<Vec<Option<MergeMethod>>> {
+        use rayon::prelude::*;
+        use std::collections::HashSet;
+        use std::fmt::Write;
+        
+        let diverged = || ancestries.iter().filter_map(|ancestry| match ancestry {
+            Ancestry::Diverged(divergence) => Some(divergence),
+            _ => None,
+        });
+        
+        let commits: HashSet<_> = diverged()
+            .flat_map(|divergence| divergence.branch_commits.iter().chain(&divergence.target_commits))
+            .collect();
+        let mut single = String::new();
+        for id in commits {
+            writeln!(single, "{}", id)?;
+        }
+        let mut whole = String::new();
+        for divergence in diverged() {
+            writeln!(whole, "{} {}", divergence.tip, divergence.base)?;
+        }
+        let commit_patches = patch_ids(&self.dir, &single)?;
+        let branch_patches = patch_ids(&self.dir, &whole)?;
+        
+        // gix handles can't be shared across threads, so each worker opens its own
+        ancestries
+            .into_par_iter()
+            .map_init(
+                || gix::open(&self.dir).ok(),
+                |repo, ancestry| match ancestry {
+                    Ancestry::Merged => Ok(Some(MergeMethod::Ancestor)),
+                    Ancestry::Unrelated => Ok(None),
+                    Ancestry::Diverged(divergence) => match repo {
+                        Some(repo) => self.diverged_method(repo, &divergence, &commit_patches, &branch_patches),
+                        None => Err(GitWarpError::NotInGitRepository.into()),
+                    },
+                },
+            )
+            .collect()
+    }
+    
+    /// Detect rebase, squash and identical-tree merges of a branch that isn't an ancestor
+    fn diverged_method(
+        &self,
+        repo: &Repository,
+        divergence: &Divergence,
+        commit_patches: &HashMap<gix::ObjectId, String>,
+        branch_patches: &HashMap<gix::ObjectId, String>,
+    ) -> Result<Option<MergeMethod>> {
+        let landed: std::collections::HashSet<&String> = divergence
+            .target_commits
+            .iter()
+            .filter_map(|id| commit_patches.get(id))
+            .collect();
+        
+        // A squash merge lands the branch's whole diff as one commit. Checked before rebases,
+        // which a squashed single-commit branch would look like too.
+        if branch_patches.get(&divergence.tip).is_some_and(|id| landed.contains(id)) {
+            return Ok(Some(MergeMethod::Squash));
+        }
+        
+        // Every commit on the branch has a patch-equivalent commit on the target
+        let rebased = divergence
+            .branch_commits
+            .iter()
+            .all(|id| commit_patches.get(id).is_some_and(|id| landed.contains(id)));
+        if rebased && !divergence.branch_commits.is_empty() {
+            return Ok(Some(MergeMethod::Rebase));
+        }
+        
+        // Changes that arrived in a different shape still merge to the target's own tree
+        let tree_merge = tree_merge(repo, divergence.base, divergence.tip, self.tip).unwrap_or_else(|e| {
+            log::debug!("Falling back to git to test-merge {}: {}", divergence.tip, e);
+            TreeMerge::Undecided
+        });
+        let identical = match tree_merge {
+            TreeMerge::Unchanged => true,
+            TreeMerge::Changed => false,
+            TreeMerge::Undecided => self.merges_to_own_tree(divergence.tip)?,
+        };
+        Ok(identical.then_some(MergeMethod::IdenticalTree))
+    }
+    
+    /// Whether `git merge-tree` merges `tip` into the target without changing its tree.
+    /// Always `false` with a git too old to test-merge.
+    fn merges_to_own_tree(&self, tip: gix::ObjectId) -> Result<bool> {
         use std::process::Command;
         
+        let Some(tree) = self.tree else {
+            return Ok(false);
+        };
+        if !git_can_merge_trees() {
+            return Ok(false);
+        }
+        
         let output = Command::new("git")
-            .args(&["merge-base", "--is-ancestor", branch, target_branch])
-            .current_dir(&self.repo_path)
+            .args(["merge-tree", "--write-tree", &self.tip.to_string(), &tip.to_string()])
+            .current_dir(&self.dir)
             .output()
-            .map_err(|e| anyhow::anyhow!("Failed to check merge status: {}", e))?;
-            
-        Ok(output.status.success())
+            .map_err(|e| anyhow::anyhow!("Failed to test-merge branch: {}", e))?;
+        // Exit code 1 is a conflicted merge, which can't be the target's tree
+        match output.status.code() {
+            Some(0) => {
+                let merged_tree = String::from_utf8_lossy(&output.stdout);
+                Ok(merged_tree.lines().next() == Some(tree.to_string().as_str()))
+            }
+            Some(1) => Ok(false),
+            _ => {
+                let error = String::from_utf8_lossy(&output.stderr);
+                log::warn!("Could not test-merge {} into the target: {}", tip, error.trim());
+                Ok(false)
+            }
+        }
+    }
+}
+
+/// What merging a branch into its target does to the target's tree, judged by whole files
+enum TreeMerge {
+    /// Every file the branch changed is already the same on the target
+    Unchanged,
+    /// The branch changes a file the target left alone
+    Changed,
+    /// Both sides changed some file differently, which only a content merge can settle
+    Undecided,
+}
+
+/// Compare each file the branch changed between `base` and `tip` with the same file on `target`
+fn tree_merge(repo: &Repository, base: gix::ObjectId, tip: gix::ObjectId, target: gix::ObjectId) -> Result<TreeMerge> {
+    use gix::object::tree::diff::{change::Event, for_each::Error, Action};
+    
+    let tree = |id: gix::ObjectId| -> Result<gix::Tree<'_>> {
+        let commit = find_commit(repo, id).ok_or_else(|| anyhow::anyhow!("{} is not a commit", id))?;
+        Ok(commit.tree()?)
+    };
+    let (base_tree, tip_tree, target_tree) = (tree(base)?, tree(tip)?, tree(target)?);
+    // Directories are compared through the files in them
+    let file = |mode: gix::object::tree::EntryMode, id: gix::Id<'_>| (!mode.is_tree()).then(|| (mode, id.detach()));
+    
+    let mut outcome = TreeMerge::Unchanged;
+    let mut buf = Vec::new();
+    let diff = base_tree.changes()?.track_path().track_rewrites(None).for_each_to_obtain_tree(&tip_tree, |change| {
+        let (before, after) = match change.event {
+            Event::Addition { entry_mode, id } => (None, file(entry_mode, id)),
+            Event::Deletion { entry_mode, id } => (file(entry_mode, id), None),
+            Event::Modification { previous_entry_mode, previous_id, entry_mode, id } => {
+                (file(previous_entry_mode, previous_id), file(entry_mode, id))
+            }
+            // Not reported with rename tracking off
+            Event::Rewrite { .. } => return Ok(Action::Continue),
+        };
+        if before.is_none() && after.is_none() {
+            return Ok(Action::Continue);
+        }
+        
+        let on_target = target_tree
+            .lookup_entry(change.location.split(|byte| *byte == b'/'), &mut buf)?
+            .map(|entry| (entry.mode(), entry.object_id()))
+            .filter(|(mode, _)| !mode.is_tree());
+        if on_target == after {
+            Ok(Action::Continue)
+        } else if on_target == before {
+            outcome = TreeMerge::Changed;
+            Ok(Action::Cancel)
+        } else {
+            outcome = TreeMerge::Undecided;
+            Ok::<_, gix::object::find::existing::Error>(Action::Continue)
+        }
+    });
+    
+    match diff {
+        Ok(_) | Err(Error::Diff(gix::diff::tree::changes::Error::Cancelled)) => Ok(outcome),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Whether git has `merge-tree --write-tree`, added in 2.38. Checked once, warning if it's missing.
+fn git_can_merge_trees() -> bool {
+    use std::sync::OnceLock;
+    
+    static SUPPORTED: OnceLock<bool> = OnceLock::new();
+    *SUPPORTED.get_or_init(|| {
+        let output = std::process::Command::new("git").arg("--version").output();
+        let version = output
+            .ok()
+            .and_then(|output| parse_git_version(&String::from_utf8_lossy(&output.stdout)));
+        let supported = version.is_some_and(|version| version >= (2, 38));
+        if !supported {
+            log::warn!(
+                "git 2.38 or newer is needed to test-merge branches; \
+                 branches merged with changes reshaped may show as unmerged"
+            );
+        }
+        supported
+    })
+}
+
+/// The major and minor version in `git --version` output, e.g. `git version 2.39.5`
+fn parse_git_version(output: &str) -> Option<(u32, u32)> {
+    let version = output.trim().strip_prefix("git version ")?;
+    let mut parts = version.split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+/// Flags spreading down from a branch and its merge target, the way `git merge-base` paints them.
+///
+/// Commits are visited newest first: by commit-graph generation where the graph has them, by
+/// committer date otherwise, and in the order they were queued when dated alike. The walk ends
+/// once everything still queued is reachable from both sides, so it covers where the two differ
+/// and not the history they share.
+struct Paint<'repo> {
+    graph: gix::revwalk::Graph<'repo, gix::revwalk::graph::Commit<u8>>,
+    queue: std::collections::BinaryHeap<(PaintOrder, std::cmp::Reverse<u64>, gix::ObjectId)>,
+    queued: u64,
+    /// Queued commits not yet known to be below a common ancestor
+    pending: usize,
+}
+
+/// Commit-graph generation, then commit time
+type PaintOrder = (u32, i64);
+
+impl<'repo> Paint<'repo> {
+    const BRANCH: u8 = 1;
+    const TARGET: u8 = 2;
+    const BOTH: u8 = Self::BRANCH | Self::TARGET;
+    /// Below a common ancestor, so nothing the walk is looking for
+    const STALE: u8 = 4;
+    const QUEUED: u8 = 8;
+    
+    fn new(repo: &'repo Repository) -> Self {
+        Self {
+            graph: repo.revision_graph(),
+            queue: Default::default(),
+            queued: 0,
+            pending: 0,
+        }
+    }
+    
+    /// Add `flags` to `id`, queueing it again if that tells its parents something new.
+    /// Commits missing from a shallow clone are skipped.
+    fn mark(&mut self, id: gix::ObjectId, flags: u8) -> Result<()> {
+        let Some(commit) = self.graph.try_lookup_or_insert_commit(id, |_| {})? else {
+            return Ok(());
+        };
+        let old = commit.data;
+        let new = old | flags;
+        if new == old {
+            return Ok(());
+        }
+        commit.data = new | Self::QUEUED;
+        
+        if old & Self::QUEUED == 0 {
+            // Commits outside the commit-graph are newer than everything in it
+            let order = (commit.generation.unwrap_or(u32::MAX), commit.commit_time);
+            self.queue.push((order, std::cmp::Reverse(self.queued), id));
+            self.queued += 1;
+            if new & Self::STALE == 0 {
+                self.pending += 1;
+            }
+        } else if old & Self::STALE == 0 && new & Self::STALE != 0 {
+            self.pending -= 1;
+        }
+        Ok(())
+    }
+    
+    /// The newest queued commit with its flags and parents, until only stale ones are left
+    fn next(&mut self) -> Option<(gix::ObjectId, u8, Vec<gix::ObjectId>)> {
+        if self.pending == 0 {
+            return None;
+        }
+        let (_, _, id) = self.queue.pop()?;
+        let commit = self.graph.get_mut(&id)?;
+        commit.data &= !Self::QUEUED;
+        let flags = commit.data;
+        if flags & Self::STALE == 0 {
+            self.pending -= 1;
+        }
+        Some((id, flags, commit.parents.to_vec()))
+    }
+    
+    fn flags(&self, id: &gix::oid) -> u8 {
+        self.graph.get(id).map_or(0, |commit| commit.data & !Self::QUEUED)
+    }
+    
+    fn parent_count(&self, id: &gix::oid) -> usize {
+        self.graph.get(id).map_or(0, |commit| commit.parents.len())
+    }
+    
+    fn order(&self, id: &gix::oid) -> Option<PaintOrder> {
+        let commit = self.graph.get(id)?;
+        Some((commit.generation.unwrap_or(u32::MAX), commit.commit_time))
+    }
+}
+
+/// Classify one worktree's branch against the integration target, leaving how it merged to
+/// [`MergeAnalysis::merge_methods`], which settles all worktrees at once
+fn inspect_worktree(
+    repo: &Repository,
+    worktree: &WorktreeInfo,
+    target_branch: &str,
+    merges: Option<&MergeAnalysis>,
+) -> Result<(BranchStatus, Ancestry)> {
+    use gix::remote::Direction;
+    
+    let branch = &worktree.branch;
+    let has_remote = repo.branch_remote_name(branch.as_str(), Direction::Fetch).is_some();
+    let upstream_gone = has_remote && upstream_gone(repo, branch);
+    let tip = branch_tip(repo, branch);
+    
+    let ancestry = match merges {
+        Some(merges) if *branch != target_branch => merges.ancestry(repo, branch, tip)?,
+        _ => Ancestry::Unrelated,
+    };
+    
+    let tip_tree = tip
+        .and_then(|tip| find_commit(repo, tip))
+        .and_then(|commit| commit.tree_id().ok())
+        .map(|id| id.detach());
+    let is_identical = tip_tree.is_some() && tip_tree == merges.and_then(|merges| merges.tree);
+    
+    let has_uncommitted_changes = worktree_is_dirty(&worktree.path).unwrap_or(false);
+    
+    let status = BranchStatus {
+        branch: branch.clone(),
+        path: worktree.path.clone(),
+        has_remote,
+        upstream_gone,
+        is_merged: false,
+        merged_via: None,
+        is_identical,
+        has_uncommitted_changes,
+        locked: worktree.locked.clone(),
+    };
+    Ok((status, ancestry))
+}
+
+/// The commit a local branch points at
+fn branch_tip(repo: &Repository, branch: &str) -> Option<gix::ObjectId> {
+    let mut reference = repo.try_find_reference(format!("refs/heads/{}", branch).as_str()).ok()??;
+    reference.peel_to_id_in_place().ok().map(|id| id.detach())
+}
+
+/// The commit an object id names, peeling annotated tags
+fn find_commit(repo: &Repository, id: impl Into<gix::ObjectId>) -> Option<gix::Commit<'_>> {
+    let object = repo.find_object(id).ok()?.peel_to_kind(gix::object::Kind::Commit).ok()?;
+    object.try_into_commit().ok()
+}
+
+/// Whether the branch has an upstream configured whose remote-tracking ref no longer exists
+fn upstream_gone(repo: &Repository, branch: &str) -> bool {
+    let Ok(name) = gix::refs::FullName::try_from(format!("refs/heads/{}", branch)) else {
+        return false;
+    };
+    match repo.branch_remote_tracking_ref_name(name.as_ref(), gix::remote::Direction::Fetch) {
+        Some(Ok(tracking)) => matches!(repo.try_find_reference(tracking.as_ref()), Ok(None)),
+        Some(Err(e)) => {
+            log::debug!("Could not resolve the upstream of {}: {}", branch, e);
+            false
+        }
+        None => false,
+    }
+}
+
+/// Gather upstream, ahead/behind, last commit and dirty state for one worktree
+fn describe_worktree(worktree: &WorktreeInfo, target: Option<&str>) -> WorktreeDetails {
+    let mut details = WorktreeDetails::default();
+    if worktree.is_bare || worktree.prunable.is_some() {
+        return details;
+    }
+    
+    let dir = worktree.path.as_path();
+    let stdout = |args: &[&str]| {
+        run_git(dir, args)
+            .ok()
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim_end().to_string())
+    };
+    let ahead_behind = |other: &str| {
+        let counts = stdout(&["rev-list", "--left-right", "--count", &format!("HEAD...{}", other)])?;
+        let (ahead, behind) = counts.split_once('\t')?;
+        Some(AheadBehind {
+            ahead: ahead.parse().ok()?,
+            behind: behind.parse().ok()?,
+        })
+    };
+    
+    if !worktree.branch.is_empty() {
+        details.upstream = stdout(&["rev-parse", "--abbrev-ref", "--symbolic-full-name", "HEAD@{upstream}"])
+            .filter(|upstream| !upstream.is_empty());
+    }
+    if let Some(upstream) = &details.upstream {
+        details.upstream_status = ahead_behind(upstream);
+    }
+    if let Some(target) = target {
+        details.default_status = ahead_behind(target);
+    }
+    
+    details.last_commit = stdout(&["log", "-1", "--format=%s%x00%an%x00%ct", "HEAD"]).and_then(|line| {
+        let mut fields = line.splitn(3, '\0');
+        Some(CommitSummary {
+            subject: fields.next()?.to_string(),
+            author: fields.next()?.to_string(),
+            timestamp: fields.next()?.parse().ok()?,
+        })
+    });
+    
+    details.dirty_files = stdout(&["status", "--porcelain"])
+        .map(|status| status.lines().count())
+        .unwrap_or(0);
+    
+    details
+}
+
+/// The git dir of the worktree rooted at `worktree`, not of a repository around it.
+///
+/// Read straight from `.git`, so it works without a usable current directory.
+pub(crate) fn worktree_git_dir(worktree: &Path) -> Option<PathBuf> {
+    let dot_git = worktree.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    // Linked worktrees have a `.git` file pointing at their git dir
+    let gitfile = std::fs::read_to_string(&dot_git).ok()?;
+    let git_dir = gitfile.strip_prefix("gitdir:")?.trim();
+    Some(worktree.join(git_dir))
+}
+
+/// Run git in `dir`, failing with its stderr if it exits unsuccessfully
+fn run_git(dir: &Path, args: &[&str]) -> Result<std::process::Output> {
+    use std::process::Command;
+    
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|e| anyhow::anyhow!("Failed to run git {}: {}", args[0], e))?;
+    
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow::anyhow!("git {} failed: {}", args[0], error.trim()));
+    }
+    Ok(output)
+}
+
+/// Stable patch ids for the revisions on each line of `revisions`, keyed by the first of them.
+///
+/// A line with one commit diffs it against its parent, as `git log -p` would; merges get none.
+/// A line `<tip> <base>` diffs the two, so a whole branch gets one id, as a squash of it would.
+/// Every line goes through a single `git diff-tree | git patch-id` pipeline.
+fn patch_ids(dir: &Path, revisions: &str) -> Result<HashMap<gix::ObjectId, String>> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    
+    if revisions.is_empty() {
+        return Ok(HashMap::new());
+    }
+    
+    let mut diff = Command::new("git")
+        .args(["diff-tree", "--stdin", "-p", "--root", "--no-renames", "--no-color", "--no-ext-diff"])
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("Failed to run git diff-tree: {}", e))?;
+    let patches = diff.stdout.take().expect("stdout is piped");
+    let patch_id = Command::new("git")
+        .args(["patch-id", "--stable"])
+        .current_dir(dir)
+        .stdin(patches)
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("Failed to run git patch-id: {}", e))?;
+    
+    // Feed stdin from another thread so full pipes downstream can't stall the write
+    let mut stdin = diff.stdin.take().expect("stdin is piped");
+    let (output, status) = std::thread::scope(|scope| {
+        let writer = scope.spawn(move || stdin.write_all(revisions.as_bytes()));
+        let output = patch_id.wait_with_output();
+        let status = diff.wait();
+        writer.join().expect("diff-tree writer panicked")?;
+        Ok::<_, std::io::Error>((output?, status?))
+    })?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("git diff-tree failed with {}", status));
+    }
+    
+    // Each line is `<patch id> <commit>`, the commit being the first revision of its input line
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .map(|(patch_id, commit)| Ok((gix::ObjectId::from_hex(commit.trim().as_bytes())?, patch_id.to_string())))
+        .collect()
+}
+
+/// Whether the worktree at `path` has staged, unstaged or untracked changes
+fn worktree_is_dirty(path: &Path) -> Result<bool> {
+    match worktree_is_dirty_native(path) {
+        Ok(dirty) => Ok(dirty),
+        Err(e) => {
+            log::debug!("Falling back to git status in {}: {}", path.display(), e);
+            let status = run_git(path, &["status", "--porcelain"])?;
+            Ok(!status.stdout.is_empty())
+        }
+    }
+}
+
+/// [`worktree_is_dirty`] through gix, which can't tell for an unborn HEAD
+fn worktree_is_dirty_native(path: &Path) -> Result<bool> {
+    let repo = gix::open(path)?;
+    
+    // Staged changes: the index differs from the one HEAD's tree would give
+    let index = repo.index_or_empty()?;
+    let committed = repo.index_from_tree(&repo.head_tree_id()?)?;
+    let entry = |state: &gix::index::State, e: &gix::index::Entry| (e.path(state).to_owned(), e.id, e.mode, e.stage_raw());
+    let staged = index.entries().len() != committed.entries().len()
+        || index
+            .entries()
+            .iter()
+            .zip(committed.entries())
+            .any(|(ours, theirs)| entry(&index, ours) != entry(&committed, theirs));
+    if staged {
+        return Ok(true);
+    }
+    
+    // Unstaged and untracked files; ignored ones don't count
+    let mut changes = repo
+        .status(gix::progress::Discard)?
+        .into_index_worktree_iter(Vec::new())?;
+    match changes.next() {
+        Some(change) => change.map(|_| true).map_err(Into::into),
+        None => Ok(false),
+    }
+}
+
+/// Fill in the branch and HEAD of a worktree from its repository
+fn describe_head(repo: &Repository, info: &mut WorktreeInfo) -> Result<()> {
+    use gix::refs::Category;
+    
+    let head = repo.head()?;
+    info.head = match head.id() {
+        Some(id) => id.to_string(),
+        None => repo.object_hash().null().to_string(),
+    };
+    info.is_detached = head.is_detached();
+    
+    if let Some(name) = head.referent_name()
+        && let Some((Category::LocalBranch, short_name)) = name.category_and_short_name()
+    {
+        info.branch = short_name.to_string();
+    }
+    
+    Ok(())
+}
+
+/// Parse `git worktree list --porcelain` output; the first entry is the main worktree
+fn parse_worktree_porcelain(output: &str) -> Vec<WorktreeInfo> {
+    let mut worktrees: Vec<WorktreeInfo> = Vec::new();
+    
+    for line in output.lines() {
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        
+        if key == "worktree" {
+            worktrees.push(WorktreeInfo {
+                path: PathBuf::from(value),
+                is_main: worktrees.is_empty(),
+                ..Default::default()
+            });
+            continue;
+        }
+        
+        let Some(wt) = worktrees.last_mut() else {
+            continue;
+        };
+        
+        match key {
+            "HEAD" => wt.head = value.to_string(),
+            "branch" => wt.branch = value.strip_prefix("refs/heads/").unwrap_or("").to_string(),
+            "bare" => wt.is_bare = true,
+            "detached" => wt.is_detached = true,
+            "locked" => wt.locked = Some(value.to_string()),
+            "prunable" => wt.prunable = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    
+    worktrees
+}
+
+/// A completed step of worktree creation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CreationStep {
+    /// The branch did not exist before warp created it
+    CreatedBranch(String),
+    /// The directory did not exist before warp created it
+    CreatedDirectory(PathBuf),
+    /// The directory was registered with `git worktree`
+    RegisteredWorktree(PathBuf),
+}
+
+impl std::fmt::Display for CreationStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CreationStep::CreatedBranch(branch) => write!(f, "branch '{}'", branch),
+            CreationStep::CreatedDirectory(path) => write!(f, "directory {}", path.display()),
+            CreationStep::RegisteredWorktree(path) => write!(f, "worktree registration for {}", path.display()),
+        }
+    }
+}
+
+/// Outcome of rolling back a worktree creation
+#[derive(Debug, Default)]
+pub struct RollbackReport {
+    /// Steps that were undone, in the order they were undone
+    pub undone: Vec<CreationStep>,
+    /// Steps that could not be undone, with the reason
+    pub failed: Vec<(CreationStep, String)>,
+}
+
+impl RollbackReport {
+    /// True when every recorded step was undone
+    pub fn is_clean(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// Records each completed step of a worktree creation so a failure can undo them.
+///
+/// Call `commit` once the worktree is ready. A transaction that is dropped without being
+/// committed rolls itself back.
+pub struct WorktreeTransaction<'a> {
+    repo: &'a GitRepository,
+    steps: Vec<CreationStep>,
+}
+
+impl WorktreeTransaction<'_> {
+    /// Create the worktree (and the branch, if it doesn't exist yet), recording what was created
+    pub fn create_worktree_and_branch<P: AsRef<Path>>(
+        &mut self,
+        branch_name: &str,
+        worktree_path: P,
+        from_commit: Option<&str>,
+    ) -> Result<()> {
+        self.create_worktree(WorktreeHead::Branch { name: branch_name, from: from_commit }, worktree_path)
+    }
+    
+    /// Create the worktree on a branch or at a detached revision, recording what was created
+    pub fn create_worktree<P: AsRef<Path>>(&mut self, head: WorktreeHead<'_>, worktree_path: P) -> Result<()> {
+        let worktree_path = worktree_path.as_ref();
+        self.track(head.branch(), worktree_path, |repo| repo.add_worktree(head, worktree_path, true))
+    }
+    
+    /// Create the worktree by cloning `source`, then fill in its git metadata.
+    ///
+    /// The worktree is registered without a checkout, `source` is cloned over it with
+    /// `strategy`, and the worktree's gitfile, index and tracked files are then set up for its
+    /// own branch. Commits made in the result land in this repository like any other worktree.
+    #[allow(clippy::too_many_arguments)]
+    pub fn clone_worktree_and_branch<P: AsRef<Path>>(
+        &mut self,
+        branch_name: &str,
+        worktree_path: P,
+        from_commit: Option<&str>,
+        source: &WorktreeInfo,
+        spec: &CloneSpec,
+        strategy: CloneStrategy,
+        observer: &dyn ProgressObserver,
+    ) -> Result<CloneReport> {
+        let head = WorktreeHead::Branch { name: branch_name, from: from_commit };
+        self.clone_worktree(head, worktree_path, source, spec, strategy, observer)
+    }
+    
+    /// Like `clone_worktree_and_branch`, for a branch or a detached revision
+    pub fn clone_worktree<P: AsRef<Path>>(
+        &mut self,
+        head: WorktreeHead<'_>,
+        worktree_path: P,
+        source: &WorktreeInfo,
+        spec: &CloneSpec,
+        strategy: CloneStrategy,
+        observer: &dyn ProgressObserver,
+    ) -> Result<CloneReport> {
+        let worktree_path = worktree_path.as_ref();
+        self.track(head.branch(), worktree_path, |repo| repo.add_worktree(head, worktree_path, false))?;
+        
+        // The clone replaces the directory and never carries the source's .git
+        let gitfile = std::fs::read(worktree_path.join(".git"))?;
+        let report = cow::clone_directory_with_progress(&source.path, worktree_path, spec, strategy, observer)?;
+        std::fs::write(worktree_path.join(".git"), &gitfile)?;
+        
+        self.repo.sync_cloned_worktree(worktree_path, &source.head)?;
+        
+        Ok(report)
+    }
+    
+    /// Run a git step that may create the branch and directory, recording what it created
+    fn track(
+        &mut self,
+        branch_name: Option<&str>,
+        worktree_path: &Path,
+        create: impl FnOnce(&GitRepository) -> Result<()>,
+    ) -> Result<()> {
+        let branch_existed = match branch_name {
+            Some(branch) => self.repo.branch_exists(branch)?,
+            None => true,
+        };
+        let directory_existed = worktree_path.exists();
+        
+        let result = create(self.repo);
+        
+        // Record whatever git managed to create, even if it then failed
+        if let Some(branch) = branch_name
+            && !branch_existed
+            && self.repo.branch_exists(branch).unwrap_or(false)
+        {
+            self.record(CreationStep::CreatedBranch(branch.to_string()));
+        }
+        if !directory_existed && worktree_path.exists() {
+            self.record(CreationStep::CreatedDirectory(worktree_path.to_path_buf()));
+        }
+        if result.is_ok() {
+            self.record(CreationStep::RegisteredWorktree(worktree_path.to_path_buf()));
+        }
+        
+        result
+    }
+    
+    /// Record a step performed outside the transaction
+    pub fn record(&mut self, step: CreationStep) {
+        log::debug!("Recorded creation step: {}", step);
+        self.steps.push(step);
+    }
+    
+    /// Steps completed so far, oldest first
+    pub fn steps(&self) -> &[CreationStep] {
+        &self.steps
+    }
+    
+    /// Keep everything that was created
+    pub fn commit(mut self) {
+        self.steps.clear();
+    }
+    
+    /// Undo every recorded step, newest first.
+    ///
+    /// The transaction is empty afterwards and can be used to try again.
+    pub fn rollback(&mut self) -> RollbackReport {
+        let mut report = RollbackReport::default();
+        
+        while let Some(step) = self.steps.pop() {
+            match self.undo(&step) {
+                Ok(()) => report.undone.push(step),
+                Err(e) => report.failed.push((step, e.to_string())),
+            }
+        }
+        
+        report
+    }
+    
+    fn undo(&self, step: &CreationStep) -> Result<()> {
+        match step {
+            CreationStep::RegisteredWorktree(path) => {
+                // Everything in the worktree was just created by us, so nothing of the user's is lost
+                if self.repo.remove_worktree(path, true).is_err() {
+                    // The directory may already be gone or broken; drop the registration instead
+                    if path.exists() {
+                        std::fs::remove_dir_all(path)?;
+                    }
+                    self.repo.prune_worktrees()?;
+                }
+                
+                if self.repo.list_worktrees()?.iter().any(|wt| wt.path == *path) {
+                    return Err(anyhow::anyhow!("Worktree is still registered"));
+                }
+                Ok(())
+            }
+            CreationStep::CreatedDirectory(path) => {
+                if path.exists() {
+                    std::fs::remove_dir_all(path)?;
+                }
+                Ok(())
+            }
+            CreationStep::CreatedBranch(branch) => self.repo.delete_branch(branch, true),
+        }
+    }
+}
+
+impl Drop for WorktreeTransaction<'_> {
+    fn drop(&mut self) {
+        if self.steps.is_empty() {
+            return;
+        }
+        
+        for (step, reason) in self.rollback().failed {
+            log::warn!("Could not undo {}: {}", step, reason);
+        }
     }
 }
 
@@ -433,20 +1897,20 @@ mod tests {
         
         // Initialize git repo
         Command::new("git")
-            .args(&["init"])
+            .args(["init"])
             .current_dir(repo_path)
             .output()
             .unwrap();
         
         // Configure git
         Command::new("git")
-            .args(&["config", "user.email", "test@example.com"])
+            .args(["config", "user.email", "test@example.com"])
             .current_dir(repo_path)
             .output()
             .unwrap();
         
         Command::new("git")
-            .args(&["config", "user.name", "Test User"])
+            .args(["config", "user.name", "Test User"])
             .current_dir(repo_path)
             .output()
             .unwrap();
@@ -454,13 +1918,13 @@ mod tests {
         // Create initial commit
         std::fs::write(repo_path.join("test.txt"), "test").unwrap();
         Command::new("git")
-            .args(&["add", "."])
+            .args(["add", "."])
             .current_dir(repo_path)
             .output()
             .unwrap();
         
         Command::new("git")
-            .args(&["commit", "-m", "Initial commit"])
+            .args(["commit", "-m", "Initial commit"])
             .current_dir(repo_path)
             .output()
             .unwrap();
@@ -469,4 +1933,187 @@ mod tests {
         let git_repo = GitRepository::open(repo_path);
         assert!(git_repo.is_ok());
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_parse_worktree_porcelain() {
+        let output = "\
+worktree /repo
+HEAD 1111111111111111111111111111111111111111
+branch refs/heads/main
+
+worktree /repo-wt/feature
+HEAD 2222222222222222222222222222222222222222
+branch refs/heads/feature
+locked on a USB drive
+
+worktree /repo-wt/detached
+HEAD 3333333333333333333333333333333333333333
+detached
+prunable gitdir file points to non-existent location
+";
+        let worktrees = parse_worktree_porcelain(output);
+        assert_eq!(worktrees.len(), 3);
+        
+        assert!(worktrees[0].is_main);
+        assert_eq!(worktrees[0].branch, "main");
+        
+        assert!(!worktrees[1].is_main);
+        assert_eq!(worktrees[1].branch, "feature");
+        assert_eq!(worktrees[1].locked.as_deref(), Some("on a USB drive"));
+        
+        assert!(worktrees[2].is_detached);
+        assert!(worktrees[2].branch.is_empty());
+        assert_eq!(worktrees[2].prunable.as_deref(), Some("gitdir file points to non-existent location"));
+        
+        let bare = parse_worktree_porcelain("worktree /repo.git\nbare\n");
+        assert!(bare[0].is_main && bare[0].is_bare);
+    }
+    
+    /// Run git in `dir`, returning its trimmed stdout
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git").args(args).current_dir(dir).output().unwrap();
+        assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+    
+    fn init_repo(dir: &Path) {
+        git(dir, &["init", "-q", "-b", "main"]);
+        git(dir, &["config", "user.email", "test@example.com"]);
+        git(dir, &["config", "user.name", "Test User"]);
+    }
+    
+    fn commit_file(dir: &Path, name: &str) {
+        std::fs::write(dir.join(name), name).unwrap();
+        git(dir, &["add", name]);
+        git(dir, &["commit", "-q", "-m", name]);
+    }
+    
+    #[test]
+    fn test_native_dirty_check() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path();
+        init_repo(dir);
+        std::fs::write(dir.join(".gitignore"), "*.log\n").unwrap();
+        git(dir, &["add", ".gitignore"]);
+        commit_file(dir, "tracked.txt");
+        let dirty = || worktree_is_dirty_native(dir).unwrap();
+        assert!(!dirty());
+        
+        std::fs::write(dir.join("build.log"), "ignored").unwrap();
+        assert!(!dirty(), "ignored files don't count");
+        
+        std::fs::write(dir.join("new.txt"), "untracked").unwrap();
+        assert!(dirty(), "untracked file");
+        std::fs::remove_file(dir.join("new.txt")).unwrap();
+        
+        std::fs::write(dir.join("tracked.txt"), "modified").unwrap();
+        assert!(dirty(), "unstaged change");
+        git(dir, &["add", "tracked.txt"]);
+        assert!(dirty(), "staged change");
+        git(dir, &["commit", "-q", "-m", "modified"]);
+        assert!(!dirty());
+        
+        git(dir, &["rm", "-q", "--cached", "tracked.txt"]);
+        std::fs::remove_file(dir.join("tracked.txt")).unwrap();
+        assert!(dirty(), "staged deletion");
+    }
+    
+    #[test]
+    fn test_tree_merge() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path();
+        init_repo(dir);
+        std::fs::write(dir.join("shared.txt"), "one\ntwo\nthree\nfour\nfive\n").unwrap();
+        git(dir, &["add", "shared.txt"]);
+        commit_file(dir, "base.txt");
+        
+        // The target picked up the branch's file by hand, alongside work of its own
+        git(dir, &["checkout", "-q", "-b", "landed"]);
+        commit_file(dir, "feature.txt");
+        git(dir, &["checkout", "-q", "-b", "fresh", "main"]);
+        commit_file(dir, "fresh.txt");
+        git(dir, &["checkout", "-q", "-b", "edited", "main"]);
+        std::fs::write(dir.join("shared.txt"), "one\ntwo\nthree\nfour\nFIVE\n").unwrap();
+        git(dir, &["commit", "-q", "-am", "edit the end"]);
+        git(dir, &["checkout", "-q", "main"]);
+        commit_file(dir, "feature.txt");
+        std::fs::write(dir.join("shared.txt"), "ONE\ntwo\nthree\nfour\nfive\n").unwrap();
+        git(dir, &["commit", "-q", "-am", "edit the start"]);
+        
+        let repo = gix::open(dir).unwrap();
+        let id = |rev: &str| gix::ObjectId::from_hex(git(dir, &["rev-parse", rev]).as_bytes()).unwrap();
+        let merge = |branch: &str| tree_merge(&repo, id("main~2"), id(branch), id("main")).unwrap();
+        assert!(matches!(merge("landed"), TreeMerge::Unchanged));
+        assert!(matches!(merge("fresh"), TreeMerge::Changed));
+        assert!(matches!(merge("edited"), TreeMerge::Undecided));
+    }
+    
+    #[test]
+    fn test_parse_git_version() {
+        assert_eq!(parse_git_version("git version 2.39.5\n"), Some((2, 39)));
+        assert_eq!(parse_git_version("git version 2.37.1 (Apple Git-137.1)"), Some((2, 37)));
+        assert_eq!(parse_git_version("git version 2.45.2.windows.1"), Some((2, 45)));
+        assert_eq!(parse_git_version("not git"), None);
+    }
+    
+    #[test]
+    fn test_native_ancestry_matches_git() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path();
+        init_repo(dir);
+        commit_file(dir, "base.txt");
+        git(dir, &["checkout", "-q", "-b", "feature"]);
+        commit_file(dir, "feature-1.txt");
+        git(dir, &["checkout", "-q", "-b", "merged"]);
+        commit_file(dir, "merged.txt");
+        git(dir, &["checkout", "-q", "feature"]);
+        commit_file(dir, "feature-2.txt");
+        git(dir, &["checkout", "-q", "main"]);
+        commit_file(dir, "main-1.txt");
+        git(dir, &["merge", "-q", "--no-edit", "merged"]);
+        commit_file(dir, "main-2.txt");
+        
+        let repo = gix::open(dir).unwrap();
+        let merges = MergeAnalysis::new(&repo, dir, "main").unwrap();
+        let rev_list = |args: &[&str]| -> Vec<String> {
+            let mut ids: Vec<_> = git(dir, args).lines().map(str::to_string).collect();
+            ids.sort();
+            ids
+        };
+        let sorted = |ids: &[gix::ObjectId]| -> Vec<String> {
+            let mut ids: Vec<_> = ids.iter().map(|id| id.to_string()).collect();
+            ids.sort();
+            ids
+        };
+        
+        let tip = branch_tip(&repo, "merged");
+        assert!(matches!(merges.ancestry(&repo, "merged", tip).unwrap(), Ancestry::Merged));
+        
+        let tip = branch_tip(&repo, "feature");
+        let Ancestry::Diverged(divergence) = merges.ancestry(&repo, "feature", tip).unwrap() else {
+            panic!("feature should have diverged from main");
+        };
+        assert_eq!(divergence.base.to_string(), git(dir, &["merge-base", "main", "feature"]));
+        assert_eq!(sorted(&divergence.branch_commits), rev_list(&["rev-list", "--no-merges", "main..feature"]));
+        assert_eq!(sorted(&divergence.target_commits), rev_list(&["rev-list", "feature..main"]));
+        
+        let Ancestry::Diverged(from_git) = merges.git_ancestry("feature").unwrap() else {
+            panic!("git should agree that feature diverged");
+        };
+        assert_eq!(from_git.base, divergence.base);
+        assert_eq!(sorted(&from_git.target_commits), sorted(&divergence.target_commits));
+        
+        // Commit-graph generations order the walk instead of dates, to the same result
+        git(dir, &["commit-graph", "write", "--reachable"]);
+        let repo = gix::open(dir).unwrap();
+        let tip = branch_tip(&repo, "merged");
+        assert!(matches!(merges.ancestry(&repo, "merged", tip).unwrap(), Ancestry::Merged));
+        let tip = branch_tip(&repo, "feature");
+        let Ancestry::Diverged(from_graph) = merges.ancestry(&repo, "feature", tip).unwrap() else {
+            panic!("feature should have diverged from main with a commit-graph");
+        };
+        assert_eq!(from_graph.base, divergence.base);
+        assert_eq!(sorted(&from_graph.branch_commits), sorted(&divergence.branch_commits));
+        assert_eq!(sorted(&from_graph.target_commits), sorted(&divergence.target_commits));
+    }
+}
@@ -1,10 +1,30 @@
 use crate::error::Result;
+use serde::Serialize;
 use serde_json::{Value, json};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::fs;
 
 pub struct HooksManager;
 
+/// Installation state of the git-warp hooks, for `warp hooks-status`
+#[derive(Debug, Clone, Serialize)]
+pub struct HooksStatus {
+    pub settings: Vec<SettingsStatus>,
+}
+
+/// One Claude Code settings file
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingsStatus {
+    /// `user` or `project`
+    pub level: String,
+    /// None when the location can't be determined
+    pub path: Option<PathBuf>,
+    pub exists: bool,
+    /// Number of git-warp hooks per hook type
+    pub hooks: BTreeMap<String, usize>,
+}
+
 impl HooksManager {
     pub fn install_hooks(level: Option<&str>) -> Result<()> {
         let hooks_config = Self::get_hooks_config();
@@ -43,34 +63,51 @@ impl HooksManager {
         Self::remove_hooks_from_settings(settings_path)
     }
     
+    /// Where the Claude Code settings live and which git-warp hooks they contain
+    pub fn hooks_status() -> Result<HooksStatus> {
+        let mut settings = Vec::new();
+        for (level, path) in [
+            ("user", Self::get_user_settings_path()),
+            ("project", Self::get_project_settings_path()),
+        ] {
+            let path = path.ok();
+            let exists = path.as_ref().is_some_and(|path| path.exists());
+            let hooks = match &path {
+                Some(path) if exists => Self::count_hooks(path)?,
+                _ => BTreeMap::new(),
+            };
+            settings.push(SettingsStatus {
+                level: level.to_string(),
+                path,
+                exists,
+                hooks,
+            });
+        }
+        
+        Ok(HooksStatus { settings })
+    }
+    
     pub fn show_hooks_status() -> Result<()> {
         println!("🔧 Git-Warp Claude Code Integration Status");
         println!("==========================================");
         
-        // Check user level settings
-        match Self::get_user_settings_path() {
-            Ok(path) => {
-                if path.exists() {
-                    println!("✅ User settings: {}", path.display());
-                    Self::show_hooks_for_path(&path)?;
-                } else {
-                    println!("❌ User settings: Not found");
-                }
-            }
-            Err(_) => println!("❌ User settings: Unable to locate"),
-        }
-        
-        // Check project level settings
-        match Self::get_project_settings_path() {
-            Ok(path) => {
-                if path.exists() {
-                    println!("✅ Project settings: {}", path.display());
-                    Self::show_hooks_for_path(&path)?;
-                } else {
-                    println!("❌ Project settings: Not found");
+        for settings in Self::hooks_status()?.settings {
+            let label = if settings.level == "user" { "User" } else { "Project" };
+            match &settings.path {
+                Some(path) if settings.exists => {
+                    println!("✅ {} settings: {}", label, path.display());
+                    if settings.hooks.is_empty() {
+                        println!("  No git-warp hooks installed");
+                    } else {
+                        println!("  ✓ Hooks installed:");
+                        for (hook_type, count) in &settings.hooks {
+                            println!("    {}: {} git-warp hook(s)", hook_type, count);
+                        }
+                    }
                 }
+                Some(_) => println!("❌ {} settings: Not found", label),
+                None => println!("❌ {} settings: Unable to locate", label),
             }
-            Err(_) => println!("❌ Project settings: Unable to locate"),
         }
         
         println!("\n📖 Integration Guide:");
@@ -171,17 +208,15 @@ impl HooksManager {
         let mut settings: Value = serde_json::from_str(&content)?;
         
         // Remove git-warp hooks
-        if let Some(hooks) = settings.get_mut("hooks") {
-            if let Some(hooks_obj) = hooks.as_object_mut() {
-                for (_, hook_array) in hooks_obj.iter_mut() {
-                    if let Some(array) = hook_array.as_array_mut() {
-                        array.retain(|hook| {
-                            !hook.get("git_warp_hook_id")
-                                .and_then(|id| id.as_str())
-                                .unwrap_or("")
-                                .starts_with("agent_status_")
-                        });
-                    }
+        if let Some(hooks_obj) = settings.get_mut("hooks").and_then(|hooks| hooks.as_object_mut()) {
+            for (_, hook_array) in hooks_obj.iter_mut() {
+                if let Some(array) = hook_array.as_array_mut() {
+                    array.retain(|hook| {
+                        !hook.get("git_warp_hook_id")
+                            .and_then(|id| id.as_str())
+                            .unwrap_or("")
+                            .starts_with("agent_status_")
+                    });
                 }
             }
         }
@@ -193,44 +228,32 @@ impl HooksManager {
         Ok(())
     }
     
-    fn show_hooks_for_path(path: &PathBuf) -> Result<()> {
-        if path.exists() {
-            let content = fs::read_to_string(path)?;
-            let settings: Value = serde_json::from_str(&content)?;
-            
-            let mut found_hooks = false;
-            if let Some(hooks) = settings.get("hooks") {
-                if let Some(hooks_obj) = hooks.as_object() {
-                    for (hook_type, hook_array) in hooks_obj {
-                        if let Some(array) = hook_array.as_array() {
-                            let git_warp_hooks: Vec<_> = array.iter()
-                                .filter(|hook| {
-                                    hook.get("git_warp_hook_id")
-                                        .and_then(|id| id.as_str())
-                                        .unwrap_or("")
-                                        .starts_with("agent_status_")
-                                })
-                                .collect();
-                            
-                            if !git_warp_hooks.is_empty() {
-                                if !found_hooks {
-                                    println!("  ✓ Hooks installed:");
-                                    found_hooks = true;
-                                }
-                                println!("    {}: {} git-warp hook(s)", hook_type, git_warp_hooks.len());
-                            }
-                        }
+    /// Count the git-warp hooks per hook type in a settings file
+    fn count_hooks(path: &PathBuf) -> Result<BTreeMap<String, usize>> {
+        let content = fs::read_to_string(path)?;
+        let settings: Value = serde_json::from_str(&content)?;
+        
+        let mut counts = BTreeMap::new();
+        if let Some(hooks_obj) = settings.get("hooks").and_then(|hooks| hooks.as_object()) {
+            for (hook_type, hook_array) in hooks_obj {
+                if let Some(array) = hook_array.as_array() {
+                    let git_warp_hooks = array.iter()
+                        .filter(|hook| {
+                            hook.get("git_warp_hook_id")
+                                .and_then(|id| id.as_str())
+                                .unwrap_or("")
+                                .starts_with("agent_status_")
+                        })
+                        .count();
+                    
+                    if git_warp_hooks > 0 {
+                        counts.insert(hook_type.clone(), git_warp_hooks);
                     }
                 }
             }
-            
-            if !found_hooks {
-                println!("  No git-warp hooks installed");
-            }
-        } else {
-            println!("  No settings file found");
         }
-        Ok(())
+        
+        Ok(counts)
     }
 }
 
@@ -238,7 +261,6 @@ impl HooksManager {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tempfile::tempdir;
     
     #[test]
     fn test_hooks_config_generation() {
@@ -0,0 +1,340 @@
+use crate::error::Result;
+use crate::git::worktree_git_dir;
+use crate::rewrite::{retarget_symlink, write_atomically};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The rewrite journal, relative to the worktree's git dir
+pub const JOURNAL_FILE: &str = "warp/rewrite-journal.json";
+
+/// Original contents of rewritten files, mirroring the worktree layout
+const BACKUP_DIR: &str = "warp/rewrite-backup";
+
+/// What path rewriting changed in a worktree, so it can be audited and undone.
+///
+/// Later rewrites of the same worktree are merged in; undo always goes back to the first original.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Journal {
+    /// Source root of the latest rewrite
+    pub src: PathBuf,
+    /// The rewritten worktree
+    pub dest: PathBuf,
+    /// When the latest rewrite ran, RFC 3339
+    pub rewritten_at: String,
+    pub entries: Vec<JournalEntry>,
+}
+
+/// One rewritten file or retargeted symlink, relative to the worktree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum JournalEntry {
+    File {
+        path: PathBuf,
+        replacements: usize,
+        /// Git blob id of the content before the first rewrite
+        original_hash: String,
+        /// Git blob id of the content the latest rewrite left
+        rewritten_hash: String,
+    },
+    Symlink {
+        path: PathBuf,
+        original_target: PathBuf,
+        rewritten_target: PathBuf,
+    },
+}
+
+impl JournalEntry {
+    pub fn path(&self) -> &Path {
+        match self {
+            JournalEntry::File { path, .. } | JournalEntry::Symlink { path, .. } => path,
+        }
+    }
+}
+
+impl Journal {
+    /// The journal of the worktree at `worktree`, if a rewrite left one
+    pub fn load(worktree: &Path) -> Result<Option<Self>> {
+        let Some(git_dir) = worktree_git_dir(worktree) else {
+            return Ok(None);
+        };
+        let path = git_dir.join(JOURNAL_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_slice(&fs::read(path)?)?))
+    }
+    
+    fn save(&self, git_dir: &Path) -> Result<PathBuf> {
+        let path = git_dir.join(JOURNAL_FILE);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        write_atomically(&path, &serde_json::to_vec_pretty(self)?)?;
+        Ok(path)
+    }
+}
+
+/// Records changes while a rewrite runs, backing up originals before they're overwritten
+#[derive(Debug)]
+pub(crate) struct JournalWriter {
+    git_dir: PathBuf,
+    journal: Mutex<Journal>,
+}
+
+impl JournalWriter {
+    /// Start journaling a rewrite of `dest`; `None` when it isn't a git worktree
+    pub(crate) fn open(src: &Path, dest: &Path) -> Result<Option<Self>> {
+        let Some(git_dir) = worktree_git_dir(dest) else {
+            return Ok(None);
+        };
+        let mut journal = Journal::load(dest)?.unwrap_or_default();
+        journal.src = src.to_path_buf();
+        journal.dest = dest.to_path_buf();
+        journal.rewritten_at = chrono::Utc::now().to_rfc3339();
+        
+        Ok(Some(Self {
+            git_dir,
+            journal: Mutex::new(journal),
+        }))
+    }
+    
+    /// Call before `relative` is overwritten with `rewritten`
+    pub(crate) fn record_file(&self, relative: &Path, original: &[u8], rewritten: &[u8], replacements: usize) -> Result<()> {
+        let mut journal = self.journal.lock().unwrap();
+        let rewritten_hash = blob_hash(rewritten);
+        
+        if let Some(JournalEntry::File { replacements: total, rewritten_hash: latest, .. }) =
+            journal.entries.iter_mut().find(|entry| entry.path() == relative)
+        {
+            // The backup from the first rewrite is the one to restore
+            *total += replacements;
+            *latest = rewritten_hash;
+            return Ok(());
+        }
+        
+        let backup = self.git_dir.join(BACKUP_DIR).join(relative);
+        if let Some(parent) = backup.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&backup, original)?;
+        
+        journal.entries.push(JournalEntry::File {
+            path: relative.to_path_buf(),
+            replacements,
+            original_hash: blob_hash(original),
+            rewritten_hash,
+        });
+        Ok(())
+    }
+    
+    pub(crate) fn record_symlink(&self, relative: &Path, original: &Path, rewritten: &Path) {
+        let mut journal = self.journal.lock().unwrap();
+        
+        if let Some(JournalEntry::Symlink { rewritten_target, .. }) =
+            journal.entries.iter_mut().find(|entry| entry.path() == relative)
+        {
+            *rewritten_target = rewritten.to_path_buf();
+            return;
+        }
+        
+        journal.entries.push(JournalEntry::Symlink {
+            path: relative.to_path_buf(),
+            original_target: original.to_path_buf(),
+            rewritten_target: rewritten.to_path_buf(),
+        });
+    }
+    
+    /// Write the journal, unless nothing was ever rewritten
+    pub(crate) fn finish(self) -> Result<Option<PathBuf>> {
+        let mut journal = self.journal.into_inner().unwrap();
+        if journal.entries.is_empty() {
+            return Ok(None);
+        }
+        journal.entries.sort_by(|a, b| a.path().cmp(b.path()));
+        journal.save(&self.git_dir).map(Some)
+    }
+}
+
+/// Keeps a worktree's journal out of the way while one more rewrite runs, so that rewrite can be
+/// undone on its own, e.g. when a move fails half way
+pub(crate) struct JournalAside {
+    git_dir: PathBuf,
+}
+
+impl JournalAside {
+    pub(crate) fn set(worktree: &Path) -> Result<Self> {
+        let git_dir = worktree_git_dir(worktree)
+            .ok_or_else(|| anyhow::anyhow!("{} is not a git worktree", worktree.display()))?;
+        for name in [JOURNAL_FILE, BACKUP_DIR] {
+            let path = git_dir.join(name);
+            if path.exists() {
+                fs::rename(&path, aside(&path))?;
+            }
+        }
+        Ok(Self { git_dir })
+    }
+    
+    /// Undo the rewrite made since, then put the earlier journal back
+    pub(crate) fn restore(self, worktree: &Path) -> Result<()> {
+        if self.git_dir.join(JOURNAL_FILE).exists() {
+            let report = undo(worktree)?;
+            if let Some((path, reason)) = report.skipped.first() {
+                return Err(anyhow::anyhow!("{} can't be restored: {}", path.display(), reason));
+            }
+        }
+        
+        for name in [JOURNAL_FILE, BACKUP_DIR] {
+            let path = self.git_dir.join(name);
+            if aside(&path).exists() {
+                fs::rename(aside(&path), &path)?;
+            }
+        }
+        Ok(())
+    }
+    
+    /// Fold the rewrite made since into the earlier journal, which keeps the first originals
+    pub(crate) fn merge(self) -> Result<()> {
+        let journal_path = self.git_dir.join(JOURNAL_FILE);
+        let backup_dir = self.git_dir.join(BACKUP_DIR);
+        let (earlier_path, earlier_backups) = (aside(&journal_path), aside(&backup_dir));
+        
+        if !journal_path.exists() {
+            // Nothing was rewritten this time
+            for path in [&journal_path, &backup_dir] {
+                if aside(path).exists() {
+                    fs::rename(aside(path), path)?;
+                }
+            }
+            return Ok(());
+        }
+        if !earlier_path.exists() {
+            let _ = fs::remove_dir_all(&earlier_backups);
+            return Ok(());
+        }
+        
+        let mut earlier: Journal = serde_json::from_slice(&fs::read(&earlier_path)?)?;
+        let latest: Journal = serde_json::from_slice(&fs::read(&journal_path)?)?;
+        for entry in latest.entries {
+            match (earlier.entries.iter_mut().find(|earlier| earlier.path() == entry.path()), entry) {
+                (
+                    Some(JournalEntry::File { replacements, rewritten_hash, .. }),
+                    JournalEntry::File { replacements: more, rewritten_hash: latest_hash, .. },
+                ) => {
+                    *replacements += more;
+                    *rewritten_hash = latest_hash;
+                }
+                (
+                    Some(JournalEntry::Symlink { rewritten_target, .. }),
+                    JournalEntry::Symlink { rewritten_target: latest_target, .. },
+                ) => *rewritten_target = latest_target,
+                (_, entry) => {
+                    // First rewrite of this path, so its backup holds the original
+                    if let JournalEntry::File { path, .. } = &entry {
+                        let backup = earlier_backups.join(path);
+                        if let Some(parent) = backup.parent() {
+                            fs::create_dir_all(parent)?;
+                        }
+                        fs::rename(backup_dir.join(path), backup)?;
+                    }
+                    earlier.entries.retain(|earlier| earlier.path() != entry.path());
+                    earlier.entries.push(entry);
+                }
+            }
+        }
+        
+        earlier.src = latest.src;
+        earlier.dest = latest.dest;
+        earlier.rewritten_at = latest.rewritten_at;
+        earlier.entries.sort_by(|a, b| a.path().cmp(b.path()));
+        
+        // What's left in the latest backups are intermediate versions nobody will restore
+        fs::remove_dir_all(&backup_dir)?;
+        if earlier_backups.exists() {
+            fs::rename(&earlier_backups, &backup_dir)?;
+        }
+        earlier.save(&self.git_dir)?;
+        fs::remove_file(earlier_path)?;
+        Ok(())
+    }
+}
+
+/// Where [`JournalAside`] keeps `path`
+fn aside(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".aside");
+    name.into()
+}
+
+/// Outcome of undoing a rewrite, with paths relative to the worktree
+#[derive(Debug, Clone, Default)]
+pub struct UndoReport {
+    pub restored: Vec<PathBuf>,
+    /// Entries left as they are, with the reason; they stay in the journal
+    pub skipped: Vec<(PathBuf, String)>,
+}
+
+/// Restore every file and symlink the journal of `worktree` recorded.
+///
+/// Anything changed since it was rewritten is skipped rather than clobbered.
+pub fn undo(worktree: &Path) -> Result<UndoReport> {
+    let git_dir = worktree_git_dir(worktree).ok_or_else(|| anyhow::anyhow!("{} is not a git worktree", worktree.display()))?;
+    let mut journal = Journal::load(worktree)?
+        .ok_or_else(|| anyhow::anyhow!("No rewrite journal for {}", worktree.display()))?;
+    
+    let mut report = UndoReport::default();
+    let mut remaining = Vec::new();
+    for entry in journal.entries {
+        match restore(worktree, &git_dir, &entry) {
+            Ok(()) => report.restored.push(entry.path().to_path_buf()),
+            Err(reason) => {
+                report.skipped.push((entry.path().to_path_buf(), reason.to_string()));
+                remaining.push(entry);
+            }
+        }
+    }
+    
+    for path in &report.restored {
+        let _ = fs::remove_file(git_dir.join(BACKUP_DIR).join(path));
+    }
+    if remaining.is_empty() {
+        let _ = fs::remove_dir_all(git_dir.join(BACKUP_DIR));
+        let _ = fs::remove_file(git_dir.join(JOURNAL_FILE));
+    } else {
+        journal.entries = remaining;
+        journal.save(&git_dir)?;
+    }
+    
+    Ok(report)
+}
+
+fn restore(worktree: &Path, git_dir: &Path, entry: &JournalEntry) -> Result<()> {
+    let path = worktree.join(entry.path());
+    match entry {
+        JournalEntry::File { original_hash, rewritten_hash, .. } => {
+            let current = fs::read(&path).map_err(|_| anyhow::anyhow!("missing"))?;
+            if &blob_hash(&current) != rewritten_hash {
+                return Err(anyhow::anyhow!("changed since it was rewritten"));
+            }
+            let original = fs::read(git_dir.join(BACKUP_DIR).join(entry.path()))
+                .map_err(|_| anyhow::anyhow!("backup is missing"))?;
+            if &blob_hash(&original) != original_hash {
+                return Err(anyhow::anyhow!("backup doesn't match the journal"));
+            }
+            write_atomically(&path, &original)
+        }
+        JournalEntry::Symlink { original_target, rewritten_target, .. } => {
+            let current = fs::read_link(&path).map_err(|_| anyhow::anyhow!("missing"))?;
+            if &current != rewritten_target {
+                return Err(anyhow::anyhow!("retargeted since it was rewritten"));
+            }
+            retarget_symlink(&path, original_target)
+        }
+    }
+}
+
+/// The git blob id of `content`, as `git hash-object` prints it
+pub fn blob_hash(content: &[u8]) -> String {
+    gix::objs::compute_hash(gix::hash::Kind::Sha1, gix::objs::Kind::Blob, content).to_string()
+}
@@ -0,0 +1,279 @@
+use crate::config::Config;
+use crate::error::{GitWarpError, Result};
+use std::path::{Path, PathBuf};
+
+/// Where worktrees go unless `worktree_template` says otherwise
+pub const DEFAULT_TEMPLATE: &str = "{worktrees_path}/{repo}/{branch_slug}";
+
+/// Variables a worktree path template can use
+pub const TEMPLATE_VARIABLES: &[&str] = &["worktrees_path", "repo", "owner", "branch", "branch_slug"];
+
+/// Longest slug kept before truncating, well below common filename limits
+const MAX_SLUG_LEN: usize = 80;
+
+/// Names Windows refuses as a file name, with or without an extension
+const RESERVED_NAMES: &[&str] = &[
+    "con", "prn", "aux", "nul",
+    "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8", "com9",
+    "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+/// How worktree paths are derived from branch names
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorktreeLayout {
+    worktrees_path: Option<PathBuf>,
+    template: String,
+}
+
+impl Default for WorktreeLayout {
+    fn default() -> Self {
+        Self {
+            worktrees_path: None,
+            template: DEFAULT_TEMPLATE.to_string(),
+        }
+    }
+}
+
+impl WorktreeLayout {
+    /// Validate `template` and pair it with a base directory; `None` uses `<repo>/../worktrees`
+    pub fn new(worktrees_path: Option<PathBuf>, template: &str) -> Result<Self> {
+        let uses_branch = std::cell::Cell::new(false);
+        expand(template, |name| {
+            uses_branch.set(uses_branch.get() || name.starts_with("branch"));
+            None
+        })?;
+        if !uses_branch.get() {
+            return Err(GitWarpError::ConfigError {
+                message: format!("worktree_template '{}' must use {{branch}} or {{branch_slug}}", template),
+            }
+            .into());
+        }
+        
+        Ok(Self {
+            worktrees_path: worktrees_path.map(expand_home),
+            template: template.to_string(),
+        })
+    }
+    
+    /// The layout configured by `worktrees_path` and `worktree_template`
+    pub fn from_config(config: &Config) -> Result<Self> {
+        Self::new(config.worktrees_path.clone(), &config.worktree_template)
+    }
+    
+    /// Render the path for `branch` in the repository whose main worktree is `repo_root`.
+    ///
+    /// Relative results are taken relative to `repo_root`.
+    pub fn render(&self, repo_root: &Path, owner: &str, branch: &str) -> PathBuf {
+        let worktrees_path = self
+            .worktrees_path
+            .clone()
+            .unwrap_or_else(|| default_worktrees_path(repo_root));
+        
+        let rendered = expand(&self.template, |name| match name {
+            "worktrees_path" => Some(worktrees_path.to_string_lossy().into_owned()),
+            "repo" => Some(sanitize_component(&repo_name(repo_root))),
+            "owner" => Some(sanitize_component(owner)),
+            "branch" => Some(branch.split('/').map(sanitize_component).collect::<Vec<_>>().join("/")),
+            "branch_slug" => Some(branch_slug(branch)),
+            _ => None,
+        })
+        .expect("template was validated when the layout was built");
+        
+        repo_root.join(rendered)
+    }
+}
+
+/// Turn a branch name into a single filesystem-safe path component.
+///
+/// Slashes, whitespace and other punctuation become `-`, reserved names get a `_` suffix,
+/// and names with non-ASCII characters get a short hash so distinct branches stay distinct.
+pub fn branch_slug(branch: &str) -> String {
+    let slug = sanitize_component(branch);
+    if branch.is_ascii() {
+        slug
+    } else {
+        format!("{}-{}", slug, short_hash(branch))
+    }
+}
+
+/// Return `path`, or a deterministic variant of it when it's already taken.
+///
+/// The first alternative appends a hash of the branch name; later ones also count up.
+pub fn disambiguate(path: PathBuf, branch: &str, is_taken: impl Fn(&Path) -> bool) -> PathBuf {
+    if !is_taken(&path) {
+        return path;
+    }
+    
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let hash = short_hash(branch);
+    (1..)
+        .map(|n| match n {
+            1 => path.with_file_name(format!("{}-{}", name, hash)),
+            n => path.with_file_name(format!("{}-{}-{}", name, hash, n)),
+        })
+        .find(|candidate| !is_taken(candidate))
+        .expect("some suffix is free")
+}
+
+/// The owner part of a remote URL path such as `owner/repo.git`
+pub fn remote_owner(url_path: &str) -> Option<String> {
+    let mut segments = url_path.trim_end_matches('/').rsplit('/').filter(|s| !s.is_empty());
+    segments.next()?;
+    segments.next().map(str::to_string)
+}
+
+/// Substitute `{name}` variables, failing on unknown names and unbalanced braces
+fn expand(template: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let invalid = |reason: String| -> anyhow::Error {
+        GitWarpError::ConfigError {
+            message: format!("worktree_template '{}': {}", template, reason),
+        }
+        .into()
+    };
+    
+    let mut output = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            return Err(invalid("unclosed '{'".to_string()));
+        };
+        let name = &rest[start + 1..start + end];
+        if !TEMPLATE_VARIABLES.contains(&name) {
+            return Err(invalid(format!(
+                "unknown variable {{{}}}, expected one of {}",
+                name,
+                TEMPLATE_VARIABLES.iter().map(|v| format!("{{{}}}", v)).collect::<Vec<_>>().join(", ")
+            )));
+        }
+        output.push_str(&lookup(name).unwrap_or_default());
+        rest = &rest[start + end + 1..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Keep ASCII letters, digits, `-`, `_` and `.`; collapse everything else into single dashes
+fn sanitize_component(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() || matches!(c, '_' | '.') {
+            slug.push(c);
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    
+    let mut slug = slug.trim_matches(['-', '.']).to_string();
+    if slug.len() > MAX_SLUG_LEN {
+        slug.truncate(MAX_SLUG_LEN);
+        slug = slug.trim_end_matches(['-', '.']).to_string();
+    }
+    
+    if slug.is_empty() {
+        return format!("branch-{}", short_hash(name));
+    }
+    let stem = slug.split('.').next().unwrap_or_default().to_ascii_lowercase();
+    if RESERVED_NAMES.contains(&stem.as_str()) {
+        slug.push('_');
+    }
+    slug
+}
+
+/// The directory name of the main worktree, or of a bare repository without `.git`
+fn repo_name(repo_root: &Path) -> String {
+    let name = repo_root
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    name.strip_suffix(".git").map(str::to_string).unwrap_or(name)
+}
+
+/// Resolve a leading `~` to the home directory
+fn expand_home(path: PathBuf) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path,
+    }
+}
+
+/// `worktrees` next to the main worktree
+fn default_worktrees_path(repo_root: &Path) -> PathBuf {
+    match repo_root.parent() {
+        Some(parent) => parent.join("worktrees"),
+        None => repo_root.join("../worktrees"),
+    }
+}
+
+/// 32-bit FNV-1a, stable across platforms and Rust versions
+fn short_hash(value: &str) -> String {
+    let hash = value.bytes().fold(0x811c9dc5u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    });
+    format!("{:08x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_branch_slug() {
+        assert_eq!(branch_slug("feature/awesome-feature"), "feature-awesome-feature");
+        assert_eq!(branch_slug("feature branch with spaces"), "feature-branch-with-spaces");
+        assert_eq!(branch_slug("hotfix/issue-#123"), "hotfix-issue-123");
+        assert_eq!(branch_slug("con"), "con_");
+        assert_eq!(branch_slug("Aux.txt"), "Aux.txt_");
+        assert!(branch_slug("..").starts_with("branch-"));
+        
+        // Accented names keep what they can and stay distinct
+        let e = branch_slug("fix/café");
+        let u = branch_slug("fix/cafü");
+        assert!(e.starts_with("fix-caf-"));
+        assert_ne!(e, u);
+        assert_eq!(e, branch_slug("fix/café"));
+    }
+    
+    #[test]
+    fn test_render_template() {
+        let root = Path::new("/src/project");
+        
+        let layout = WorktreeLayout::default();
+        assert_eq!(layout.render(root, "me", "feature/x"), Path::new("/src/worktrees/project/feature-x"));
+        
+        let layout = WorktreeLayout::new(Some("/wt".into()), "{worktrees_path}/{owner}/{repo}/{branch}").unwrap();
+        assert_eq!(layout.render(root, "me", "feature/x y"), Path::new("/wt/me/project/feature/x-y"));
+        
+        let layout = WorktreeLayout::new(None, "../{repo}.{branch_slug}").unwrap();
+        assert_eq!(layout.render(root, "me", "fix"), Path::new("/src/project/../project.fix"));
+    }
+    
+    #[test]
+    fn test_invalid_templates() {
+        assert!(WorktreeLayout::new(None, "{worktrees_path}/{nope}").is_err());
+        assert!(WorktreeLayout::new(None, "{worktrees_path}/{branch").is_err());
+        assert!(WorktreeLayout::new(None, "{worktrees_path}/{repo}").is_err());
+    }
+    
+    #[test]
+    fn test_disambiguate() {
+        let taken = [PathBuf::from("/wt/a"), PathBuf::from(format!("/wt/a-{}", short_hash("a")))];
+        let is_taken = |path: &Path| taken.iter().any(|t| t == path);
+        
+        assert_eq!(disambiguate("/wt/b".into(), "b", is_taken), Path::new("/wt/b"));
+        assert_eq!(
+            disambiguate("/wt/a".into(), "a", is_taken),
+            PathBuf::from(format!("/wt/a-{}-2", short_hash("a")))
+        );
+    }
+    
+    #[test]
+    fn test_remote_owner() {
+        assert_eq!(remote_owner("owner/repo.git").as_deref(), Some("owner"));
+        assert_eq!(remote_owner("/group/sub/repo").as_deref(), Some("sub"));
+        assert_eq!(remote_owner("repo.git"), None);
+    }
+}
@@ -3,13 +3,19 @@
 //! This crate combines Copy-on-Write (CoW) filesystem operations with advanced Git worktree
 //! management to provide fast, reliable development environment setup.
 
+pub mod cleanup;
 pub mod config;
 pub mod cow;
 pub mod error;
 pub mod git;
 pub mod hooks;
+pub mod journal;
+pub mod layout;
+pub mod output;
 pub mod process;
+pub mod progress;
 pub mod rewrite;
+pub mod rewriters;
 pub mod terminal;
 pub mod tui;
 
@@ -1,13 +1,7 @@
 mod cli;
-mod config;
-mod cow;
-mod error;
-mod git;
-mod hooks;
-mod process;
-mod rewrite;
-mod terminal;
-mod tui;
+
+// The CLI is a thin front-end over the library crate
+use git_warp::{cleanup, config, cow, git, hooks, journal, layout, output, process, progress, rewrite, terminal, tui};
 
 use anyhow::Result;
 use clap::Parser;
@@ -0,0 +1,113 @@
+use crate::error::Result;
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Bumped whenever a field is renamed or removed from machine-readable output
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// How a command prints its result
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// A single JSON document
+    Json,
+    /// One JSON object per line, one line per item
+    Ndjson,
+}
+
+impl OutputFormat {
+    pub fn is_text(&self) -> bool {
+        *self == OutputFormat::Text
+    }
+}
+
+/// A whole result as one JSON document
+#[derive(Debug, Serialize)]
+pub struct Document<'a, T: Serialize> {
+    pub schema_version: u32,
+    pub kind: &'a str,
+    pub data: T,
+}
+
+/// One item of an NDJSON stream, with the item's fields inlined
+#[derive(Debug, Serialize)]
+pub struct Record<'a, T: Serialize> {
+    pub schema_version: u32,
+    pub kind: &'a str,
+    #[serde(flatten)]
+    pub item: T,
+}
+
+/// Serialize `data` as a versioned JSON document
+pub fn to_json<T: Serialize>(kind: &str, data: T) -> Result<String> {
+    Ok(serde_json::to_string_pretty(&Document {
+        schema_version: SCHEMA_VERSION,
+        kind,
+        data,
+    })?)
+}
+
+/// Serialize each item as a versioned JSON object on its own line
+pub fn to_ndjson<T: Serialize>(kind: &str, items: impl IntoIterator<Item = T>) -> Result<String> {
+    let mut output = String::new();
+    for item in items {
+        output.push_str(&serde_json::to_string(&Record {
+            schema_version: SCHEMA_VERSION,
+            kind,
+            item,
+        })?);
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// A worktree as listed by `warp ls`
+#[derive(Debug, Serialize)]
+pub struct WorktreeEntry<'a> {
+    #[serde(flatten)]
+    pub worktree: &'a crate::git::WorktreeInfo,
+    /// Only present with `--long`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<&'a crate::git::WorktreeDetails>,
+}
+
+/// The effective configuration and where it was loaded from
+#[derive(Debug, Serialize)]
+pub struct ConfigReport<'a> {
+    pub config_path: PathBuf,
+    /// The repository's `.git-warp.toml`, when there is one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_config_path: Option<PathBuf>,
+    pub config: &'a crate::config::Config,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[derive(Serialize)]
+    struct Item {
+        name: &'static str,
+    }
+    
+    #[test]
+    fn test_json_document_is_versioned() {
+        let json = to_json("items", vec![Item { name: "a" }]).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["schema_version"], SCHEMA_VERSION);
+        assert_eq!(value["kind"], "items");
+        assert_eq!(value["data"][0]["name"], "a");
+    }
+    
+    #[test]
+    fn test_ndjson_inlines_each_item() {
+        let ndjson = to_ndjson("item", [Item { name: "a" }, Item { name: "b" }]).unwrap();
+        let lines: Vec<serde_json::Value> = ndjson.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["schema_version"], SCHEMA_VERSION);
+        assert_eq!(lines[1]["kind"], "item");
+        assert_eq!(lines[1]["name"], "b");
+    }
+}
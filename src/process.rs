@@ -1,6 +1,6 @@
 use crate::error::{GitWarpError, Result};
 use std::path::{Path, PathBuf};
-use sysinfo::{System, Pid, Process, ProcessRefreshKind};
+use sysinfo::{System, ProcessRefreshKind};
 use std::time::Duration;
 
 #[derive(Debug, Clone)]
@@ -27,6 +27,12 @@ pub struct ProcessManager {
     system: System,
 }
 
+impl Default for ProcessManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ProcessManager {
     pub fn new() -> Self {
         let mut system = System::new();
@@ -41,6 +47,12 @@ impl ProcessManager {
     
     /// Find all processes running in a specific directory
     pub fn find_processes_in_directory<P: AsRef<Path>>(&mut self, path: P) -> Result<Vec<ProcessInfo>> {
+        if !path.as_ref().exists() {
+            return Err(GitWarpError::WorktreeNotFound {
+                path: path.as_ref().display().to_string(),
+            }.into());
+        }
+        
         let target_path = path.as_ref().canonicalize()
             .unwrap_or_else(|_| path.as_ref().to_path_buf());
         
@@ -48,18 +60,18 @@ impl ProcessManager {
         let mut processes = Vec::new();
         
         for (pid, process) in self.system.processes() {
-            if let Some(cwd) = process.cwd() {
-                if cwd.starts_with(&target_path) {
-                    processes.push(ProcessInfo {
-                        pid: pid.as_u32(),
-                        name: process.name().to_string(),
-                        cmd: process.cmd().join(" "),
-                        working_dir: cwd.to_path_buf(),
-                        cpu_usage: process.cpu_usage(),
-                        memory_usage: process.memory(),
-                        start_time: process.start_time(),
-                    });
-                }
+            if let Some(cwd) = process.cwd()
+                && cwd.starts_with(&target_path)
+            {
+                processes.push(ProcessInfo {
+                    pid: pid.as_u32(),
+                    name: process.name().to_string(),
+                    cmd: process.cmd().join(" "),
+                    working_dir: cwd.to_path_buf(),
+                    cpu_usage: process.cpu_usage(),
+                    memory_usage: process.memory(),
+                    start_time: process.start_time(),
+                });
             }
         }
         
@@ -234,7 +246,7 @@ mod tests {
     
     #[test]
     fn test_process_manager_creation() {
-        let manager = ProcessManager::new();
+        let _manager = ProcessManager::new();
         // Just verify we can create a process manager
     }
     
@@ -248,6 +260,17 @@ mod tests {
         // Most likely no processes will be running in a temporary directory
     }
     
+    #[test]
+    fn test_find_processes_missing_directory() {
+        let temp_dir = tempdir().unwrap();
+        let missing = temp_dir.path().join("removed");
+        let mut manager = ProcessManager::new();
+        
+        // A missing path would otherwise be matched uncanonicalized and always come back empty
+        let error = manager.find_processes_in_directory(&missing).unwrap_err();
+        assert!(matches!(error.downcast_ref::<GitWarpError>(), Some(GitWarpError::WorktreeNotFound { .. })));
+    }
+    
     #[test]
     fn test_process_info_fields() {
         let process = ProcessInfo {
@@ -0,0 +1,137 @@
+use crate::error::{GitWarpError, Result};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A snapshot of a long-running file operation
+#[derive(Debug, Clone, Default)]
+pub struct Progress {
+    pub files_done: usize,
+    pub files_total: usize,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    /// The entry being worked on, relative to the operation's root
+    pub current: PathBuf,
+}
+
+impl Progress {
+    /// Completed fraction of the work, by bytes when known and by files otherwise
+    pub fn fraction(&self) -> f64 {
+        if self.bytes_total > 0 {
+            self.bytes_done as f64 / self.bytes_total as f64
+        } else if self.files_total > 0 {
+            self.files_done as f64 / self.files_total as f64
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Receives progress updates from clones and rewrites, and can ask them to stop.
+///
+/// Updates arrive from worker threads, so implementations should be cheap and throttle
+/// any rendering themselves. Plain closures work as observers; to feed a channel, wrap
+/// the sender in one.
+pub trait ProgressObserver: Sync {
+    fn on_progress(&self, progress: &Progress);
+    
+    /// Polled between files; returning true aborts the operation
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+impl<F: Fn(&Progress) + Sync> ProgressObserver for F {
+    fn on_progress(&self, progress: &Progress) {
+        self(progress)
+    }
+}
+
+/// Observer that ignores every update
+pub struct NoProgress;
+
+impl ProgressObserver for NoProgress {
+    fn on_progress(&self, _progress: &Progress) {}
+}
+
+/// Whether an error means the operation was cancelled rather than failed
+pub fn is_cancelled(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref::<GitWarpError>(), Some(GitWarpError::Cancelled))
+}
+
+/// Shared flag used to cancel an operation from another thread or a signal handler
+#[derive(Debug, Clone, Default)]
+pub struct CancelFlag(Arc<AtomicBool>);
+
+impl CancelFlag {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+    
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+    
+    /// Set the flag on Ctrl-C for as long as the guard lives.
+    ///
+    /// A second Ctrl-C while the flag is already set terminates the process as usual.
+    pub fn cancel_on_ctrl_c(&self) -> Result<CtrlCGuard> {
+        use signal_hook::consts::SIGINT;
+        use signal_hook::flag;
+        
+        let shutdown = flag::register_conditional_shutdown(SIGINT, 130, Arc::clone(&self.0))?;
+        let cancel = flag::register(SIGINT, Arc::clone(&self.0))?;
+        
+        Ok(CtrlCGuard {
+            handlers: vec![shutdown, cancel],
+        })
+    }
+}
+
+/// Restores the default Ctrl-C behaviour when dropped
+pub struct CtrlCGuard {
+    handlers: Vec<signal_hook::SigId>,
+}
+
+impl Drop for CtrlCGuard {
+    fn drop(&mut self) {
+        for handler in self.handlers.drain(..) {
+            signal_hook::low_level::unregister(handler);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_progress_fraction() {
+        let mut progress = Progress {
+            files_done: 1,
+            files_total: 4,
+            ..Default::default()
+        };
+        assert_eq!(progress.fraction(), 0.25);
+        
+        progress.bytes_done = 75;
+        progress.bytes_total = 100;
+        assert_eq!(progress.fraction(), 0.75);
+        
+        assert_eq!(Progress::default().fraction(), 1.0);
+    }
+    
+    #[test]
+    fn test_cancel_flag_is_shared() {
+        let flag = CancelFlag::new();
+        let clone = flag.clone();
+        assert!(!clone.is_cancelled());
+        
+        flag.cancel();
+        assert!(clone.is_cancelled());
+    }
+}
@@ -1,12 +1,270 @@
-use crate::error::Result;
-use ignore::{WalkBuilder, DirEntry};
+use crate::error::{GitWarpError, Result};
+use crate::journal::JournalWriter;
+use crate::progress::{NoProgress, Progress, ProgressObserver};
+use aho_corasick::{AhoCorasick, MatchKind};
+use ignore::WalkBuilder;
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 use std::fs;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Files at least this large are memory-mapped instead of read
+const MMAP_THRESHOLD: u64 = 1 << 20;
+
+/// How much of a file without a known magic number is checked for NUL bytes
+const BINARY_SNIFF_LEN: usize = 8192;
+
+/// Which files in the destination may be rewritten. Tracked files are only touched with `All`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum RewriteScope {
+    /// Only files matched by gitignore rules: build output, dependencies, virtualenvs
+    IgnoredOnly,
+    /// Every file git doesn't track, ignored or not
+    #[default]
+    Untracked,
+    /// Every file, including tracked source
+    All,
+}
+
+/// Binary formats that store offsets or lengths, so a path of a different length corrupts them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryFormat {
+    Elf,
+    MachO,
+    JavaClass,
+    Pyc,
+    Sqlite,
+    Archive,
+    Zip,
+    /// No known magic number, but NUL bytes near the start
+    Data,
+}
+
+impl fmt::Display for BinaryFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BinaryFormat::Elf => "ELF binary",
+            BinaryFormat::MachO => "Mach-O binary",
+            BinaryFormat::JavaClass => "Java class file",
+            BinaryFormat::Pyc => "Python bytecode",
+            BinaryFormat::Sqlite => "SQLite database",
+            BinaryFormat::Archive => "ar archive",
+            BinaryFormat::Zip => "zip archive",
+            BinaryFormat::Data => "binary data",
+        })
+    }
+}
+
+/// Outcome of a rewrite pass, with paths relative to the destination
+#[derive(Debug, Clone, Default)]
+pub struct RewriteReport {
+    /// Files whose paths were rewritten
+    pub rewritten: Vec<PathBuf>,
+    /// Binary files that still contain the source path because editing them would corrupt them
+    pub binaries: Vec<(PathBuf, BinaryFormat)>,
+    /// Symlinks whose target doesn't exist, with that target
+    pub dangling: Vec<(PathBuf, PathBuf)>,
+    /// Unified diff of the proposed changes, on dry runs only
+    pub diff: String,
+    /// Where the changes were journaled, when the destination is a git worktree
+    pub journal: Option<PathBuf>,
+}
+
+/// What rewriting did to a single file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileOutcome {
+    Unchanged,
+    Rewritten,
+    /// Left alone because editing would corrupt it
+    Binary(BinaryFormat),
+}
+
+/// A file or symlink under the destination
+#[derive(Debug, Clone, Copy)]
+pub struct Entry<'a> {
+    pub path: &'a Path,
+    /// `path` relative to the destination root
+    pub relative: &'a Path,
+    pub is_symlink: bool,
+    session: &'a Session,
+}
+
+impl Entry<'_> {
+    /// Whether changes are only being previewed, so nothing on disk may be touched
+    pub fn is_dry_run(&self) -> bool {
+        self.session.dry_run
+    }
+}
+
+/// Applies the changes rewriters make and journals them, or on dry runs only collects a diff
+#[derive(Debug)]
+struct Session {
+    paths: PathMap,
+    dry_run: bool,
+    journal: Option<JournalWriter>,
+    diffs: Mutex<Vec<(PathBuf, String)>>,
+    /// Targets symlinks would get, on dry runs, by path relative to the destination
+    planned_links: Mutex<HashMap<PathBuf, PathBuf>>,
+}
+
+impl Session {
+    fn write_file(&self, entry: &Entry, original: &[u8], rewritten: &[u8]) -> Result<()> {
+        if self.dry_run {
+            let diff = unified_diff(entry.relative, original, rewritten);
+            self.diffs.lock().unwrap().push((entry.relative.to_path_buf(), diff));
+            return Ok(());
+        }
+        
+        if let Some(journal) = &self.journal {
+            // Format-aware rewriters may replace spellings the plain matcher doesn't count
+            let replacements = self.paths.count(original).saturating_sub(self.paths.count(rewritten)).max(1);
+            journal.record_file(entry.relative, original, rewritten, replacements)?;
+        }
+        write_atomically(entry.path, rewritten)
+    }
+    
+    fn retarget(&self, entry: &Entry, original: &Path, target: &Path) -> Result<()> {
+        if self.dry_run {
+            let diff = unified_diff(
+                entry.relative,
+                format!("{}\n", original.display()).as_bytes(),
+                format!("{}\n", target.display()).as_bytes(),
+            );
+            self.diffs.lock().unwrap().push((entry.relative.to_path_buf(), diff));
+            self.planned_links.lock().unwrap().insert(entry.relative.to_path_buf(), target.to_path_buf());
+            return Ok(());
+        }
+        
+        if let Some(journal) = &self.journal {
+            journal.record_symlink(entry.relative, original, target);
+        }
+        retarget_symlink(entry.path, target)
+    }
+    
+    /// The target of the symlink `entry` once this pass is done, when it doesn't exist
+    fn dangling_target(&self, entry: &Entry) -> Option<PathBuf> {
+        let planned = self.planned_links.lock().unwrap().get(entry.relative).cloned();
+        let target = planned.or_else(|| fs::read_link(entry.path).ok())?;
+        // Relative targets resolve from the link's directory; joining keeps absolute ones as they are
+        let resolved = entry.path.parent().unwrap_or(Path::new("")).join(&target);
+        (!resolved.exists()).then_some(target)
+    }
+}
+
+/// A format-aware way of fixing paths in one kind of file.
+///
+/// Files no rewriter handles get the source path replaced wherever it appears;
+/// symlinks no rewriter handles are retargeted when they point under the source.
+pub trait Rewriter: Send + Sync {
+    /// Short name for logs
+    fn name(&self) -> &'static str;
+    
+    /// Whether this rewriter takes care of `entry`
+    fn handles(&self, entry: &Entry) -> bool;
+    
+    /// Fix the paths in `entry`
+    fn rewrite(&self, entry: &Entry, paths: &PathMap) -> Result<FileOutcome>;
+}
+
+/// Where paths move from and to, and how to find the old one in file contents
+#[derive(Debug)]
+pub struct PathMap {
+    /// The source root as given, then resolved through symlinks when that differs
+    src: Vec<PathBuf>,
+    dest: PathBuf,
+    matcher: AhoCorasick,
+}
+
+impl PathMap {
+    pub fn new(src: &Path, dest: &Path) -> Result<Self> {
+        let mut sources = vec![src.to_path_buf()];
+        if let Ok(canonical) = src.canonicalize()
+            && canonical != src
+        {
+            sources.push(canonical);
+        }
+        
+        let matcher = Self::build_matcher(sources.iter().map(|src| src.as_os_str().as_encoded_bytes()))?;
+        Ok(Self {
+            src: sources,
+            dest: dest.to_path_buf(),
+            matcher,
+        })
+    }
+    
+    pub fn dest(&self) -> &Path {
+        &self.dest
+    }
+    
+    /// Whether `content` mentions the source root
+    pub fn contains(&self, content: &[u8]) -> bool {
+        self.matcher.is_match(content)
+    }
+    
+    /// How often `content` mentions the source root
+    pub fn count(&self, content: &[u8]) -> usize {
+        self.matcher.find_iter(content).count()
+    }
+    
+    /// `content` with every mention of the source root replaced, or `None` without one
+    pub fn replace(&self, content: &[u8]) -> Option<Vec<u8>> {
+        if !self.contains(content) {
+            return None;
+        }
+        let dest = self.dest.as_os_str().as_encoded_bytes();
+        Some(self.matcher.replace_all_bytes(content, &vec![dest; self.src.len()]))
+    }
+    
+    /// Like `replace`, for a format that writes paths as `spellings` do, such as escaped.
+    ///
+    /// When two spellings of the source agree, the first one decides how the destination is written.
+    pub fn replace_spelled(&self, content: &[u8], spellings: &[fn(&str) -> String]) -> Option<Vec<u8>> {
+        let mut patterns: Vec<Vec<u8>> = Vec::new();
+        let mut replacements: Vec<Vec<u8>> = Vec::new();
+        for spell in spellings {
+            for src in &self.src {
+                let pattern = spell(&src.to_string_lossy()).into_bytes();
+                if !patterns.contains(&pattern) {
+                    patterns.push(pattern);
+                    replacements.push(spell(&self.dest.to_string_lossy()).into_bytes());
+                }
+            }
+        }
+        
+        let matcher = Self::build_matcher(patterns.iter().map(Vec::as_slice)).ok()?;
+        matcher
+            .is_match(content)
+            .then(|| matcher.replace_all_bytes(content, &replacements))
+    }
+    
+    /// `path` moved to the destination, if it lies under the source root
+    pub fn map_path(&self, path: &Path) -> Option<PathBuf> {
+        self.src
+            .iter()
+            .find_map(|src| path.strip_prefix(src).ok())
+            .map(|rest| if rest.as_os_str().is_empty() { self.dest.clone() } else { self.dest.join(rest) })
+    }
+    
+    fn build_matcher<'a>(patterns: impl IntoIterator<Item = &'a [u8]>) -> Result<AhoCorasick> {
+        AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .build(patterns)
+            .map_err(|e| anyhow::anyhow!("Cannot search for the source path: {}", e))
+    }
+}
 
 pub struct PathRewriter {
     src_path: PathBuf,
     dest_path: PathBuf,
+    scope: RewriteScope,
+    rewriters: Vec<Box<dyn Rewriter>>,
+    dry_run: bool,
 }
 
 impl PathRewriter {
@@ -14,95 +272,387 @@ impl PathRewriter {
         Self {
             src_path: src_path.as_ref().to_path_buf(),
             dest_path: dest_path.as_ref().to_path_buf(),
+            scope: RewriteScope::default(),
+            rewriters: crate::rewriters::builtin(),
+            dry_run: false,
         }
     }
     
-    /// Rewrite absolute paths in gitignored files
+    /// Limit rewriting to the files in `scope`
+    pub fn with_scope(mut self, scope: RewriteScope) -> Self {
+        self.scope = scope;
+        self
+    }
+    
+    /// Handle the files `rewriter` claims with it, ahead of the built-in rewriters
+    pub fn with_rewriter(mut self, rewriter: impl Rewriter + 'static) -> Self {
+        self.rewriters.insert(0, Box::new(rewriter));
+        self
+    }
+    
+    /// Only work out what would change; the report carries a unified diff and nothing is written
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+    
+    /// Rewrite absolute paths in the files in scope, never in tracked ones unless asked
     pub fn rewrite_paths(&self) -> Result<()> {
-        let src_str = self.src_path.to_string_lossy();
-        let dest_str = self.dest_path.to_string_lossy();
-        
-        // Build a list of files to process
-        let files: Vec<PathBuf> = WalkBuilder::new(&self.dest_path)
-            .hidden(false) // Process hidden files
-            .git_ignore(true) // Respect gitignore
-            .build()
-            .filter_map(|entry| {
-                match entry {
-                    Ok(entry) => {
-                        if entry.file_type()?.is_file() {
-                            Some(entry.path().to_path_buf())
-                        } else {
-                            None
-                        }
-                    }
-                    Err(_) => None,
+        self.rewrite_paths_with_progress(&NoProgress)?;
+        Ok(())
+    }
+    
+    /// Rewrite absolute paths, reporting each processed file to `observer`.
+    ///
+    /// Changes are journaled in the destination's git dir so they can be undone.
+    /// Stops with `Cancelled` if the observer asks; files already rewritten stay rewritten and journaled.
+    pub fn rewrite_paths_with_progress(&self, observer: &dyn ProgressObserver) -> Result<RewriteReport> {
+        let journal = match self.dry_run {
+            true => None,
+            false => JournalWriter::open(&self.src_path, &self.dest_path)?,
+        };
+        let session = Session {
+            paths: PathMap::new(&self.src_path, &self.dest_path)?,
+            dry_run: self.dry_run,
+            journal,
+            diffs: Mutex::new(Vec::new()),
+            planned_links: Mutex::new(HashMap::new()),
+        };
+        
+        let files = self.files_in_scope()?;
+        
+        let files_total = files.len();
+        let bytes_total = files.iter().map(|(_, size)| size).sum();
+        let files_done = AtomicUsize::new(0);
+        let bytes_done = AtomicU64::new(0);
+        
+        // Process files in parallel
+        let outcomes = files
+            .par_iter()
+            .map(|(file_path, size)| {
+                if observer.is_cancelled() {
+                    return Err(());
                 }
+                
+                let relative = file_path.strip_prefix(&self.dest_path).unwrap_or(file_path).to_path_buf();
+                let entry = Entry {
+                    path: file_path,
+                    relative: &relative,
+                    is_symlink: file_path.is_symlink(),
+                    session: &session,
+                };
+                let outcome = self.rewrite_entry(&entry, &session.paths).unwrap_or_else(|e| {
+                    log::warn!("Failed to rewrite paths in {}: {}", file_path.display(), e);
+                    FileOutcome::Unchanged
+                });
+                
+                observer.on_progress(&Progress {
+                    files_done: files_done.fetch_add(1, Ordering::Relaxed) + 1,
+                    files_total,
+                    bytes_done: bytes_done.fetch_add(*size, Ordering::Relaxed) + size,
+                    bytes_total,
+                    current: relative.clone(),
+                });
+                
+                // Checked after rewriting, so a retargeted link is judged by its new target,
+                // including the one a dry run only plans
+                let dangling = entry.is_symlink.then(|| session.dangling_target(&entry)).flatten();
+                
+                Ok((relative, outcome, dangling))
             })
-            .collect();
+            .collect::<std::result::Result<Vec<_>, ()>>();
         
-        // Process files in parallel
-        files.par_iter().for_each(|file_path| {
-            if let Err(e) = self.rewrite_file(file_path, &src_str, &dest_str) {
-                log::warn!("Failed to rewrite paths in {}: {}", file_path.display(), e);
-            }
-        });
+        let Session { journal, diffs, .. } = session;
+        let journal = journal.map(JournalWriter::finish).transpose()?.flatten();
+        let Ok(outcomes) = outcomes else {
+            return Err(GitWarpError::Cancelled.into());
+        };
         
-        Ok(())
+        let mut diffs = diffs.into_inner().unwrap();
+        diffs.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut report = RewriteReport {
+            diff: diffs.into_iter().map(|(_, diff)| diff).collect(),
+            journal,
+            ..RewriteReport::default()
+        };
+        for (path, outcome, dangling) in outcomes {
+            if let Some(target) = dangling {
+                log::warn!("Dangling symlink {} -> {}", path.display(), target.display());
+                report.dangling.push((path.clone(), target));
+            }
+            match outcome {
+                FileOutcome::Unchanged => {}
+                FileOutcome::Rewritten => report.rewritten.push(path),
+                FileOutcome::Binary(format) => {
+                    log::warn!("Not rewriting {} ({}), it still refers to {}", path.display(), format, self.src_path.display());
+                    report.binaries.push((path, format));
+                }
+            }
+        }
+        Ok(report)
     }
     
-    /// Rewrite paths in a single file
-    fn rewrite_file(&self, file_path: &Path, src_str: &str, dest_str: &str) -> Result<()> {
-        // Read file content
-        let content = match fs::read_to_string(file_path) {
-            Ok(content) => content,
-            Err(_) => {
-                // Skip binary files or files we can't read as UTF-8
-                return Ok(());
+    /// Rewrite `entry` with the first rewriter that handles it, or the generic replacement
+    fn rewrite_entry(&self, entry: &Entry, paths: &PathMap) -> Result<FileOutcome> {
+        match self.rewriters.iter().find(|rewriter| rewriter.handles(entry)) {
+            Some(rewriter) => {
+                log::debug!("Rewriting {} as {}", entry.relative.display(), rewriter.name());
+                rewriter.rewrite(entry, paths)
             }
-        };
-        
-        // Check if file contains the source path
-        if !content.contains(src_str) {
-            return Ok(());
+            None if entry.is_symlink => rewrite_symlink(entry, paths),
+            None => rewrite_generic(entry, paths),
         }
+    }
+    
+    /// Files under the destination that `scope` allows rewriting, with their sizes
+    fn files_in_scope(&self) -> Result<Vec<(PathBuf, u64)>> {
+        let mut files = walk_files(WalkBuilder::new(&self.dest_path).standard_filters(false));
         
-        // Skip files that are likely binary
-        if self.is_likely_binary(&content) {
-            return Ok(());
+        if self.scope != RewriteScope::All {
+            let tracked = tracked_files(&self.dest_path)?;
+            files.retain(|(path, _)| !tracked.contains(path));
         }
         
-        // Replace paths
-        let new_content = content.replace(src_str, dest_str);
-        
-        // Write back if content changed
-        if new_content != content {
-            fs::write(file_path, new_content)?;
-            log::debug!("Rewrote paths in: {}", file_path.display());
+        if self.scope == RewriteScope::IgnoredOnly {
+            // Whatever a gitignore-aware walk still sees isn't ignored
+            let visible: HashSet<PathBuf> = walk_files(
+                WalkBuilder::new(&self.dest_path)
+                    .standard_filters(false)
+                    .git_ignore(true)
+                    .git_exclude(true)
+                    .git_global(true)
+                    .require_git(false),
+            )
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+            files.retain(|(path, _)| !visible.contains(path));
         }
         
-        Ok(())
+        Ok(files)
+    }
+}
+
+/// Replace the source root wherever it appears, refusing known binary formats
+pub fn rewrite_generic(entry: &Entry, paths: &PathMap) -> Result<FileOutcome> {
+    edit_file(entry, |content| replace_in_text(entry.path, content, paths))
+}
+
+/// The generic replacement for the contents of `path`, as an `edit_file` edit
+pub fn replace_in_text(
+    path: &Path,
+    content: &[u8],
+    paths: &PathMap,
+) -> std::result::Result<Option<Vec<u8>>, BinaryFormat> {
+    if !paths.contains(content) {
+        return Ok(None);
     }
+    if let Some(format) = binary_format(path, content) {
+        return Err(format);
+    }
+    Ok(paths.replace(content))
+}
+
+/// Run `edit` on the contents of `entry` and write back what it returns.
+///
+/// `edit` returns `None` to leave the file alone, or the binary format that rules out editing it.
+pub fn edit_file(
+    entry: &Entry,
+    edit: impl FnOnce(&[u8]) -> std::result::Result<Option<Vec<u8>>, BinaryFormat>,
+) -> Result<FileOutcome> {
+    let mut file = fs::File::open(entry.path)?;
+    let size = file.metadata()?.len();
     
-    /// Simple heuristic to detect binary files
-    fn is_likely_binary(&self, content: &str) -> bool {
-        // Check for null bytes (common in binary files)
-        content.contains('\0') ||
-        // Check for very high ratio of non-printable characters
-        {
-            let total = content.len();
-            if total == 0 {
-                return false;
+    let map;
+    let mut buffer = Vec::new();
+    let original: &[u8] = if size >= MMAP_THRESHOLD {
+        // SAFETY: the map is read-only, and the file is replaced by a rename rather than written to
+        map = unsafe { memmap2::Mmap::map(&file)? };
+        &map
+    } else {
+        buffer.reserve(size as usize);
+        file.read_to_end(&mut buffer)?;
+        &buffer
+    };
+    
+    match edit(original) {
+        Ok(Some(content)) => {
+            entry.session.write_file(entry, original, &content)?;
+            log::debug!("Rewrote paths in: {}", entry.path.display());
+            Ok(FileOutcome::Rewritten)
+        }
+        Ok(None) => Ok(FileOutcome::Unchanged),
+        Err(format) => Ok(FileOutcome::Binary(format)),
+    }
+}
+
+/// Retarget an absolute symlink that points under the source root to the same place under the destination.
+///
+/// Relative links and links elsewhere are left alone.
+pub fn rewrite_symlink(entry: &Entry, paths: &PathMap) -> Result<FileOutcome> {
+    let target = fs::read_link(entry.path)?;
+    if !target.is_absolute() {
+        return Ok(FileOutcome::Unchanged);
+    }
+    match paths.map_path(&target) {
+        Some(new_target) => {
+            entry.session.retarget(entry, &target, &new_target)?;
+            log::debug!("Retargeted {} to {}", entry.path.display(), new_target.display());
+            Ok(FileOutcome::Rewritten)
+        }
+        None => Ok(FileOutcome::Unchanged),
+    }
+}
+
+/// Replace the file at `path` with `content` through a rename, keeping its permissions.
+///
+/// Hardlinked copies, such as files a clone linked to the source, keep their old content.
+pub(crate) fn write_atomically(path: &Path, content: &[u8]) -> Result<()> {
+    let temp = temp_sibling(path);
+    let permissions = fs::metadata(path).map(|metadata| metadata.permissions()).ok();
+    
+    let written = fs::write(&temp, content)
+        .and_then(|()| permissions.map_or(Ok(()), |permissions| fs::set_permissions(&temp, permissions)))
+        .and_then(|()| fs::rename(&temp, path));
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Point the symlink at `path` to `target`, swapping it in with a rename so it never goes missing
+pub(crate) fn retarget_symlink(path: &Path, target: &Path) -> Result<()> {
+    let temp = temp_sibling(path);
+    
+    let _ = fs::remove_file(&temp);
+    create_symlink(target, &temp)?;
+    if let Err(e) = fs::rename(&temp, path) {
+        let _ = fs::remove_file(&temp);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// A scratch name next to `path`, for swapping in a replacement
+fn temp_sibling(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.warp-tmp", name))
+}
+
+/// A git-style unified diff of one file; binary content is only summarized
+fn unified_diff(relative: &Path, original: &[u8], rewritten: &[u8]) -> String {
+    let (old_name, new_name) = (format!("a/{}", relative.display()), format!("b/{}", relative.display()));
+    if memchr::memchr(0, original).is_some() {
+        return format!("Binary files {} and {} differ\n", old_name, new_name);
+    }
+    
+    let (original, rewritten) = (String::from_utf8_lossy(original), String::from_utf8_lossy(rewritten));
+    similar::TextDiff::from_lines(original.as_ref(), rewritten.as_ref())
+        .unified_diff()
+        .context_radius(3)
+        .header(&old_name, &new_name)
+        .to_string()
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(not(unix))]
+fn create_symlink(_target: &Path, link: &Path) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("cannot create symlink {}", link.display()),
+    ))
+}
+
+/// Recognize formats a length-changing edit would corrupt, by magic number
+fn binary_format(file_path: &Path, content: &[u8]) -> Option<BinaryFormat> {
+    const MAGIC: &[(&[u8], BinaryFormat)] = &[
+        (b"\x7fELF", BinaryFormat::Elf),
+        (&[0xfe, 0xed, 0xfa, 0xce], BinaryFormat::MachO),
+        (&[0xfe, 0xed, 0xfa, 0xcf], BinaryFormat::MachO),
+        (&[0xce, 0xfa, 0xed, 0xfe], BinaryFormat::MachO),
+        (&[0xcf, 0xfa, 0xed, 0xfe], BinaryFormat::MachO),
+        (b"SQLite format 3\0", BinaryFormat::Sqlite),
+        (b"!<arch>\n", BinaryFormat::Archive),
+        (b"PK\x03\x04", BinaryFormat::Zip),
+    ];
+    
+    if let Some((_, format)) = MAGIC.iter().find(|(magic, _)| content.starts_with(magic)) {
+        return Some(*format);
+    }
+    
+    // Fat Mach-O binaries and Java classes share 0xCAFEBABE. A fat binary follows it with a
+    // handful of architectures, a class with its version, whose major part starts at 45.
+    if let Some(&[0xca, 0xfe, 0xba, 0xbe, a, b, c, d]) = content.get(..8) {
+        return Some(match u32::from_be_bytes([a, b, c, d]) {
+            0..45 => BinaryFormat::MachO,
+            _ => BinaryFormat::JavaClass,
+        });
+    }
+    
+    // Two version bytes then CRLF; only trusted with the extension since text can start that way too
+    let is_pyc = matches!(file_path.extension().and_then(|e| e.to_str()), Some("pyc" | "pyo"));
+    if is_pyc && content.get(2..4) == Some(b"\r\n") {
+        return Some(BinaryFormat::Pyc);
+    }
+    
+    let head = &content[..content.len().min(BINARY_SNIFF_LEN)];
+    memchr::memchr(0, head).map(|_| BinaryFormat::Data)
+}
+
+/// Regular files and symlinks found by `walk`, with their sizes, never descending into git metadata
+fn walk_files(walk: &mut WalkBuilder) -> Vec<(PathBuf, u64)> {
+    walk.filter_entry(|entry| entry.file_name() != ".git") // Never touch git metadata or gitfiles
+        .build()
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let file_type = entry.file_type()?;
+            if !file_type.is_file() && !file_type.is_symlink() {
+                return None;
             }
-            
-            let printable = content.chars()
-                .filter(|c| c.is_ascii_graphic() || c.is_ascii_whitespace())
-                .count();
-            
-            let printable_ratio = printable as f64 / total as f64;
-            printable_ratio < 0.95
+            let size = entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+            Some((entry.into_path(), size))
+        })
+        .collect()
+}
+
+/// Absolute paths of the files git tracks under `dir`; empty only when `dir` isn't in a work tree
+fn tracked_files(dir: &Path) -> Result<HashSet<PathBuf>> {
+    use std::process::Command;
+    
+    let git = |args: &[&str]| -> Result<Vec<u8>> {
+        let output = Command::new("git").args(args).current_dir(dir).output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Cannot tell which files in {} are tracked, git {} failed: {}",
+                dir.display(),
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
         }
+        Ok(output.stdout)
+    };
+    
+    let inside = match git(&["rev-parse", "--is-inside-work-tree"]) {
+        Ok(inside) => inside,
+        // Git can't tell a broken repository from none at all, so look for one ourselves
+        Err(_) if std::env::var_os("GIT_DIR").is_none()
+            && !dir.ancestors().any(|ancestor| ancestor.join(".git").exists()) => return Ok(HashSet::new()),
+        Err(e) => return Err(e),
+    };
+    if inside.trim_ascii() != b"true" {
+        return Ok(HashSet::new());
     }
+    
+    Ok(git(&["ls-files", "-z"])?
+        .split(|&b| b == 0)
+        .filter(|path| !path.is_empty())
+        .map(|path| dir.join(String::from_utf8_lossy(path).as_ref()))
+        .collect())
 }
 
 #[cfg(test)]
@@ -140,16 +690,22 @@ mod tests {
     
     #[test]
     fn test_binary_detection() {
-        let rewriter = PathRewriter::new("/tmp", "/tmp2");
+        let text = Path::new("notes.txt");
         
-        // Text content
-        assert!(!rewriter.is_likely_binary("Hello, world!\nThis is text."));
+        // Text, including non-ASCII and non-UTF-8 text, is never binary
+        assert_eq!(binary_format(text, b"Hello, world!\nThis is text."), None);
+        assert_eq!(binary_format(text, "# Kommentar: Größe 😀".as_bytes()), None);
+        assert_eq!(binary_format(text, b"caf\xe9 latin-1"), None);
         
-        // Binary-like content with null bytes
-        assert!(rewriter.is_likely_binary("Hello\0world"));
+        assert_eq!(binary_format(text, b"\x7fELF\x02\x01\x01"), Some(BinaryFormat::Elf));
+        assert_eq!(binary_format(text, &[0xcf, 0xfa, 0xed, 0xfe, 7, 0, 0, 1]), Some(BinaryFormat::MachO));
+        assert_eq!(binary_format(text, &[0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 2]), Some(BinaryFormat::MachO));
+        assert_eq!(binary_format(text, &[0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 61]), Some(BinaryFormat::JavaClass));
+        assert_eq!(binary_format(text, b"SQLite format 3\0\x10\0"), Some(BinaryFormat::Sqlite));
+        assert_eq!(binary_format(text, b"Hello\0world"), Some(BinaryFormat::Data));
         
-        // Content with many non-printable characters
-        let binary_like: String = (0..100).map(|_| '\x01').collect();
-        assert!(rewriter.is_likely_binary(&binary_like));
+        // The pyc magic is only trusted with the extension
+        assert_eq!(binary_format(Path::new("m.cpython-311.pyc"), b"\xa7\r\r\n\0\0"), Some(BinaryFormat::Pyc));
+        assert_eq!(binary_format(text, b"ab\r\nplain"), None);
     }
 }
\ No newline at end of file
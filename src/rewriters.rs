@@ -0,0 +1,462 @@
+//! Built-in rewriters for toolchains that hardcode absolute paths in their own formats
+
+use crate::error::Result;
+use crate::rewrite::{
+    edit_file, replace_in_text, rewrite_symlink, BinaryFormat, Entry, FileOutcome, PathMap, Rewriter,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Longest shebang line the kernel reads in full (Linux `BINPRM_BUF_SIZE` less the newline)
+const MAX_SHEBANG_LEN: usize = 127;
+
+/// The built-in rewriters, tried in order
+pub fn builtin() -> Vec<Box<dyn Rewriter>> {
+    vec![
+        Box::new(PythonVenv),
+        Box::new(NodeBinLinks),
+        Box::new(CMakeCache),
+        Box::new(CompileCommands),
+        Box::new(CargoTarget),
+    ]
+}
+
+/// Python virtual environments: `pyvenv.cfg`, activate scripts, console-script shebangs,
+/// `.pth` files and the `direct_url.json` of editable installs
+pub struct PythonVenv;
+
+enum VenvFile {
+    Config,
+    Activate,
+    Script,
+    Pth,
+    DirectUrl,
+}
+
+impl PythonVenv {
+    fn classify(entry: &Entry) -> Option<VenvFile> {
+        if entry.is_symlink {
+            return None;
+        }
+        let name = entry.relative.file_name()?.to_str()?;
+        let parent = entry.relative.parent()?.file_name().and_then(|name| name.to_str());
+        let in_bin = matches!(parent, Some("bin" | "Scripts"));
+        
+        let kind = match name {
+            "pyvenv.cfg" => return Some(VenvFile::Config),
+            "direct_url.json" if parent.is_some_and(|parent| parent.ends_with(".dist-info")) => VenvFile::DirectUrl,
+            _ if name.ends_with(".pth") => VenvFile::Pth,
+            _ if in_bin && name.to_ascii_lowercase().starts_with("activate") => VenvFile::Activate,
+            _ if in_bin => VenvFile::Script,
+            _ => return None,
+        };
+        in_venv(entry).then_some(kind)
+    }
+}
+
+impl Rewriter for PythonVenv {
+    fn name(&self) -> &'static str {
+        "Python venv"
+    }
+    
+    fn handles(&self, entry: &Entry) -> bool {
+        Self::classify(entry).is_some()
+    }
+    
+    fn rewrite(&self, entry: &Entry, paths: &PathMap) -> Result<FileOutcome> {
+        let Some(kind) = Self::classify(entry) else {
+            return Ok(FileOutcome::Unchanged);
+        };
+        
+        edit_file(entry, |content| match kind {
+            // Only values; `home` and `executable` usually point at the base interpreter anyway
+            VenvFile::Config => Ok(rewrite_lines(content, |line| {
+                let eq = memchr::memchr(b'=', line)?;
+                let value = paths.replace(&line[eq + 1..])?;
+                Some([&line[..=eq], &value].concat())
+            })),
+            VenvFile::Activate => Ok(paths.replace(content)),
+            VenvFile::Script if content.starts_with(b"#!") => Ok(rewrite_shebang(content, paths)),
+            // Compiled launchers and other binaries end up in `bin/` too
+            VenvFile::Script => replace_in_text(entry.path, content, paths),
+            VenvFile::Pth => Ok(rewrite_lines(content, |line| {
+                if line.starts_with(b"import ") || line.starts_with(b"import\t") {
+                    return paths.replace(line);
+                }
+                let path = paths.map_path(Path::new(std::str::from_utf8(line).ok()?))?;
+                Some(path.to_string_lossy().into_owned().into_bytes())
+            })),
+            VenvFile::DirectUrl => Ok(rewrite_direct_url(content, paths)),
+        })
+    }
+}
+
+/// Whether a directory between `entry` and the destination root has a `pyvenv.cfg`
+fn in_venv(entry: &Entry) -> bool {
+    entry
+        .path
+        .ancestors()
+        .skip(1)
+        .take(entry.relative.components().count())
+        .any(|dir| dir.join("pyvenv.cfg").is_file())
+}
+
+/// Move a console script's interpreter, switching to pip's `/bin/sh` trampoline when the
+/// new shebang would be too long or has a space the kernel would split on
+fn rewrite_shebang(content: &[u8], paths: &PathMap) -> Option<Vec<u8>> {
+    let line_end = memchr::memchr(b'\n', content).unwrap_or(content.len());
+    let (shebang, body) = content.split_at(line_end);
+    let new_shebang = paths.replace(shebang);
+    let new_body = paths.replace(body);
+    if new_shebang.is_none() && new_body.is_none() {
+        return None;
+    }
+    
+    let body = new_body.unwrap_or_else(|| body.to_vec());
+    let Some(shebang) = new_shebang else {
+        return Some([shebang, &body].concat());
+    };
+    
+    let interpreter = shebang[2..].trim_ascii();
+    if shebang.len() <= MAX_SHEBANG_LEN && !interpreter.contains(&b' ') {
+        return Some([shebang.as_slice(), &body].concat());
+    }
+    let mut trampoline = b"#!/bin/sh\n'''exec' \"".to_vec();
+    trampoline.extend_from_slice(interpreter);
+    trampoline.extend_from_slice(b"\" \"$0\" \"$@\"\n' '''");
+    trampoline.extend_from_slice(&body);
+    Some(trampoline)
+}
+
+/// Point a `file://` URL in `direct_url.json` at the destination, keeping the rest of the file as written
+fn rewrite_direct_url(content: &[u8], paths: &PathMap) -> Option<Vec<u8>> {
+    let json: serde_json::Value = serde_json::from_slice(content).ok()?;
+    let url = json.get("url")?.as_str()?;
+    let path = percent_decode(url.strip_prefix("file://")?)?;
+    let new_path = paths.map_path(Path::new(&path))?;
+    let new_url = format!("file://{}", percent_encode(&new_path.to_string_lossy()));
+    
+    let old = serde_json::to_string(url).ok()?;
+    let new = serde_json::to_string(&new_url).ok()?;
+    let content = std::str::from_utf8(content).ok()?;
+    content.contains(&old).then(|| content.replacen(&old, &new, 1).into_bytes())
+}
+
+/// Absolute symlinks in `node_modules/.bin`, as some package managers and `npm link` leave them
+pub struct NodeBinLinks;
+
+impl Rewriter for NodeBinLinks {
+    fn name(&self) -> &'static str {
+        "node_modules/.bin"
+    }
+    
+    fn handles(&self, entry: &Entry) -> bool {
+        entry.is_symlink
+            && entry.relative.parent().and_then(Path::file_name).is_some_and(|name| name == ".bin")
+            && entry.relative.components().any(|component| component.as_os_str() == "node_modules")
+    }
+    
+    fn rewrite(&self, entry: &Entry, paths: &PathMap) -> Result<FileOutcome> {
+        rewrite_symlink(entry, paths)
+    }
+}
+
+/// `CMakeCache.txt`: entry values and the build directory note, but not the help comments
+pub struct CMakeCache;
+
+impl Rewriter for CMakeCache {
+    fn name(&self) -> &'static str {
+        "CMake cache"
+    }
+    
+    fn handles(&self, entry: &Entry) -> bool {
+        !entry.is_symlink && entry.relative.file_name().is_some_and(|name| name == "CMakeCache.txt")
+    }
+    
+    fn rewrite(&self, entry: &Entry, paths: &PathMap) -> Result<FileOutcome> {
+        edit_file(entry, |content| {
+            Ok(rewrite_lines(content, |line| {
+                if line.starts_with(b"#") {
+                    return line.starts_with(b"# For build in directory:").then(|| paths.replace(line)).flatten();
+                }
+                if line.starts_with(b"//") {
+                    return None;
+                }
+                
+                // `KEY:TYPE=VALUE`, where a quoted key may itself contain `=`
+                let key_end = match line.strip_prefix(b"\"") {
+                    Some(rest) => memchr::memchr(b'"', rest)? + 2,
+                    None => 0,
+                };
+                let eq = key_end + memchr::memchr(b'=', &line[key_end..])?;
+                let value = paths.replace(&line[eq + 1..])?;
+                Some([&line[..=eq], &value].concat())
+            }))
+        })
+    }
+}
+
+/// `compile_commands.json`, where paths may be shell-escaped inside `command`
+pub struct CompileCommands;
+
+impl Rewriter for CompileCommands {
+    fn name(&self) -> &'static str {
+        "compile_commands.json"
+    }
+    
+    fn handles(&self, entry: &Entry) -> bool {
+        !entry.is_symlink && entry.relative.file_name().is_some_and(|name| name == "compile_commands.json")
+    }
+    
+    fn rewrite(&self, entry: &Entry, paths: &PathMap) -> Result<FileOutcome> {
+        edit_file(entry, |content| Ok(rewrite_compile_commands(content, paths)))
+    }
+}
+
+/// One entry of a compilation database, fields in the order CMake writes them
+#[derive(Serialize, Deserialize)]
+struct CompileCommand {
+    directory: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    command: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    arguments: Option<Vec<String>>,
+    file: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    output: Option<String>,
+    #[serde(flatten)]
+    other: BTreeMap<String, serde_json::Value>,
+}
+
+/// Move paths in every field, shell-escaping them inside `command`
+fn rewrite_compile_commands(content: &[u8], paths: &PathMap) -> Option<Vec<u8>> {
+    let mut commands: Vec<CompileCommand> = serde_json::from_slice(content).ok()?;
+    
+    let mut changed = false;
+    let mut replace = |value: &mut String, spellings: &[fn(&str) -> String]| {
+        if let Some(new) = paths.replace_spelled(value.as_bytes(), spellings) {
+            *value = String::from_utf8_lossy(&new).into_owned();
+            changed = true;
+        }
+    };
+    for command in &mut commands {
+        replace(&mut command.directory, &[verbatim]);
+        replace(&mut command.file, &[verbatim]);
+        command.output.iter_mut().for_each(|output| replace(output, &[verbatim]));
+        command.arguments.iter_mut().flatten().for_each(|argument| replace(argument, &[verbatim]));
+        // A bare path with a space would split into two arguments
+        command.command.iter_mut().for_each(|line| replace(line, &[shell_escape, verbatim]));
+    }
+    if !changed {
+        return None;
+    }
+    
+    let mut output = serde_json::to_vec_pretty(&commands).ok()?;
+    if content.ends_with(b"\n") {
+        output.push(b'\n');
+    }
+    Some(output)
+}
+
+/// Cargo's `target/`: dep-info files and the encoded dep-info in fingerprints.
+///
+/// Modification times are kept, since Cargo compares them to decide what to rebuild.
+pub struct CargoTarget;
+
+enum CargoFile {
+    DepInfo,
+    EncodedDepInfo,
+}
+
+impl CargoTarget {
+    fn classify(entry: &Entry) -> Option<CargoFile> {
+        if entry.is_symlink {
+            return None;
+        }
+        let name = entry.relative.file_name()?.to_str()?;
+        let grandparent = entry.relative.parent()?.parent()?.file_name();
+        
+        if name.starts_with("dep-") && grandparent.is_some_and(|dir| dir == ".fingerprint") {
+            return Some(CargoFile::EncodedDepInfo);
+        }
+        let in_target = entry.relative.components().any(|component| component.as_os_str() == "target");
+        (name.ends_with(".d") && in_target).then_some(CargoFile::DepInfo)
+    }
+}
+
+impl Rewriter for CargoTarget {
+    fn name(&self) -> &'static str {
+        "Cargo target"
+    }
+    
+    fn handles(&self, entry: &Entry) -> bool {
+        Self::classify(entry).is_some()
+    }
+    
+    fn rewrite(&self, entry: &Entry, paths: &PathMap) -> Result<FileOutcome> {
+        let Some(kind) = Self::classify(entry) else {
+            return Ok(FileOutcome::Unchanged);
+        };
+        let modified = fs::metadata(entry.path)?.modified()?;
+        
+        let outcome = edit_file(entry, |content| match kind {
+            CargoFile::DepInfo => Ok(paths.replace_spelled(content, &[make_escape])),
+            CargoFile::EncodedDepInfo if !paths.contains(content) => Ok(None),
+            CargoFile::EncodedDepInfo => rewrite_encoded_dep_info(content, paths)
+                .map(Some)
+                .ok_or(BinaryFormat::Data),
+        })?;
+        
+        if outcome == FileOutcome::Rewritten && !entry.is_dry_run() {
+            fs::File::options().write(true).open(entry.path)?.set_modified(modified)?;
+        }
+        Ok(outcome)
+    }
+}
+
+/// Re-encode Cargo's binary dep-info with moved paths and env values.
+///
+/// Paths and values are `u32`-length-prefixed, so a plain replacement would corrupt the file.
+/// Current Cargo prefixes a fake single-file count, an invalid path kind and a version byte.
+fn rewrite_encoded_dep_info(content: &[u8], paths: &PathMap) -> Option<Vec<u8>> {
+    let mut input = content;
+    let mut output = Vec::with_capacity(content.len());
+    
+    let versioned = content.get(..5) == Some(&[1, 0, 0, 0, 0xff]);
+    if versioned {
+        let header = take(&mut input, 6)?;
+        if header[5] != 1 {
+            return None;
+        }
+        output.extend_from_slice(header);
+    }
+    
+    let files = take_u32(&mut input)?;
+    output.extend_from_slice(&files.to_le_bytes());
+    for _ in 0..files {
+        // Relative to the package or to the target directory; absolute paths are kept as-is
+        output.extend_from_slice(take(&mut input, 1)?);
+        let path = take_bytes(&mut input)?;
+        let moved = std::str::from_utf8(path).ok().and_then(|path| paths.map_path(Path::new(path)));
+        match moved {
+            Some(moved) => put_bytes(&mut output, moved.to_string_lossy().as_bytes()),
+            None => put_bytes(&mut output, path),
+        }
+        
+        if versioned {
+            let has_checksum = take(&mut input, 1)?;
+            output.extend_from_slice(has_checksum);
+            if has_checksum[0] == 1 {
+                output.extend_from_slice(take(&mut input, 8)?);
+                put_bytes(&mut output, take_bytes(&mut input)?);
+            }
+        }
+    }
+    
+    let env = take_u32(&mut input)?;
+    output.extend_from_slice(&env.to_le_bytes());
+    for _ in 0..env {
+        put_bytes(&mut output, take_bytes(&mut input)?);
+        let has_value = take(&mut input, 1)?;
+        output.extend_from_slice(has_value);
+        if has_value[0] == 1 {
+            let value = take_bytes(&mut input)?;
+            put_bytes(&mut output, &paths.replace(value).unwrap_or_else(|| value.to_vec()));
+        }
+    }
+    
+    input.is_empty().then_some(output)
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    let (head, rest) = input.split_at_checked(len)?;
+    *input = rest;
+    Some(head)
+}
+
+fn take_u32(input: &mut &[u8]) -> Option<u32> {
+    Some(u32::from_le_bytes(take(input, 4)?.try_into().ok()?))
+}
+
+fn take_bytes<'a>(input: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let len = take_u32(input)?;
+    take(input, len as usize)
+}
+
+fn put_bytes(output: &mut Vec<u8>, bytes: &[u8]) {
+    output.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    output.extend_from_slice(bytes);
+}
+
+/// Apply `rewrite` to each line without its terminator; `None` if no line changed
+fn rewrite_lines(content: &[u8], mut rewrite: impl FnMut(&[u8]) -> Option<Vec<u8>>) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(content.len());
+    let mut changed = false;
+    for line in content.split_inclusive(|&b| b == b'\n') {
+        let text_len = line.len() - line.iter().rev().take_while(|&&b| b == b'\n' || b == b'\r').count();
+        let (text, terminator) = line.split_at(text_len);
+        match rewrite(text) {
+            Some(new_text) => {
+                changed = true;
+                output.extend_from_slice(&new_text);
+            }
+            None => output.extend_from_slice(text),
+        }
+        output.extend_from_slice(terminator);
+    }
+    changed.then_some(output)
+}
+
+/// A path as written
+fn verbatim(value: &str) -> String {
+    value.to_string()
+}
+
+/// Backslash-escape the characters a POSIX shell would split or expand
+fn shell_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, ' ' | '\t' | '"' | '\'' | '\\' | '$' | '`' | '(' | ')' | '&' | ';' | '|' | '*' | '?') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Spaces as rustc writes them in Makefile-style dep-info
+fn make_escape(value: &str) -> String {
+    value.replace(' ', "\\ ")
+}
+
+/// Keep unreserved characters and `/`, as Python's `Path.as_uri` does
+fn percent_encode(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for &b in path.as_bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~' | b'/') {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
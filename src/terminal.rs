@@ -1,5 +1,6 @@
 use crate::error::{GitWarpError, Result};
 use std::path::Path;
+#[cfg(target_os = "macos")]
 use std::process::Command;
 
 #[derive(Debug, Clone)]
@@ -11,6 +12,7 @@ pub enum TerminalMode {
 }
 
 impl TerminalMode {
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Self> {
         match s.to_lowercase().as_str() {
             "tab" => Some(Self::Tab),
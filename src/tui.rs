@@ -2,8 +2,8 @@ use crate::error::Result;
 use ratatui::{
     backend::CrosstermBackend,
     Terminal as RatatuiTerminal,
-    widgets::{Block, Borders, Paragraph, List, ListItem, ListState, Gauge, Table, Row, Cell},
-    layout::{Layout, Constraint, Direction, Alignment, Margin},
+    widgets::{Block, Borders, Paragraph, List, ListItem, ListState, Gauge},
+    layout::{Layout, Constraint, Direction, Alignment},
     text::{Span, Line},
     style::{Style, Color, Modifier},
     Frame,
@@ -59,6 +59,12 @@ impl AgentStatus {
     }
 }
 
+impl Default for TuiApp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TuiApp {
     pub fn new() -> Self {
         Self {
@@ -139,41 +145,37 @@ impl TuiApp {
         loop {
             // Non-blocking event check
             let timeout = Duration::from_millis(100);
-            if poll(timeout)? {
-                if let Event::Key(key) = event::read()? {
-                    match key.code {
-                        KeyCode::Char('q') => {
-                            self.should_quit = true;
-                        }
-                        KeyCode::Esc => {
-                            self.should_quit = true;
-                        }
-                        KeyCode::Up => {
-                            if self.selected_index > 0 {
-                                self.selected_index -= 1;
-                            }
-                        }
-                        KeyCode::Down => {
-                            if self.selected_index < activities.len().saturating_sub(1) {
-                                self.selected_index += 1;
-                            }
-                        }
-                        KeyCode::Char('r') => {
-                            // Simulate refresh - add new activity
-                            activities.insert(0, AgentActivity {
-                                timestamp: format!("{:02}:{:02}:{:02}", 
-                                    chrono::Local::now().hour(),
-                                    chrono::Local::now().minute(), 
-                                    chrono::Local::now().second()),
-                                agent_name: "Claude-Code".to_string(),
-                                activity: "Processing new request".to_string(),
-                                file_path: Some(PathBuf::from("/project/src/new_module.rs")),
-                                status: AgentStatus::Active,
-                            });
-                            self.selected_index = 0;
-                        }
-                        _ => {}
+            if poll(timeout)?
+                && let Event::Key(key) = event::read()?
+            {
+                match key.code {
+                    KeyCode::Char('q') => {
+                        self.should_quit = true;
+                    }
+                    KeyCode::Esc => {
+                        self.should_quit = true;
+                    }
+                    KeyCode::Up if self.selected_index > 0 => {
+                        self.selected_index -= 1;
                     }
+                    KeyCode::Down if self.selected_index < activities.len().saturating_sub(1) => {
+                        self.selected_index += 1;
+                    }
+                    KeyCode::Char('r') => {
+                        // Simulate refresh - add new activity
+                        activities.insert(0, AgentActivity {
+                            timestamp: format!("{:02}:{:02}:{:02}", 
+                                chrono::Local::now().hour(),
+                                chrono::Local::now().minute(), 
+                                chrono::Local::now().second()),
+                            agent_name: "Claude-Code".to_string(),
+                            activity: "Processing new request".to_string(),
+                            file_path: Some(PathBuf::from("/project/src/new_module.rs")),
+                            status: AgentStatus::Active,
+                        });
+                        self.selected_index = 0;
+                    }
+                    _ => {}
                 }
             }
             
@@ -301,6 +303,7 @@ impl TuiApp {
     }
 }
 
+#[derive(Default)]
 pub struct AgentsDashboard;
 
 impl AgentsDashboard {
@@ -329,14 +332,24 @@ impl AgentsDashboard {
     }
 }
 
-pub struct CleanupTui;
+#[derive(Default)]
+pub struct CleanupTui {
+    include_locked: bool,
+}
 
 impl CleanupTui {
     pub fn new() -> Self {
-        Self
+        Self::default()
+    }
+    
+    /// Allow locked worktrees to be selected; they are always listed with their lock reason
+    pub fn include_locked(mut self, include_locked: bool) -> Self {
+        self.include_locked = include_locked;
+        self
     }
     
     pub fn run(&self) -> Result<Vec<String>> {
+        use crate::config::ConfigManager;
         use crate::git::GitRepository;
         use crossterm::{
             event::{self, Event, KeyCode, KeyEventKind},
@@ -355,8 +368,10 @@ impl CleanupTui {
         // Get the git repository and worktrees
         let git_repo = GitRepository::find()
             .map_err(|_| anyhow::anyhow!("Not in a git repository"))?;
+        let config = ConfigManager::for_repo(git_repo.main_root_path())?.config;
+        let git_repo = git_repo.with_config(&config.git);
         let worktrees = git_repo.list_worktrees()?;
-        let branch_statuses = git_repo.analyze_branches_for_cleanup(&worktrees)?;
+        let branch_statuses = git_repo.analyze_branches_for_cleanup_with(&worktrees, true)?;
 
         if branch_statuses.is_empty() {
             println!("✨ No worktrees found that can be cleaned up!");
@@ -375,7 +390,7 @@ impl CleanupTui {
         let mut should_quit = false;
         let mut confirmed = false;
 
-        let result = loop {
+        loop {
             terminal.draw(|f| {
                 let chunks = Layout::default()
                     .direction(Direction::Vertical)
@@ -403,17 +418,31 @@ impl CleanupTui {
                         let merged_indicator = if status.is_merged { "✅" } else { "🔄" };
                         let style = if i == selected_index {
                             Style::default().bg(Color::Blue).fg(Color::White)
+                        } else if status.locked.is_some() && !self.include_locked {
+                            Style::default().fg(Color::DarkGray)
                         } else {
                             Style::default()
                         };
+                        let lock_note = match status.locked.as_deref() {
+                            Some("") => " 🔒 locked".to_string(),
+                            Some(reason) => format!(" 🔒 locked: {}", reason),
+                            None => String::new(),
+                        };
                         
                         ListItem::new(format!(
-                            "{} {} {} - {}{}",
+                            "{} {} {} - {}{}{}",
                             checkbox,
                             merged_indicator,
                             status.branch,
-                            if status.has_remote { "with remote" } else { "no remote" },
-                            if status.has_uncommitted_changes { " (uncommitted)" } else { "" }
+                            if status.upstream_gone {
+                                "remote deleted"
+                            } else if status.has_remote {
+                                "with remote"
+                            } else {
+                                "no remote"
+                            },
+                            if status.has_uncommitted_changes { " (uncommitted)" } else { "" },
+                            lock_note
                         )).style(style)
                     })
                     .collect();
@@ -438,35 +467,32 @@ impl CleanupTui {
             })?;
 
             // Handle input
-            if let Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press {
-                    match key.code {
-                        KeyCode::Char('q') => {
-                            should_quit = true;
-                            break;
-                        }
-                        KeyCode::Up => {
-                            if selected_index > 0 {
-                                selected_index -= 1;
-                            }
-                        }
-                        KeyCode::Down => {
-                            if selected_index < branch_statuses.len() - 1 {
-                                selected_index += 1;
-                            }
-                        }
-                        KeyCode::Char(' ') => {
-                            selected_branches[selected_index] = !selected_branches[selected_index];
-                        }
-                        KeyCode::Enter => {
-                            confirmed = true;
-                            break;
-                        }
-                        _ => {}
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                match key.code {
+                    KeyCode::Char('q') => {
+                        should_quit = true;
+                        break;
+                    }
+                    KeyCode::Up => {
+                        selected_index = selected_index.saturating_sub(1);
+                    }
+                    KeyCode::Down if selected_index < branch_statuses.len() - 1 => {
+                        selected_index += 1;
                     }
+                    // Locked worktrees are listed for reference but stay unselectable
+                    KeyCode::Char(' ') if self.include_locked || branch_statuses[selected_index].locked.is_none() => {
+                        selected_branches[selected_index] = !selected_branches[selected_index];
+                    }
+                    KeyCode::Enter => {
+                        confirmed = true;
+                        break;
+                    }
+                    _ => {}
                 }
             }
-        };
+        }
 
         // Cleanup terminal
         disable_raw_mode()?;
@@ -494,6 +520,7 @@ impl CleanupTui {
     }
 }
 
+#[derive(Default)]
 pub struct ConfigTui;
 
 impl ConfigTui {
@@ -520,7 +547,7 @@ mod tests {
     
     #[test]
     fn test_tui_creation() {
-        let dashboard = AgentsDashboard::new();
+        let _dashboard = AgentsDashboard::new();
         // Just test that we can create the TUI components
         let _cleanup_tui = CleanupTui::new();
         let _config_tui = ConfigTui::new();
@@ -1,99 +1,92 @@
-use git_warp::cow::{clone_directory, is_cow_supported};
+use git_warp::cow::{clone_directory, clone_directory_with, is_cow_supported, CloneReport, CloneSpec, CloneStrategy};
 use git_warp::git::GitRepository;
+use git_warp::progress::NoProgress;
 use git_warp::rewrite::PathRewriter;
 use tempfile::tempdir;
 use std::fs;
+use std::path::Path;
 use std::process::Command;
 
 #[test]
 fn test_cow_clone_with_git_worktree_registration() {
-    let temp_dir = setup_git_repository();
+    let temp_dir = setup_repository_with_large_deps();
     let repo_path = temp_dir.path();
+    let worktrees_dir = tempdir().unwrap();
+    let worktree_path = worktrees_dir.path().join("cow-integration");
+    let branch_name = "feature/cow-integration";
     
     std::env::set_current_dir(repo_path).unwrap();
+    let git_repo = GitRepository::find().unwrap();
+    
+    let report = clone_worktree(&git_repo, branch_name, &worktree_path);
+    assert!(report.is_complete());
+    
+    // The worktree gets its own gitfile pointing at the admin directory, not a repository
+    let gitfile = fs::read_to_string(worktree_path.join(".git")).unwrap();
+    let admin_dir = repo_path.join(".git").join("worktrees").join("cow-integration");
+    assert_eq!(gitfile.trim(), format!("gitdir: {}", admin_dir.display()));
+    assert!(admin_dir.join("index").exists());
+    
+    // HEAD is on the new branch and the cloned tracked files match it
+    assert_eq!(git_output(&worktree_path, &["rev-parse", "--abbrev-ref", "HEAD"]), branch_name);
+    assert_eq!(git_output(&worktree_path, &["status", "--porcelain", "--untracked-files=no"]), "");
+    
+    // Ignored dependencies came along with the clone
+    assert!(worktree_path.join("node_modules").join("package-1").join("package.json").exists());
+    
+    let worktrees = git_repo.list_worktrees().unwrap();
+    let cow_worktree = worktrees.iter().find(|w| w.branch == branch_name).unwrap();
+    assert_eq!(cow_worktree.path, worktree_path);
     
+    git_repo.remove_worktree(&worktree_path, true).unwrap();
+    assert!(!worktree_path.exists());
+}
+
+#[test]
+fn test_cow_worktree_commits_show_up_in_main_repo() {
+    let temp_dir = setup_git_repository();
+    let repo_path = temp_dir.path();
+    let worktrees_dir = tempdir().unwrap();
+    let worktree_path = worktrees_dir.path().join("cow-commits");
+    
+    std::env::set_current_dir(repo_path).unwrap();
     let git_repo = GitRepository::find().unwrap();
-    let branch_name = "feature/cow-integration";
-    let worktree_path = repo_path.join("worktrees").join("cow-integration");
+    clone_worktree(&git_repo, "cow-commits", &worktree_path);
     
-    // Test the full CoW + Git worktree integration
-    let cow_result = clone_directory(repo_path, &worktree_path);
+    fs::write(worktree_path.join("feature.txt"), "CoW feature").unwrap();
+    git_output(&worktree_path, &["add", "feature.txt"]);
+    git_output(&worktree_path, &["commit", "-m", "Add CoW feature"]);
     
-    match cow_result {
-        Ok(()) => {
-            println!("CoW clone succeeded, testing git integration");
-            
-            // Verify the clone exists
-            assert!(worktree_path.exists());
-            assert!(worktree_path.join(".git").exists());
-            assert!(worktree_path.join("README.md").exists());
-            
-            // Now we need to register this as a proper git worktree
-            // First, remove the .git directory (it's a copy, not a worktree)
-            fs::remove_dir_all(worktree_path.join(".git")).unwrap();
-            
-            // Use git worktree add with existing directory
-            let result = Command::new("git")
-                .args(&["worktree", "add", "--detach", worktree_path.to_str().unwrap()])
-                .current_dir(repo_path)
-                .output();
-            
-            match result {
-                Ok(output) => {
-                    if output.status.success() {
-                        // Now create and checkout the branch
-                        Command::new("git")
-                            .args(&["checkout", "-b", branch_name])
-                            .current_dir(&worktree_path)
-                            .output()
-                            .unwrap();
-                        
-                        // Verify it's a proper worktree
-                        let worktrees = git_repo.list_worktrees().unwrap();
-                        let cow_worktree = worktrees.iter().find(|w| w.branch == branch_name);
-                        assert!(cow_worktree.is_some());
-                        
-                        // Test that we can make commits
-                        fs::write(worktree_path.join("feature.txt"), "CoW feature").unwrap();
-                        
-                        Command::new("git")
-                            .args(&["add", "."])
-                            .current_dir(&worktree_path)
-                            .output()
-                            .unwrap();
-                        
-                        Command::new("git")
-                            .args(&["commit", "-m", "Add CoW feature"])
-                            .current_dir(&worktree_path)
-                            .output()
-                            .unwrap();
-                        
-                        println!("CoW + Git worktree integration successful");
-                    } else {
-                        println!("Git worktree registration failed: {}", 
-                                String::from_utf8_lossy(&output.stderr));
-                    }
-                },
-                Err(e) => {
-                    println!("Failed to register git worktree: {}", e);
-                }
-            }
-            
-            // Cleanup
-            git_repo.remove_worktree(&worktree_path).ok();
-        },
-        Err(e) => {
-            println!("CoW clone failed (expected on non-APFS): {}", e);
-            
-            // Test fallback to regular worktree
-            let result = git_repo.create_worktree_and_branch(branch_name, &worktree_path, None);
-            assert!(result.is_ok());
-            
-            println!("Fallback to regular worktree successful");
-            
-            git_repo.remove_worktree(&worktree_path).unwrap();
-        }
-    }
+    // The commit lives in the shared repository, on the worktree's branch only
+    assert_eq!(git_output(repo_path, &["log", "-1", "--format=%s", "cow-commits"]), "Add CoW feature");
+    assert_eq!(git_output(repo_path, &["log", "-1", "--format=%s", "HEAD"]), "Initial commit");
+    assert_eq!(git_output(repo_path, &["status", "--porcelain"]), "");
+    assert!(!repo_path.join("feature.txt").exists());
+}
+
+#[test]
+fn test_cow_worktree_for_existing_branch() {
+    let temp_dir = setup_git_repository();
+    let repo_path = temp_dir.path();
+    let worktrees_dir = tempdir().unwrap();
+    let worktree_path = worktrees_dir.path().join("older");
+    
+    // The branch stays behind while the main checkout moves on
+    git_output(repo_path, &["branch", "older"]);
+    fs::write(repo_path.join("newer.txt"), "only on main").unwrap();
+    fs::write(repo_path.join("README.md"), "# Changed on main").unwrap();
+    git_output(repo_path, &["add", "."]);
+    git_output(repo_path, &["commit", "-m", "Move main on"]);
+    
+    std::env::set_current_dir(repo_path).unwrap();
+    let git_repo = GitRepository::find().unwrap();
+    clone_worktree(&git_repo, "older", &worktree_path);
+    
+    // Tracked files follow the branch, not the checkout they were cloned from
+    assert!(!worktree_path.join("newer.txt").exists());
+    assert_eq!(fs::read_to_string(worktree_path.join("README.md")).unwrap(), "# Test Repo");
+    assert_eq!(git_output(&worktree_path, &["status", "--porcelain"]), "");
+    assert_eq!(git_output(&worktree_path, &["rev-parse", "HEAD"]), git_output(repo_path, &["rev-parse", "older"]));
 }
 
 #[test]
@@ -103,10 +96,10 @@ fn test_cow_with_path_rewriting() {
     let clone_path = temp_dir.path().parent().unwrap().join("clone");
     
     // Test CoW clone followed by path rewriting
-    let cow_result = clone_directory(repo_path, &clone_path);
+    let cow_result = clone_directory(repo_path, &clone_path, &CloneSpec::new());
     
     match cow_result {
-        Ok(()) => {
+        Ok(_report) => {
             println!("CoW clone succeeded, testing path rewriting");
             
             // Verify files were copied
@@ -122,7 +115,7 @@ fn test_cow_with_path_rewriting() {
             
             // Apply path rewriting
             let rewriter = PathRewriter::new(
-                &repo_path,
+                repo_path,
                 &clone_path
             );
             
@@ -151,7 +144,7 @@ fn test_cow_with_path_rewriting() {
 }
 
 #[test]
-fn test_cow_preserves_git_history() {
+fn test_cow_clone_leaves_primary_git_behind() {
     let temp_dir = setup_git_repository_with_history();
     let repo_path = temp_dir.path();
     let clone_path = temp_dir.path().parent().unwrap().join("history_clone");
@@ -160,60 +153,31 @@ fn test_cow_preserves_git_history() {
     
     // Get original git history
     let original_log = Command::new("git")
-        .args(&["log", "--oneline"])
+        .args(["log", "--oneline"])
         .current_dir(repo_path)
         .output()
         .unwrap();
     
-    let cow_result = clone_directory(repo_path, &clone_path);
+    let cow_result = clone_directory(repo_path, &clone_path, &CloneSpec::new());
     
     match cow_result {
-        Ok(()) => {
-            println!("CoW clone succeeded, checking git history preservation");
-            
-            // Check that .git directory was copied
-            assert!(clone_path.join(".git").exists());
+        Ok(_report) => {
+            println!("CoW clone succeeded, checking the primary .git was left behind");
             
-            // Get cloned git history
-            let cloned_log = Command::new("git")
-                .args(&["log", "--oneline"])
-                .current_dir(&clone_path)
-                .output()
-                .unwrap();
+            // The clone gets the working files but never the primary repository
+            assert!(!clone_path.join(".git").exists());
+            assert!(clone_path.join("README.md").exists());
             
-            // History should be identical
-            assert_eq!(original_log.stdout, cloned_log.stdout);
-            
-            // Should be able to create new commits in clone
+            // Changes in the clone must not leak into the original history
             fs::write(clone_path.join("new_file.txt"), "New content").unwrap();
             
-            Command::new("git")
-                .args(&["add", "."])
-                .current_dir(&clone_path)
-                .output()
-                .unwrap();
-            
-            Command::new("git")
-                .args(&["commit", "-m", "Add new file in clone"])
-                .current_dir(&clone_path)
-                .output()
-                .unwrap();
-            
-            // Original should not have the new commit
             let new_original_log = Command::new("git")
-                .args(&["log", "--oneline"])
+                .args(["log", "--oneline"])
                 .current_dir(repo_path)
                 .output()
                 .unwrap();
             
-            let new_cloned_log = Command::new("git")
-                .args(&["log", "--oneline"])
-                .current_dir(&clone_path)
-                .output()
-                .unwrap();
-            
-            // Histories should now be different
-            assert_ne!(new_original_log.stdout, new_cloned_log.stdout);
+            assert_eq!(original_log.stdout, new_original_log.stdout);
             
             println!("CoW git history preservation test successful");
         },
@@ -234,11 +198,11 @@ fn test_cow_with_large_dependencies() {
     println!("Original repository has {} files", original_file_count);
     
     let start_time = std::time::Instant::now();
-    let cow_result = clone_directory(repo_path, &clone_path);
+    let cow_result = clone_directory(repo_path, &clone_path, &CloneSpec::new());
     let clone_duration = start_time.elapsed();
     
     match cow_result {
-        Ok(()) => {
+        Ok(_report) => {
             println!("CoW clone completed in {:?}", clone_duration);
             
             // Verify all files were copied
@@ -283,13 +247,13 @@ fn test_cow_with_large_dependencies() {
 #[test]
 fn test_cow_filesystem_detection() {
     // Test filesystem detection accuracy
-    let current_dir = std::env::current_dir().unwrap();
+    let current_dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
     
     let cow_supported = is_cow_supported(&current_dir);
     
     match cow_supported {
-        Ok(true) => {
-            println!("CoW is supported on current filesystem");
+        Ok(capabilities) if capabilities.is_cow() => {
+            println!("CoW is supported on current filesystem ({})", capabilities.filesystem);
             
             // Should be able to perform CoW operations
             let temp_dir = tempdir().unwrap();
@@ -299,14 +263,14 @@ fn test_cow_filesystem_detection() {
             fs::create_dir_all(&src).unwrap();
             fs::write(src.join("test.txt"), "test content").unwrap();
             
-            let cow_result = clone_directory(&src, &dst);
+            let cow_result = clone_directory(&src, &dst, &CloneSpec::new());
             assert!(cow_result.is_ok());
             
             assert!(dst.exists());
             assert_eq!(fs::read_to_string(dst.join("test.txt")).unwrap(), "test content");
         },
-        Ok(false) => {
-            println!("CoW is not supported on current filesystem");
+        Ok(capabilities) => {
+            println!("CoW is not supported on current filesystem ({})", capabilities.filesystem);
             
             // Should gracefully fall back
             let temp_dir = tempdir().unwrap();
@@ -316,8 +280,15 @@ fn test_cow_filesystem_detection() {
             fs::create_dir_all(&src).unwrap();
             fs::write(src.join("test.txt"), "test content").unwrap();
             
-            let cow_result = clone_directory(&src, &dst);
+            let cow_result = clone_directory(&src, &dst, &CloneSpec::new());
             assert!(cow_result.is_err()); // Should fail gracefully
+            
+            // The non-CoW fallback still produces a usable tree
+            let strategy = capabilities.best_strategy();
+            assert!(!strategy.is_cow());
+            let report = clone_directory_with(&src, &dst, &CloneSpec::new(), strategy).unwrap();
+            assert_eq!(report.copied, 1);
+            assert_eq!(fs::read_to_string(dst.join("test.txt")).unwrap(), "test content");
         },
         Err(e) => {
             println!("CoW support detection failed: {}", e);
@@ -333,7 +304,7 @@ fn test_cow_error_recovery() {
     let nonexistent_src = temp_dir.path().join("nonexistent");
     let dst1 = temp_dir.path().join("dst1");
     
-    let result = clone_directory(&nonexistent_src, &dst1);
+    let result = clone_directory(&nonexistent_src, &dst1, &CloneSpec::new());
     assert!(result.is_err());
     assert!(!dst1.exists());
     
@@ -353,7 +324,7 @@ fn test_cow_error_recovery() {
         fs::set_permissions(&readonly_parent, perms).unwrap();
         
         let dst2 = readonly_parent.join("dst");
-        let result = clone_directory(&src, &dst2);
+        let result = clone_directory(&src, &dst2, &CloneSpec::new());
         
         // Restore permissions for cleanup
         let mut perms = fs::metadata(&readonly_parent).unwrap().permissions();
@@ -362,7 +333,7 @@ fn test_cow_error_recovery() {
         
         // Should handle permission errors gracefully
         match result {
-            Ok(()) => println!("CoW succeeded despite readonly parent"),
+            Ok(_report) => println!("CoW succeeded despite readonly parent"),
             Err(e) => println!("CoW failed as expected with readonly parent: {}", e),
         }
     }
@@ -372,10 +343,10 @@ fn test_cow_error_recovery() {
     fs::create_dir_all(&dst3).unwrap();
     fs::write(dst3.join("existing.txt"), "existing").unwrap();
     
-    let result = clone_directory(&src, &dst3);
+    let result = clone_directory(&src, &dst3, &CloneSpec::new());
     
     match result {
-        Ok(()) => {
+        Ok(_report) => {
             // Should overwrite existing destination
             assert!(!dst3.join("existing.txt").exists()); // Old content gone
             assert!(dst3.join("test.txt").exists()); // New content present
@@ -388,19 +359,40 @@ fn test_cow_error_recovery() {
 
 // Helper functions
 
+fn clone_worktree(git_repo: &GitRepository, branch: &str, worktree_path: &Path) -> CloneReport {
+    let strategy = is_cow_supported(git_repo.root_path())
+        .map(|capabilities| capabilities.best_strategy())
+        .unwrap_or(CloneStrategy::Copy);
+    let main_worktree = git_repo.list_worktrees().unwrap().remove(0);
+    
+    let mut transaction = git_repo.begin_worktree_creation();
+    let report = transaction
+        .clone_worktree_and_branch(branch, worktree_path, None, &main_worktree, &CloneSpec::new(), strategy, &NoProgress)
+        .unwrap();
+    transaction.commit();
+    
+    report
+}
+
+fn git_output(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git").args(args).current_dir(dir).output().unwrap();
+    assert!(output.status.success(), "git {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
 fn setup_git_repository() -> tempfile::TempDir {
     let temp_dir = tempdir().unwrap();
     let repo_path = temp_dir.path();
     
-    Command::new("git").args(&["init"]).current_dir(repo_path).output().unwrap();
-    Command::new("git").args(&["config", "user.email", "test@example.com"]).current_dir(repo_path).output().unwrap();
-    Command::new("git").args(&["config", "user.name", "Test User"]).current_dir(repo_path).output().unwrap();
+    Command::new("git").args(["init"]).current_dir(repo_path).output().unwrap();
+    Command::new("git").args(["config", "user.email", "test@example.com"]).current_dir(repo_path).output().unwrap();
+    Command::new("git").args(["config", "user.name", "Test User"]).current_dir(repo_path).output().unwrap();
     
     fs::write(repo_path.join("README.md"), "# Test Repo").unwrap();
     fs::write(repo_path.join(".gitignore"), "node_modules/\n*.log\n").unwrap();
     
-    Command::new("git").args(&["add", "."]).current_dir(repo_path).output().unwrap();
-    Command::new("git").args(&["commit", "-m", "Initial commit"]).current_dir(repo_path).output().unwrap();
+    Command::new("git").args(["add", "."]).current_dir(repo_path).output().unwrap();
+    Command::new("git").args(["commit", "-m", "Initial commit"]).current_dir(repo_path).output().unwrap();
     
     temp_dir
 }
@@ -432,8 +424,8 @@ echo "Building in {}"
     fs::write(scripts_dir.join("build.sh"), script_content).unwrap();
     
     // Add to git
-    Command::new("git").args(&["add", "."]).current_dir(repo_path).output().unwrap();
-    Command::new("git").args(&["commit", "-m", "Add configs"]).current_dir(repo_path).output().unwrap();
+    Command::new("git").args(["add", "."]).current_dir(repo_path).output().unwrap();
+    Command::new("git").args(["commit", "-m", "Add configs"]).current_dir(repo_path).output().unwrap();
     
     temp_dir
 }
@@ -445,8 +437,8 @@ fn setup_git_repository_with_history() -> tempfile::TempDir {
     // Create multiple commits
     for i in 1..=5 {
         fs::write(repo_path.join(format!("file_{}.txt", i)), format!("Content {}", i)).unwrap();
-        Command::new("git").args(&["add", "."]).current_dir(repo_path).output().unwrap();
-        Command::new("git").args(&["commit", "-m", &format!("Commit {}", i)]).current_dir(repo_path).output().unwrap();
+        Command::new("git").args(["add", "."]).current_dir(repo_path).output().unwrap();
+        Command::new("git").args(["commit", "-m", &format!("Commit {}", i)]).current_dir(repo_path).output().unwrap();
     }
     
     temp_dir
@@ -1,11 +1,10 @@
 use git_warp::config::{Config, ConfigManager};
 use git_warp::git::GitRepository;
-use git_warp::cow::clone_directory;
+use git_warp::cow::{clone_directory, CloneSpec};
 use git_warp::process::ProcessManager;
 use tempfile::tempdir;
 use std::fs;
 use std::process::Command;
-use std::path::Path;
 
 #[test]
 fn test_complete_worktree_creation_workflow() {
@@ -42,13 +41,13 @@ fn test_complete_worktree_creation_workflow() {
     
     // Commit the work
     Command::new("git")
-        .args(&["add", "."])
+        .args(["add", "."])
         .current_dir(&worktree_path)
         .output()
         .unwrap();
     
     Command::new("git")
-        .args(&["commit", "-m", "Add feature implementation"])
+        .args(["commit", "-m", "Add feature implementation"])
         .current_dir(&worktree_path)
         .output()
         .unwrap();
@@ -60,7 +59,7 @@ fn test_complete_worktree_creation_workflow() {
     assert!(feature_worktree.is_some());
     
     // 8. Clean up
-    let cleanup_result = git_repo.remove_worktree(&worktree_path);
+    let cleanup_result = git_repo.remove_worktree(&worktree_path, false);
     assert!(cleanup_result.is_ok());
     assert!(!worktree_path.exists());
     
@@ -79,10 +78,10 @@ fn test_cow_worktree_creation_with_dependencies() {
     let worktree_path = repo_path.join("worktrees").join("cow-test");
     
     // Try CoW clone first
-    let cow_result = clone_directory(repo_path, &worktree_path);
+    let cow_result = clone_directory(repo_path, &worktree_path, &CloneSpec::new());
     
     match cow_result {
-        Ok(()) => {
+        Ok(_report) => {
             println!("CoW clone succeeded");
             
             // Verify dependencies were copied
@@ -183,7 +182,7 @@ sleep 30
     child.wait().unwrap();
     
     // Now cleanup should succeed
-    let cleanup_result = git_repo.remove_worktree(&worktree_path);
+    let cleanup_result = git_repo.remove_worktree(&worktree_path, true);
     assert!(cleanup_result.is_ok());
     
     println!("Process detection and cleanup workflow test passed");
@@ -207,7 +206,7 @@ auto_fetch = false
     fs::write(&config_path, config_content).unwrap();
     
     // Test layered configuration loading
-    let manager = ConfigManager {
+    let _manager = ConfigManager {
         config: Config::default(),
         config_path: config_path.clone(),
     };
@@ -263,13 +262,13 @@ fn test_branch_analysis_and_cleanup_workflow() {
         fs::write(worktree_path.join("branch_file.txt"), format!("Content for {}", branch_name)).unwrap();
         
         Command::new("git")
-            .args(&["add", "."])
+            .args(["add", "."])
             .current_dir(&worktree_path)
             .output()
             .unwrap();
         
         Command::new("git")
-            .args(&["commit", "-m", &format!("Add content for {}", branch_name)])
+            .args(["commit", "-m", &format!("Add content for {}", branch_name)])
             .current_dir(&worktree_path)
             .output()
             .unwrap();
@@ -278,7 +277,7 @@ fn test_branch_analysis_and_cleanup_workflow() {
         if should_merge {
             std::env::set_current_dir(repo_path).unwrap();
             Command::new("git")
-                .args(&["merge", branch_name])
+                .args(["merge", branch_name])
                 .current_dir(repo_path)
                 .output()
                 .unwrap();
@@ -312,7 +311,7 @@ fn test_branch_analysis_and_cleanup_workflow() {
     
     // Clean up all worktrees
     for (_, worktree_path) in created_worktrees {
-        git_repo.remove_worktree(&worktree_path).unwrap();
+        git_repo.remove_worktree(&worktree_path, false).unwrap();
     }
     
     println!("Branch analysis and cleanup workflow test passed");
@@ -366,7 +365,7 @@ fn test_error_recovery_workflow() {
     
     // Test 3: Try to remove non-existent worktree
     let nonexistent_path = repo_path.join("nonexistent");
-    let result = git_repo.remove_worktree(&nonexistent_path);
+    let result = git_repo.remove_worktree(&nonexistent_path, false);
     
     // Should handle gracefully
     match result {
@@ -384,20 +383,20 @@ fn setup_test_repository() -> tempfile::TempDir {
     
     // Initialize git repo
     Command::new("git")
-        .args(&["init"])
+        .args(["init"])
         .current_dir(repo_path)
         .output()
         .unwrap();
     
     // Configure git
     Command::new("git")
-        .args(&["config", "user.email", "test@example.com"])
+        .args(["config", "user.email", "test@example.com"])
         .current_dir(repo_path)
         .output()
         .unwrap();
     
     Command::new("git")
-        .args(&["config", "user.name", "Test User"])
+        .args(["config", "user.name", "Test User"])
         .current_dir(repo_path)
         .output()
         .unwrap();
@@ -407,13 +406,13 @@ fn setup_test_repository() -> tempfile::TempDir {
     fs::write(repo_path.join(".gitignore"), "node_modules/\n*.log\nbuild/\n").unwrap();
     
     Command::new("git")
-        .args(&["add", "."])
+        .args(["add", "."])
         .current_dir(repo_path)
         .output()
         .unwrap();
     
     Command::new("git")
-        .args(&["commit", "-m", "Initial commit"])
+        .args(["commit", "-m", "Initial commit"])
         .current_dir(repo_path)
         .output()
         .unwrap();
@@ -456,13 +455,13 @@ fn setup_test_repository_with_dependencies() -> tempfile::TempDir {
     
     // Commit the changes
     Command::new("git")
-        .args(&["add", "."])
+        .args(["add", "."])
         .current_dir(repo_path)
         .output()
         .unwrap();
     
     Command::new("git")
-        .args(&["commit", "-m", "Add dependencies"])
+        .args(["commit", "-m", "Add dependencies"])
         .current_dir(repo_path)
         .output()
         .unwrap();
@@ -1,3 +1,4 @@
 // Integration tests combining multiple components
 pub mod full_workflow_tests;
-pub mod cow_git_integration_tests;
\ No newline at end of file
+pub mod cow_git_integration_tests;
+pub mod rewrite_integration_tests;
\ No newline at end of file
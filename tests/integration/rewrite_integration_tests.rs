@@ -0,0 +1,241 @@
+use git_warp::cow::{CloneSpec, CloneStrategy};
+use git_warp::git::GitRepository;
+use git_warp::journal::{self, Journal, JournalEntry};
+use git_warp::progress::NoProgress;
+use git_warp::rewrite::{PathRewriter, RewriteScope};
+use tempfile::tempdir;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_rewrite_scopes_in_git_repository() {
+    let old_root = "/old/checkout/location";
+    
+    for (scope, ignored, untracked, tracked) in [
+        (RewriteScope::IgnoredOnly, true, false, false),
+        (RewriteScope::Untracked, true, true, false),
+        (RewriteScope::All, true, true, true),
+    ] {
+        let temp_dir = setup_repository_with_paths(old_root);
+        let repo_path = temp_dir.path();
+        
+        PathRewriter::new(old_root, repo_path).with_scope(scope).rewrite_paths().unwrap();
+        
+        let rewritten = |file: &str| !fs::read_to_string(repo_path.join(file)).unwrap().contains(old_root);
+        assert_eq!(rewritten(".venv/pyvenv.cfg"), ignored, "{:?}", scope);
+        assert_eq!(rewritten("notes.txt"), untracked, "{:?}", scope);
+        assert_eq!(rewritten("config.toml"), tracked, "{:?}", scope);
+        assert_eq!(rewritten("src/paths.rs"), tracked, "{:?}", scope);
+        
+        if scope != RewriteScope::All {
+            assert_eq!(git_output(repo_path, &["status", "--porcelain", "--untracked-files=no"]), "");
+        }
+    }
+}
+
+#[test]
+fn test_tracked_file_matching_gitignore_is_left_alone() {
+    let old_root = "/old/checkout/location";
+    let temp_dir = setup_repository_with_paths(old_root);
+    let repo_path = temp_dir.path();
+    
+    // Force-added despite matching `*.log`
+    fs::write(repo_path.join("fixture.log"), format!("cwd={}", old_root)).unwrap();
+    git_output(repo_path, &["add", "--force", "fixture.log"]);
+    git_output(repo_path, &["commit", "-m", "Add fixture"]);
+    
+    PathRewriter::new(old_root, repo_path)
+        .with_scope(RewriteScope::IgnoredOnly)
+        .rewrite_paths()
+        .unwrap();
+    
+    assert!(fs::read_to_string(repo_path.join("fixture.log")).unwrap().contains(old_root));
+    assert!(!fs::read_to_string(repo_path.join(".venv/pyvenv.cfg")).unwrap().contains(old_root));
+}
+
+#[test]
+fn test_broken_repository_leaves_tracked_files_alone() {
+    let old_root = "/old/checkout/location";
+    
+    // A git dir that has gone missing, and an index git can't read
+    let missing_git_dir = setup_repository_with_paths(old_root);
+    let repo_path = missing_git_dir.path();
+    fs::rename(repo_path.join(".git"), repo_path.join("moved.git")).unwrap();
+    fs::write(repo_path.join(".git"), format!("gitdir: {}\n", repo_path.join("missing.git").display())).unwrap();
+    
+    let corrupt_index = setup_repository_with_paths(old_root);
+    fs::write(corrupt_index.path().join(".git/index"), "not an index").unwrap();
+    
+    for temp_dir in [&missing_git_dir, &corrupt_index] {
+        let repo_path = temp_dir.path();
+        let error = PathRewriter::new(old_root, repo_path).rewrite_paths().unwrap_err().to_string();
+        assert!(error.contains("tracked"), "{}", error);
+        
+        for file in ["config.toml", "src/paths.rs"] {
+            assert!(fs::read_to_string(repo_path.join(file)).unwrap().contains(old_root), "{}", file);
+        }
+    }
+}
+
+#[test]
+fn test_cloned_worktree_rewrites_artifacts_only() {
+    let temp_dir = tempdir().unwrap();
+    let repo_path = temp_dir.path().join("project");
+    fs::create_dir_all(&repo_path).unwrap();
+    init_repository(&repo_path);
+    
+    // Source that documents the checkout location, and a virtualenv that hardcodes it
+    let root = repo_path.display().to_string();
+    fs::write(repo_path.join(".gitignore"), ".venv/\n").unwrap();
+    fs::write(repo_path.join("README.md"), format!("Clone into {}", root)).unwrap();
+    git_output(&repo_path, &["add", "."]);
+    git_output(&repo_path, &["commit", "-m", "Initial commit"]);
+    fs::create_dir_all(repo_path.join(".venv/bin")).unwrap();
+    fs::write(repo_path.join(".venv/bin/activate"), format!("VIRTUAL_ENV=\"{}/.venv\"", root)).unwrap();
+    
+    std::env::set_current_dir(&repo_path).unwrap();
+    let git_repo = GitRepository::find().unwrap();
+    let main_worktree = git_repo.list_worktrees().unwrap().remove(0);
+    let worktree_path = temp_dir.path().join("worktrees").join("feature");
+    
+    let mut transaction = git_repo.begin_worktree_creation();
+    transaction
+        .clone_worktree_and_branch("feature", &worktree_path, None, &main_worktree, &CloneSpec::new(), CloneStrategy::Copy, &NoProgress)
+        .unwrap();
+    transaction.commit();
+    
+    PathRewriter::new(&repo_path, &worktree_path).rewrite_paths().unwrap();
+    
+    let activate = fs::read_to_string(worktree_path.join(".venv/bin/activate")).unwrap();
+    assert_eq!(activate, format!("VIRTUAL_ENV=\"{}/.venv\"", worktree_path.display()));
+    assert_eq!(fs::read_to_string(worktree_path.join("README.md")).unwrap(), format!("Clone into {}", root));
+    assert_eq!(git_output(&worktree_path, &["status", "--porcelain"]), "");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cloned_worktree_symlinks_stop_pointing_at_the_main_worktree() {
+    let temp_dir = tempdir().unwrap();
+    let repo_path = temp_dir.path().join("project");
+    fs::create_dir_all(&repo_path).unwrap();
+    init_repository(&repo_path);
+    
+    fs::write(repo_path.join(".gitignore"), "build/\n").unwrap();
+    fs::write(repo_path.join("README.md"), "# Project\n").unwrap();
+    git_output(&repo_path, &["add", "."]);
+    git_output(&repo_path, &["commit", "-m", "Initial commit"]);
+    fs::create_dir_all(repo_path.join("build/out")).unwrap();
+    fs::write(repo_path.join("build/out/app"), "binary").unwrap();
+    std::os::unix::fs::symlink(repo_path.join("build/out/app"), repo_path.join("build/current")).unwrap();
+    
+    std::env::set_current_dir(&repo_path).unwrap();
+    let git_repo = GitRepository::find().unwrap();
+    let main_worktree = git_repo.list_worktrees().unwrap().remove(0);
+    let worktree_path = temp_dir.path().join("worktrees").join("feature");
+    
+    let mut transaction = git_repo.begin_worktree_creation();
+    transaction
+        .clone_worktree_and_branch("feature", &worktree_path, None, &main_worktree, &CloneSpec::new(), CloneStrategy::Copy, &NoProgress)
+        .unwrap();
+    transaction.commit();
+    
+    // The clone copies the link verbatim, into the main worktree
+    assert_eq!(fs::read_link(worktree_path.join("build/current")).unwrap(), repo_path.join("build/out/app"));
+    
+    let report = PathRewriter::new(&repo_path, &worktree_path)
+        .rewrite_paths_with_progress(&NoProgress)
+        .unwrap();
+    
+    assert_eq!(fs::read_link(worktree_path.join("build/current")).unwrap(), worktree_path.join("build/out/app"));
+    assert!(report.dangling.is_empty());
+}
+
+#[test]
+fn test_rewrite_is_journaled_and_undone() {
+    let old_root = "/old/checkout/location";
+    let temp_dir = setup_repository_with_paths(old_root);
+    let repo_path = temp_dir.path();
+    let original_cfg = fs::read_to_string(repo_path.join(".venv/pyvenv.cfg")).unwrap();
+    let original_notes = fs::read_to_string(repo_path.join("notes.txt")).unwrap();
+    fs::write(repo_path.join("notes.txt"), format!("{}{}/docs\n", original_notes, old_root)).unwrap();
+    let original_notes = fs::read_to_string(repo_path.join("notes.txt")).unwrap();
+    
+    let report = PathRewriter::new(old_root, repo_path).rewrite_paths_with_progress(&NoProgress).unwrap();
+    let journal_path = report.journal.unwrap();
+    assert!(journal_path.starts_with(repo_path.join(".git")));
+    
+    let journal = Journal::load(repo_path).unwrap().unwrap();
+    let mut files = Vec::new();
+    for entry in &journal.entries {
+        let JournalEntry::File { path, replacements, original_hash, rewritten_hash } = entry else {
+            panic!("unexpected entry {:?}", entry);
+        };
+        files.push((path.display().to_string(), *replacements));
+        assert_eq!(*rewritten_hash, git_output(repo_path, &["hash-object", &path.display().to_string()]));
+        assert_ne!(original_hash, rewritten_hash);
+    }
+    assert_eq!(files, vec![(".venv/pyvenv.cfg".to_string(), 1), ("notes.txt".to_string(), 2)]);
+    
+    let undone = journal::undo(repo_path).unwrap();
+    assert_eq!(undone.restored.len(), 2);
+    assert!(undone.skipped.is_empty());
+    assert_eq!(fs::read_to_string(repo_path.join(".venv/pyvenv.cfg")).unwrap(), original_cfg);
+    assert_eq!(fs::read_to_string(repo_path.join("notes.txt")).unwrap(), original_notes);
+    
+    // Nothing is left to undo
+    assert!(!journal_path.exists());
+    assert!(Journal::load(repo_path).unwrap().is_none());
+}
+
+#[test]
+fn test_undo_skips_files_changed_since_the_rewrite() {
+    let old_root = "/old/checkout/location";
+    let temp_dir = setup_repository_with_paths(old_root);
+    let repo_path = temp_dir.path();
+    
+    PathRewriter::new(old_root, repo_path).rewrite_paths().unwrap();
+    fs::write(repo_path.join("notes.txt"), "edited by hand\n").unwrap();
+    
+    let undone = journal::undo(repo_path).unwrap();
+    assert_eq!(undone.restored, vec![Path::new(".venv/pyvenv.cfg")]);
+    assert_eq!(undone.skipped.len(), 1);
+    assert_eq!(undone.skipped[0].0, Path::new("notes.txt"));
+    assert_eq!(fs::read_to_string(repo_path.join("notes.txt")).unwrap(), "edited by hand\n");
+    
+    // The skipped file stays journaled
+    let journal = Journal::load(repo_path).unwrap().unwrap();
+    assert_eq!(journal.entries.iter().map(JournalEntry::path).collect::<Vec<_>>(), vec![Path::new("notes.txt")]);
+}
+
+/// A repository with `old_root` in tracked, untracked and ignored files
+fn setup_repository_with_paths(old_root: &str) -> tempfile::TempDir {
+    let temp_dir = tempdir().unwrap();
+    let repo_path = temp_dir.path();
+    init_repository(repo_path);
+    
+    fs::write(repo_path.join(".gitignore"), ".venv/\n*.log\n").unwrap();
+    fs::write(repo_path.join("config.toml"), format!("root = \"{}\"\n", old_root)).unwrap();
+    fs::create_dir_all(repo_path.join("src")).unwrap();
+    fs::write(repo_path.join("src/paths.rs"), format!("const ROOT: &str = \"{}\";\n", old_root)).unwrap();
+    git_output(repo_path, &["add", "."]);
+    git_output(repo_path, &["commit", "-m", "Initial commit"]);
+    
+    fs::create_dir_all(repo_path.join(".venv")).unwrap();
+    fs::write(repo_path.join(".venv/pyvenv.cfg"), format!("command = python -m venv {}/.venv\n", old_root)).unwrap();
+    fs::write(repo_path.join("notes.txt"), format!("checked out at {}\n", old_root)).unwrap();
+    
+    temp_dir
+}
+
+fn init_repository(repo_path: &Path) {
+    git_output(repo_path, &["init", "-q"]);
+    git_output(repo_path, &["config", "user.email", "test@example.com"]);
+    git_output(repo_path, &["config", "user.name", "Test User"]);
+}
+
+fn git_output(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git").args(args).current_dir(dir).output().unwrap();
+    assert!(output.status.success(), "git {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
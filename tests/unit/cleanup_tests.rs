@@ -0,0 +1,364 @@
+use git_warp::cleanup::{CleanupAction, CleanupOptions, CleanupPlan, CleanupPolicy};
+use git_warp::config::CleanupConfig;
+use git_warp::git::GitRepository;
+use git_warp::process::ProcessManager;
+use tempfile::tempdir;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn git(repo_path: &Path, args: &[&str]) {
+    let output = Command::new("git").args(args).current_dir(repo_path).output().unwrap();
+    assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+}
+
+/// A repository with merged, unmerged, dirty and locked worktrees next to it
+fn setup_cleanup_repo() -> (tempfile::TempDir, std::path::PathBuf) {
+    let temp_dir = tempdir().unwrap();
+    let repo_path = temp_dir.path().join("repo");
+    fs::create_dir_all(&repo_path).unwrap();
+    
+    git(&repo_path, &["init", "-q", "-b", "main"]);
+    git(&repo_path, &["config", "user.email", "test@example.com"]);
+    git(&repo_path, &["config", "user.name", "Test User"]);
+    fs::write(repo_path.join("README.md"), "# Test Repository").unwrap();
+    git(&repo_path, &["add", "."]);
+    git(&repo_path, &["commit", "-q", "-m", "Initial commit"]);
+    
+    for branch in ["merged", "unmerged", "dirty", "locked"] {
+        let path = temp_dir.path().join(branch);
+        git(&repo_path, &["worktree", "add", "-q", "-b", branch, path.to_str().unwrap()]);
+    }
+    
+    let unmerged = temp_dir.path().join("unmerged");
+    fs::write(unmerged.join("work.txt"), "work").unwrap();
+    git(&unmerged, &["add", "work.txt"]);
+    git(&unmerged, &["commit", "-q", "-m", "Unmerged work"]);
+    
+    fs::write(temp_dir.path().join("dirty").join("scratch.txt"), "scratch").unwrap();
+    git(&repo_path, &["worktree", "lock", "--reason", "agent", temp_dir.path().join("locked").to_str().unwrap()]);
+    
+    (temp_dir, repo_path)
+}
+
+fn build_plan(repo_path: &Path, options: &CleanupOptions) -> CleanupPlan {
+    std::env::set_current_dir(repo_path).unwrap();
+    let git_repo = GitRepository::find().unwrap();
+    let worktrees = git_repo.list_worktrees().unwrap();
+    CleanupPlan::build(&git_repo, &worktrees, options, &mut ProcessManager::new()).unwrap()
+}
+
+fn planned_branches(plan: &CleanupPlan) -> Vec<&str> {
+    let mut branches: Vec<_> = plan.entries.iter().map(|entry| entry.status.branch.as_str()).collect();
+    branches.sort();
+    branches
+}
+
+#[test]
+fn test_plan_for_merged_mode() {
+    let (_temp_dir, repo_path) = setup_cleanup_repo();
+    let options = CleanupOptions {
+        mode: "merged".to_string(),
+        policy: CleanupPolicy::builtin("merged").unwrap(),
+        ..Default::default()
+    };
+    
+    let plan = build_plan(&repo_path, &options);
+    assert_eq!(planned_branches(&plan), ["merged"]);
+    
+    let entry = &plan.entries[0];
+    assert_eq!(entry.reason, "merged (ancestor)");
+    assert_eq!(entry.actions, [CleanupAction::RemoveWorktree { force: false }, CleanupAction::DeleteBranch { force: false }]);
+    
+    // Dirty and locked worktrees are reported instead of silently dropped
+    let mut skipped: Vec<_> = plan.skipped.iter().map(|skip| (skip.branch.as_str(), skip.reason.as_str())).collect();
+    skipped.sort();
+    assert_eq!(skipped, [
+        ("dirty", "uncommitted changes; use --force"),
+        ("locked", "locked (agent); use --include-locked"),
+    ]);
+    assert!(plan.stale.is_empty());
+}
+
+#[test]
+fn test_plan_with_force_and_include_locked() {
+    let (_temp_dir, repo_path) = setup_cleanup_repo();
+    let options = CleanupOptions {
+        mode: "all".to_string(),
+        policy: CleanupPolicy::builtin("all").unwrap(),
+        force: true,
+        include_locked: true,
+        ..Default::default()
+    };
+    
+    let plan = build_plan(&repo_path, &options);
+    assert_eq!(planned_branches(&plan), ["dirty", "locked", "merged", "unmerged"]);
+    assert!(plan.skipped.is_empty());
+    
+    let locked = plan.entries.iter().find(|entry| entry.status.branch == "locked").unwrap();
+    assert_eq!(locked.actions[0], CleanupAction::Unlock);
+    
+    // --force also deletes unmerged branches
+    let unmerged = plan.entries.iter().find(|entry| entry.status.branch == "unmerged").unwrap();
+    assert_eq!(unmerged.reason, "no remote branch");
+    assert!(unmerged.actions.contains(&CleanupAction::DeleteBranch { force: true }));
+}
+
+#[test]
+fn test_plan_building_changes_nothing() {
+    let (temp_dir, repo_path) = setup_cleanup_repo();
+    fs::remove_dir_all(temp_dir.path().join("merged")).unwrap();
+    let options = CleanupOptions {
+        mode: "all".to_string(),
+        policy: CleanupPolicy::builtin("all").unwrap(),
+        force: true,
+        include_locked: true,
+        ..Default::default()
+    };
+    
+    let plan = build_plan(&repo_path, &options);
+    assert_eq!(plan.stale.len(), 1);
+    assert!(!plan.is_empty());
+    
+    // Building the plan again sees the same state
+    let again = build_plan(&repo_path, &options);
+    assert_eq!(again.stale.len(), 1);
+    assert_eq!(planned_branches(&again), planned_branches(&plan));
+    assert!(temp_dir.path().join("unmerged").exists());
+}
+
+#[test]
+fn test_execute_plan() {
+    let (temp_dir, repo_path) = setup_cleanup_repo();
+    let options = CleanupOptions {
+        mode: "all".to_string(),
+        policy: CleanupPolicy::builtin("all").unwrap(),
+        no_kill: true,
+        ..Default::default()
+    };
+    
+    let mut plan = build_plan(&repo_path, &options);
+    plan.retain_branches(&["merged".to_string(), "unmerged".to_string()]);
+    assert_eq!(planned_branches(&plan), ["merged", "unmerged"]);
+    
+    let git_repo = GitRepository::find().unwrap();
+    let outcome = plan.execute(&git_repo, &mut ProcessManager::new(), true).unwrap();
+    assert_eq!(outcome.removed, 2);
+    assert_eq!(outcome.failed, 0);
+    
+    assert!(!temp_dir.path().join("merged").exists());
+    assert!(!temp_dir.path().join("unmerged").exists());
+    assert!(!git_repo.branch_exists("merged").unwrap());
+    // Unmerged branches are only deleted with --force
+    assert!(git_repo.branch_exists("unmerged").unwrap());
+    assert!(temp_dir.path().join("dirty").exists());
+}
+
+#[test]
+fn test_worktree_dirtied_after_planning_survives_cleanup() {
+    let (temp_dir, repo_path) = setup_cleanup_repo();
+    let options = CleanupOptions {
+        mode: "merged".to_string(),
+        policy: CleanupPolicy::builtin("merged").unwrap(),
+        no_kill: true,
+        ..Default::default()
+    };
+    
+    let plan = build_plan(&repo_path, &options);
+    assert_eq!(planned_branches(&plan), ["merged"]);
+    
+    // Work started between building the plan and confirming it
+    let merged = temp_dir.path().join("merged");
+    fs::write(merged.join("wip.txt"), "not committed yet").unwrap();
+    
+    let git_repo = GitRepository::find().unwrap();
+    let outcome = plan.execute(&git_repo, &mut ProcessManager::new(), true).unwrap();
+    assert_eq!(outcome.removed, 0);
+    assert_eq!(outcome.failed, 1);
+    assert_eq!(fs::read_to_string(merged.join("wip.txt")).unwrap(), "not committed yet");
+    assert!(git_repo.branch_exists("merged").unwrap());
+}
+
+#[test]
+fn test_execute_plan_survives_a_vanished_worktree() {
+    let (temp_dir, repo_path) = setup_cleanup_repo();
+    let options = CleanupOptions {
+        mode: "all".to_string(),
+        policy: CleanupPolicy::builtin("all").unwrap(),
+        ..Default::default()
+    };
+    
+    let mut plan = build_plan(&repo_path, &options);
+    plan.retain_branches(&["merged".to_string(), "unmerged".to_string()]);
+    for entry in &mut plan.entries {
+        entry.actions.insert(0, CleanupAction::KillProcesses { count: 1 });
+    }
+    
+    // Gone between planning and confirming
+    fs::remove_dir_all(temp_dir.path().join("merged")).unwrap();
+    
+    let git_repo = GitRepository::find().unwrap();
+    let outcome = plan.execute(&git_repo, &mut ProcessManager::new(), true).unwrap();
+    assert_eq!(outcome.removed, 1);
+    assert_eq!(outcome.failed, 1);
+    assert!(!temp_dir.path().join("unmerged").exists());
+}
+
+#[test]
+fn test_execute_plan_deletes_squash_merged_branches() {
+    let (temp_dir, repo_path) = setup_cleanup_repo();
+    
+    // Land the unmerged work as a squash commit, which `git branch -d` doesn't recognise
+    git(&repo_path, &["merge", "-q", "--squash", "unmerged"]);
+    git(&repo_path, &["commit", "-q", "-m", "Squash unmerged"]);
+    
+    let options = CleanupOptions {
+        mode: "merged".to_string(),
+        policy: CleanupPolicy::builtin("merged").unwrap(),
+        no_kill: true,
+        ..Default::default()
+    };
+    let plan = build_plan(&repo_path, &options);
+    let entry = plan.entries.iter().find(|entry| entry.status.branch == "unmerged").unwrap();
+    assert_eq!(entry.reason, "merged (squash)");
+    assert!(entry.actions.contains(&CleanupAction::DeleteBranch { force: true }));
+    
+    let git_repo = GitRepository::find().unwrap();
+    let outcome = plan.execute(&git_repo, &mut ProcessManager::new(), true).unwrap();
+    assert_eq!(outcome.failed, 0);
+    assert!(!temp_dir.path().join("unmerged").exists());
+    assert!(!git_repo.branch_exists("unmerged").unwrap());
+}
+
+#[test]
+fn test_failed_branch_delete_counts_as_failure() {
+    let (temp_dir, repo_path) = setup_cleanup_repo();
+    let options = CleanupOptions {
+        mode: "all".to_string(),
+        policy: CleanupPolicy::builtin("all").unwrap(),
+        no_kill: true,
+        ..Default::default()
+    };
+    
+    let mut plan = build_plan(&repo_path, &options);
+    plan.retain_branches(&["unmerged".to_string()]);
+    plan.entries[0].actions.push(CleanupAction::DeleteBranch { force: false });
+    
+    let git_repo = GitRepository::find().unwrap();
+    let outcome = plan.execute(&git_repo, &mut ProcessManager::new(), true).unwrap();
+    assert_eq!(outcome.removed, 1);
+    assert_eq!(outcome.failed, 1);
+    assert!(!temp_dir.path().join("unmerged").exists());
+    assert!(git_repo.branch_exists("unmerged").unwrap());
+}
+
+#[test]
+fn test_stale_policy() {
+    let (temp_dir, repo_path) = setup_cleanup_repo();
+    
+    // Backdate one branch's last commit and every file in its worktree
+    let old = temp_dir.path().join("unmerged");
+    let sixty_days_ago = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() - 60 * 24 * 60 * 60;
+    let date = format!("@{} +0000", sixty_days_ago);
+    let output = Command::new("git")
+        .args(["commit", "-q", "--amend", "--no-edit", "--date", &date])
+        .env("GIT_COMMITTER_DATE", &date)
+        .current_dir(&old)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    for file in ["README.md", "work.txt"] {
+        let status = Command::new("touch")
+            .args(["-d", "20 days ago"])
+            .arg(old.join(file))
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+    
+    let options = CleanupOptions {
+        mode: "stale".to_string(),
+        policy: CleanupPolicy::builtin("stale").unwrap(),
+        no_kill: true,
+        ..Default::default()
+    };
+    let plan = build_plan(&repo_path, &options);
+    assert_eq!(planned_branches(&plan), ["unmerged"]);
+    assert_eq!(plan.entries[0].reason, "last commit 60 days ago, no changes for 20 days");
+}
+
+#[test]
+fn test_composed_policy() {
+    let (_temp_dir, repo_path) = setup_cleanup_repo();
+    
+    // Not merged, or merged and identical: everything but the dirty one
+    let policy = CleanupPolicy {
+        larger_than_gb: Some(0.0),
+        any: vec![
+            CleanupPolicy {
+                merged: Some(false),
+                ..Default::default()
+            },
+            CleanupPolicy {
+                identical: Some(true),
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+    let options = CleanupOptions {
+        mode: "custom".to_string(),
+        policy,
+        no_kill: true,
+        ..Default::default()
+    };
+    
+    let plan = build_plan(&repo_path, &options);
+    assert_eq!(planned_branches(&plan), ["merged", "unmerged"]);
+    let unmerged = plan.entries.iter().find(|entry| entry.status.branch == "unmerged").unwrap();
+    assert!(unmerged.reason.starts_with("uses 0.0 GB, not merged"), "{}", unmerged.reason);
+    
+    // Nothing is over a gigabyte
+    let options = CleanupOptions {
+        policy: CleanupPolicy {
+            larger_than_gb: Some(1.0),
+            ..Default::default()
+        },
+        ..options
+    };
+    assert!(build_plan(&repo_path, &options).entries.is_empty());
+}
+
+#[test]
+fn test_policy_lookup() {
+    let config: CleanupConfig = toml::from_str(r#"
+        [policies.recent]
+        older_than_days = 7
+        
+        [policies.abandoned]
+        inactive_days = 30
+        any = [{ merged = true }, { upstream_gone = true }]
+    "#).unwrap();
+    
+    assert!(config.validate().is_ok());
+    assert_eq!(config.policy("recent").unwrap().older_than_days, Some(7));
+    assert_eq!(config.policy("recent").unwrap().inactive_days, None);
+    assert_eq!(config.policy("stale").unwrap().older_than_days, Some(30));
+    
+    let abandoned = config.policy("abandoned").unwrap();
+    assert_eq!(abandoned.inactive_days, Some(30));
+    assert_eq!(abandoned.any.len(), 2);
+    assert_eq!(abandoned.any[1].upstream_gone, Some(true));
+    
+    assert_eq!(config.policy("merged").unwrap().merged, Some(true));
+    assert_eq!(config.policy("interactive").unwrap(), CleanupPolicy::default());
+    
+    let error = config.policy("bogus").unwrap_err().to_string();
+    assert!(error.contains("abandoned"), "{}", error);
+    
+    // Typos in criteria are rejected rather than silently matching everything
+    assert!(toml::from_str::<CleanupConfig>("[policies.typo]\nolder_than = 3").is_err());
+}
@@ -1,4 +1,6 @@
-use git_warp::config::{Config, ConfigManager, GitConfig, ProcessConfig, TerminalConfig, AgentConfig};
+use git_warp::cleanup::CleanupPolicy;
+use git_warp::rewrite::RewriteScope;
+use git_warp::config::{Config, ConfigManager, GitConfig, ProcessConfig, TerminalConfig, AgentConfig, CowConfig, CleanupConfig, PROJECT_CONFIG_FILE};
 use tempfile::tempdir;
 use std::fs;
 
@@ -8,7 +10,8 @@ fn test_config_defaults() {
     assert_eq!(config.terminal_mode, "tab");
     assert!(config.use_cow);
     assert!(!config.auto_confirm);
-    assert_eq!(config.git.default_branch, "main");
+    assert_eq!(config.git.default_branch, "");
+    assert!(config.git.compare_remote);
     assert!(config.git.auto_fetch);
     assert!(config.git.auto_prune);
     assert!(config.process.check_processes);
@@ -32,10 +35,12 @@ fn test_config_with_custom_values() {
     let config = Config {
         terminal_mode: "window".to_string(),
         worktrees_path: Some("/custom/path".into()),
+        worktree_template: "{worktrees_path}/{repo}/{branch_slug}".to_string(),
         use_cow: false,
         auto_confirm: true,
         git: GitConfig {
             default_branch: "develop".to_string(),
+            compare_remote: false,
             auto_fetch: false,
             auto_prune: false,
         },
@@ -55,6 +60,22 @@ fn test_config_with_custom_values() {
             max_activities: 50,
             claude_hooks: false,
         },
+        cow: CowConfig {
+            include: Vec::new(),
+            exclude: vec!["*.log".to_string()],
+            hardlink: vec!["vendor".to_string()],
+            rewrite_scope: RewriteScope::IgnoredOnly,
+        },
+        cleanup: CleanupConfig {
+            policies: [(
+                "old".to_string(),
+                CleanupPolicy {
+                    older_than_days: Some(60),
+                    ..Default::default()
+                },
+            )]
+            .into(),
+        },
     };
 
     let toml_str = toml::to_string(&config).unwrap();
@@ -63,8 +84,14 @@ fn test_config_with_custom_values() {
     assert_eq!(config.terminal_mode, parsed.terminal_mode);
     assert_eq!(config.use_cow, parsed.use_cow);
     assert_eq!(config.git.default_branch, parsed.git.default_branch);
+    assert!(!parsed.git.compare_remote);
     assert!(!parsed.git.auto_fetch);
     assert!(parsed.process.auto_kill);
+    assert_eq!(parsed.worktree_template, "{worktrees_path}/{repo}/{branch_slug}");
+    assert_eq!(parsed.cow.exclude, vec!["*.log"]);
+    assert_eq!(parsed.cow.hardlink, vec!["vendor"]);
+    assert_eq!(parsed.cow.rewrite_scope, RewriteScope::IgnoredOnly);
+    assert_eq!(parsed.cleanup.policies["old"].older_than_days, Some(60));
 }
 
 #[test]
@@ -87,9 +114,11 @@ fn test_config_save_and_load() {
     let temp_dir = tempdir().unwrap();
     let config_path = temp_dir.path().join("config.toml");
     
-    let mut config = Config::default();
-    config.terminal_mode = "window".to_string();
-    config.use_cow = false;
+    let config = Config {
+        terminal_mode: "window".to_string(),
+        use_cow: false,
+        ..Default::default()
+    };
     
     let manager = ConfigManager {
         config: config.clone(),
@@ -179,4 +208,125 @@ auto_fetch = true
     let config: Config = toml::from_str(toml_str).unwrap();
     assert_eq!(config.terminal_mode, "invalid_mode"); // Should preserve invalid values
     assert_eq!(config.git.default_branch, ""); // Should preserve empty strings
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_project_config_overrides_global() {
+    let temp_dir = tempdir().unwrap();
+    let config_path = temp_dir.path().join("config.toml");
+    let repo_root = temp_dir.path().join("repo");
+    fs::create_dir_all(&repo_root).unwrap();
+    
+    fs::write(&config_path, r#"
+use_cow = false
+
+[cow]
+exclude = ["*.log"]
+"#).unwrap();
+    fs::write(repo_root.join(PROJECT_CONFIG_FILE), r#"
+[cow]
+exclude = ["target/debug/incremental", "*.log"]
+hardlink = ["vendor"]
+"#).unwrap();
+    
+    let global = ConfigManager::from_paths(config_path.clone(), None).unwrap();
+    assert_eq!(global.get().cow.exclude, vec!["*.log"]);
+    
+    let project = ConfigManager::from_paths(config_path.clone(), Some(&repo_root)).unwrap();
+    assert_eq!(project.get().cow.exclude, vec!["target/debug/incremental", "*.log"]);
+    assert!(!project.get().use_cow); // Untouched settings still come from the global file
+    assert_eq!(project.config_path(), &config_path);
+    
+    let spec = project.get().cow.clone_spec();
+    assert_eq!(spec.exclude.len(), 2);
+    assert!(spec.include.is_empty());
+    assert_eq!(spec.hardlink, vec!["vendor"]);
+}
+
+#[test]
+fn test_project_config_from_linked_worktree() {
+    use git_warp::git::GitRepository;
+    use std::process::Command;
+    
+    let temp_dir = tempdir().unwrap();
+    let config_path = temp_dir.path().join("config.toml");
+    let repo_root = temp_dir.path().join("repo");
+    let worktree_path = temp_dir.path().join("feature");
+    fs::create_dir_all(&repo_root).unwrap();
+    let git = |dir: &std::path::Path, args: &[&str]| {
+        let output = Command::new("git").args(args).current_dir(dir).output().unwrap();
+        assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    };
+    git(&repo_root, &["init", "-q"]);
+    git(&repo_root, &["config", "user.email", "test@example.com"]);
+    git(&repo_root, &["config", "user.name", "Test User"]);
+    fs::write(repo_root.join("README.md"), "# Test Repository").unwrap();
+    git(&repo_root, &["add", "."]);
+    git(&repo_root, &["commit", "-q", "-m", "Initial commit"]);
+    git(&repo_root, &["worktree", "add", "-q", "-b", "feature", worktree_path.to_str().unwrap()]);
+    
+    // The branch carries a stale copy of the file; the main worktree's is the one in effect
+    fs::write(repo_root.join(PROJECT_CONFIG_FILE), "[cow]\nhardlink = [\"vendor\"]\n").unwrap();
+    fs::write(worktree_path.join(PROJECT_CONFIG_FILE), "[cow]\nhardlink = [\"stale\"]\n").unwrap();
+    
+    std::env::set_current_dir(&worktree_path).unwrap();
+    let from_worktree = GitRepository::find().unwrap();
+    let main_root = from_worktree.main_root_path();
+    assert_eq!(main_root.canonicalize().unwrap(), repo_root.canonicalize().unwrap());
+    let project = ConfigManager::from_paths(config_path.clone(), Some(&main_root)).unwrap();
+    assert_eq!(project.get().cow.hardlink, vec!["vendor"]);
+    
+    std::env::set_current_dir(&repo_root).unwrap();
+    let from_main = GitRepository::find().unwrap();
+    assert_eq!(from_main.main_root_path().canonicalize().unwrap(), repo_root.canonicalize().unwrap());
+}
+
+#[test]
+fn test_config_rejects_redefined_builtin_policy() {
+    let temp_dir = tempdir().unwrap();
+    let config_path = temp_dir.path().join("config.toml");
+    
+    fs::write(&config_path, "[cleanup.policies.merged]\nolder_than_days = 1\n").unwrap();
+    let error = ConfigManager::from_paths(config_path.clone(), None).err().unwrap().to_string();
+    assert!(error.contains("'merged' is built-in"), "{}", error);
+    
+    fs::write(&config_path, "[cleanup.policies.abandoned]\nolder_than_days = 90\n").unwrap();
+    let manager = ConfigManager::from_paths(config_path, None).unwrap();
+    assert_eq!(manager.get().cleanup.policy("abandoned").unwrap().older_than_days, Some(90));
+}
+
+#[test]
+fn test_project_config_limited_to_cow_settings() {
+    let temp_dir = tempdir().unwrap();
+    let config_path = temp_dir.path().join("config.toml");
+    let repo_root = temp_dir.path().join("repo");
+    fs::create_dir_all(&repo_root).unwrap();
+    
+    fs::write(&config_path, "[git]\ndefault_branch = \"main\"\n").unwrap();
+    // A cloned repository must not be able to widen cleanup or move worktrees
+    fs::write(repo_root.join(PROJECT_CONFIG_FILE), r#"
+worktree_template = "/tmp/{branch}"
+auto_confirm = true
+
+[git]
+default_branch = "attacker"
+compare_remote = false
+
+[cow]
+exclude = ["*.log"]
+rewrite_scope = "all"
+
+[cleanup.policies.everything]
+older_than_days = 0
+"#).unwrap();
+    
+    let project = ConfigManager::from_paths(config_path, Some(&repo_root)).unwrap();
+    let config = project.get();
+    assert_eq!(config.cow.exclude, vec!["*.log"]);
+    assert_eq!(config.cow.rewrite_scope, RewriteScope::All);
+    assert_eq!(config.git.default_branch, "main");
+    assert!(config.git.compare_remote);
+    assert!(!config.auto_confirm);
+    assert_eq!(config.worktree_template, Config::default().worktree_template);
+    assert!(config.cleanup.policies.is_empty());
+}
@@ -1,4 +1,4 @@
-use git_warp::cow::{clone_directory, is_cow_supported};
+use git_warp::cow::{clone_directory, clone_directory_with, is_cow_supported, CloneSpec, CloneStrategy};
 use tempfile::tempdir;
 use std::fs;
 use std::path::Path;
@@ -12,7 +12,7 @@ fn test_cow_support_detection() {
     // On other systems, it should return Ok(false) or Err
     match result {
         Ok(supported) => {
-            println!("CoW supported: {}", supported);
+            println!("CoW supported: {:?}", supported);
             // On macOS CI, this might be true
         },
         Err(e) => {
@@ -33,7 +33,7 @@ fn test_cow_clone_nonexistent_source() {
     let temp_dir = tempdir().unwrap();
     let dest = temp_dir.path().join("dest");
     
-    let result = clone_directory("/nonexistent/source", &dest);
+    let result = clone_directory("/nonexistent/source", &dest, &CloneSpec::new());
     assert!(result.is_err());
 }
 
@@ -53,10 +53,10 @@ fn test_cow_clone_basic_functionality() {
     fs::write(sub_dir.join("nested.txt"), "Nested content").unwrap();
     
     // Test CoW clone
-    let result = clone_directory(&src_dir, &dest_dir);
+    let result = clone_directory(&src_dir, &dest_dir, &CloneSpec::new());
     
     match result {
-        Ok(()) => {
+        Ok(_report) => {
             // Verify destination was created
             assert!(dest_dir.exists());
             
@@ -111,10 +111,10 @@ fn test_cow_clone_large_structure() {
     fs::write(build_dir.join("app.js"), "// Built application").unwrap();
     
     // Test CoW clone
-    let result = clone_directory(&src_dir, &dest_dir);
+    let result = clone_directory(&src_dir, &dest_dir, &CloneSpec::new());
     
     match result {
-        Ok(()) => {
+        Ok(_report) => {
             // Count files in both directories
             let src_count = count_files(&src_dir);
             let dest_count = count_files(&dest_dir);
@@ -152,10 +152,10 @@ fn test_cow_clone_preserves_permissions() {
         fs::set_permissions(src_dir.join("script.sh"), perms).unwrap();
     }
     
-    let result = clone_directory(&src_dir, &dest_dir);
+    let result = clone_directory(&src_dir, &dest_dir, &CloneSpec::new());
     
     match result {
-        Ok(()) => {
+        Ok(_report) => {
             // Verify file exists
             assert!(dest_dir.join("script.sh").exists());
             
@@ -191,10 +191,10 @@ fn test_cow_clone_overwrites_destination() {
     fs::write(dest_dir.join("file.txt"), "destination content").unwrap();
     fs::write(dest_dir.join("extra.txt"), "extra file").unwrap();
     
-    let result = clone_directory(&src_dir, &dest_dir);
+    let result = clone_directory(&src_dir, &dest_dir, &CloneSpec::new());
     
     match result {
-        Ok(()) => {
+        Ok(_report) => {
             // Verify source content overwrote destination
             let content = fs::read_to_string(dest_dir.join("file.txt")).unwrap();
             assert_eq!(content, "source content");
@@ -216,8 +216,8 @@ fn test_apfs_specific_features() {
     // Test APFS support indirectly through CoW support detection
     let result = is_cow_supported(".");
     match result {
-        Ok(is_supported) => {
-            println!("CoW support (likely APFS): {}", is_supported);
+        Ok(capabilities) => {
+            println!("CoW support (likely APFS): {}", capabilities.is_cow());
             // This should typically be true on modern macOS with APFS
         },
         Err(e) => {
@@ -227,6 +227,116 @@ fn test_apfs_specific_features() {
     }
 }
 
+#[test]
+fn test_capability_report() {
+    let temp_dir = tempdir().unwrap();
+    let capabilities = is_cow_supported(temp_dir.path()).unwrap();
+    
+    assert!(!capabilities.filesystem.is_empty());
+    assert_eq!(capabilities.is_cow(), capabilities.best_strategy().is_cow());
+    if !capabilities.is_cow() && capabilities.hardlink {
+        assert_eq!(capabilities.best_strategy(), CloneStrategy::Hardlink);
+    }
+}
+
+#[test]
+fn test_hardlink_strategy_shares_dependency_trees() {
+    let temp_dir = tempdir().unwrap();
+    let src_dir = temp_dir.path().join("source");
+    let dest_dir = temp_dir.path().join("destination");
+    
+    fs::create_dir_all(src_dir.join("node_modules").join("lodash")).unwrap();
+    fs::write(src_dir.join("node_modules").join("lodash").join("index.js"), "module.exports = {};").unwrap();
+    fs::create_dir_all(src_dir.join("src")).unwrap();
+    fs::write(src_dir.join("src").join("main.js"), "console.log('hi');").unwrap();
+    fs::create_dir_all(src_dir.join("vendor")).unwrap();
+    fs::write(src_dir.join("vendor").join("lib.go"), "package lib").unwrap();
+    let modified = backdate(&src_dir.join("src").join("main.js"));
+    
+    let report = clone_directory_with(&src_dir, &dest_dir, &CloneSpec::new(), CloneStrategy::Hardlink).unwrap();
+    
+    assert_eq!(report.linked, 1);
+    assert_eq!(report.copied, 2);
+    assert_eq!(report.cloned, 0);
+    assert_eq!(fs::read_to_string(dest_dir.join("src").join("main.js")).unwrap(), "console.log('hi');");
+    // Copied files keep their mtime, or build tools would rebuild them
+    assert_eq!(fs::metadata(dest_dir.join("src").join("main.js")).unwrap().modified().unwrap(), modified);
+    
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let inode = |path: &Path| fs::metadata(path).unwrap().ino();
+        
+        let dependency = Path::new("node_modules").join("lodash").join("index.js");
+        assert_eq!(inode(&src_dir.join(&dependency)), inode(&dest_dir.join(&dependency)));
+        
+        let source = Path::new("src").join("main.js");
+        assert_ne!(inode(&src_dir.join(&source)), inode(&dest_dir.join(&source)));
+        
+        // Vendored code is usually tracked and edited, so it isn't shared
+        let vendored = Path::new("vendor").join("lib.go");
+        assert_ne!(inode(&src_dir.join(&vendored)), inode(&dest_dir.join(&vendored)));
+    }
+}
+
+#[test]
+fn test_hardlink_strategy_shares_opted_in_trees() {
+    let temp_dir = tempdir().unwrap();
+    let src_dir = temp_dir.path().join("source");
+    let dest_dir = temp_dir.path().join("destination");
+    
+    fs::create_dir_all(src_dir.join("vendor").join("lib")).unwrap();
+    fs::write(src_dir.join("vendor").join("lib").join("lib.go"), "package lib").unwrap();
+    fs::write(src_dir.join("main.go"), "package main").unwrap();
+    
+    let spec = CloneSpec::new().hardlink("vendor");
+    let report = clone_directory_with(&src_dir, &dest_dir, &spec, CloneStrategy::Hardlink).unwrap();
+    
+    assert_eq!(report.linked, 1);
+    assert_eq!(report.copied, 1);
+    
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let inode = |path: &Path| fs::metadata(path).unwrap().ino();
+        
+        let vendored = Path::new("vendor").join("lib").join("lib.go");
+        assert_eq!(inode(&src_dir.join(&vendored)), inode(&dest_dir.join(&vendored)));
+        assert_ne!(inode(&src_dir.join("main.go")), inode(&dest_dir.join("main.go")));
+    }
+}
+
+#[test]
+fn test_copy_strategy_respects_spec() {
+    let temp_dir = tempdir().unwrap();
+    let src_dir = temp_dir.path().join("source");
+    let dest_dir = temp_dir.path().join("destination");
+    
+    fs::create_dir_all(src_dir.join(".git")).unwrap();
+    fs::write(src_dir.join(".git").join("HEAD"), "ref: refs/heads/main").unwrap();
+    fs::write(src_dir.join("app.js"), "// app").unwrap();
+    fs::write(src_dir.join("debug.log"), "noise").unwrap();
+    let modified = backdate(&src_dir.join("app.js"));
+    
+    let spec = CloneSpec::new().exclude("*.log");
+    let report = clone_directory_with(&src_dir, &dest_dir, &spec, CloneStrategy::Copy).unwrap();
+    
+    assert_eq!(report.copied, 1);
+    assert_eq!(report.excluded.len(), 2);
+    assert!(dest_dir.join("app.js").exists());
+    assert_eq!(fs::metadata(dest_dir.join("app.js")).unwrap().modified().unwrap(), modified);
+    assert!(!dest_dir.join(".git").exists());
+    assert!(!dest_dir.join("debug.log").exists());
+}
+
+/// Set a fixed past mtime on `path`, so a preserved one can't match by accident
+fn backdate(path: &Path) -> std::time::SystemTime {
+    let time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_600_000_000);
+    let times = fs::FileTimes::new().set_modified(time).set_accessed(time);
+    fs::File::options().write(true).open(path).unwrap().set_times(times).unwrap();
+    time
+}
+
 // Helper function to count files recursively
 fn count_files<P: AsRef<Path>>(dir: P) -> usize {
     let mut count = 0;
@@ -1,4 +1,4 @@
-use git_warp::git::{GitRepository, BranchStatus};
+use git_warp::git::{CreationStep, GitRepository, MergeMethod};
 use tempfile::tempdir;
 use std::fs;
 use std::process::Command;
@@ -9,20 +9,20 @@ fn setup_test_repo() -> tempfile::TempDir {
     
     // Initialize git repo
     Command::new("git")
-        .args(&["init"])
+        .args(["init"])
         .current_dir(repo_path)
         .output()
         .unwrap();
     
     // Configure git
     Command::new("git")
-        .args(&["config", "user.email", "test@example.com"])
+        .args(["config", "user.email", "test@example.com"])
         .current_dir(repo_path)
         .output()
         .unwrap();
     
     Command::new("git")
-        .args(&["config", "user.name", "Test User"])
+        .args(["config", "user.name", "Test User"])
         .current_dir(repo_path)
         .output()
         .unwrap();
@@ -31,13 +31,13 @@ fn setup_test_repo() -> tempfile::TempDir {
     fs::write(repo_path.join("README.md"), "# Test Repository").unwrap();
     
     Command::new("git")
-        .args(&["add", "."])
+        .args(["add", "."])
         .current_dir(repo_path)
         .output()
         .unwrap();
     
     Command::new("git")
-        .args(&["commit", "-m", "Initial commit"])
+        .args(["commit", "-m", "Initial commit"])
         .current_dir(repo_path)
         .output()
         .unwrap();
@@ -84,6 +84,63 @@ fn test_list_worktrees_single() {
     assert_eq!(worktrees.len(), 1); // Only main worktree
     assert_eq!(worktrees[0].branch, "main");
     assert_eq!(worktrees[0].path, repo_path);
+    assert!(worktrees[0].is_main);
+    assert!(!worktrees[0].is_bare);
+    assert!(!worktrees[0].is_detached);
+    
+    #[allow(deprecated)]
+    let is_primary = worktrees[0].is_primary();
+    assert!(is_primary);
+}
+
+#[test]
+fn test_list_worktrees_state() {
+    let temp_dir = setup_test_repo();
+    let repo_path = temp_dir.path();
+    let locked_path = repo_path.join("worktrees").join("locked");
+    let detached_path = repo_path.join("worktrees").join("detached");
+    let gone_path = repo_path.join("worktrees").join("gone");
+    
+    std::env::set_current_dir(repo_path).unwrap();
+    let git_repo = GitRepository::find().unwrap();
+    
+    git_repo.create_worktree_and_branch("locked", &locked_path, None).unwrap();
+    git_repo.create_worktree_and_branch("gone", &gone_path, None).unwrap();
+    for args in [
+        vec!["worktree", "lock", "--reason", "on a USB drive", locked_path.to_str().unwrap()],
+        vec!["worktree", "add", "--detach", detached_path.to_str().unwrap()],
+    ] {
+        let output = Command::new("git").args(&args).current_dir(repo_path).output().unwrap();
+        assert!(output.status.success());
+    }
+    fs::remove_dir_all(&gone_path).unwrap();
+    
+    let worktrees = git_repo.list_worktrees().unwrap();
+    assert_eq!(worktrees.len(), 4);
+    assert_eq!(worktrees.iter().filter(|w| w.is_main).count(), 1);
+    assert_eq!(worktrees[0].path, repo_path);
+    
+    let locked = worktrees.iter().find(|w| w.path == locked_path).unwrap();
+    assert_eq!(locked.branch, "locked");
+    assert_eq!(locked.locked.as_deref(), Some("on a USB drive"));
+    assert!(locked.prunable.is_none());
+    
+    let detached = worktrees.iter().find(|w| w.path == detached_path).unwrap();
+    assert!(detached.is_detached);
+    assert!(detached.branch.is_empty());
+    assert_eq!(detached.head, worktrees[0].head);
+    
+    let gone = worktrees.iter().find(|w| w.path == gone_path).unwrap();
+    assert!(gone.prunable.is_some());
+    assert!(gone.locked.is_none());
+    
+    // Detached and stale worktrees are never analyzed for cleanup, locked ones only on request
+    let statuses = git_repo.analyze_branches_for_cleanup(&worktrees).unwrap();
+    assert!(statuses.is_empty());
+    
+    let statuses = git_repo.analyze_branches_for_cleanup_with(&worktrees, true).unwrap();
+    assert_eq!(statuses.len(), 1);
+    assert_eq!(statuses[0].locked.as_deref(), Some("on a USB drive"));
 }
 
 #[test]
@@ -127,7 +184,7 @@ fn test_remove_worktree() {
     assert!(worktree_path.exists());
     
     // Remove it
-    let result = git_repo.remove_worktree(&worktree_path);
+    let result = git_repo.remove_worktree(&worktree_path, false);
     assert!(result.is_ok());
     
     // Verify it's gone
@@ -139,6 +196,246 @@ fn test_remove_worktree() {
     assert!(temp_worktree.is_none());
 }
 
+#[test]
+fn test_remove_worktree_with_untracked_files() {
+    let temp_dir = setup_test_repo();
+    let repo_path = temp_dir.path();
+    let worktree_path = repo_path.join("worktrees").join("scratch");
+    
+    std::env::set_current_dir(repo_path).unwrap();
+    let git_repo = GitRepository::find().unwrap();
+    git_repo.create_worktree_and_branch("scratch", &worktree_path, None).unwrap();
+    fs::write(worktree_path.join("notes.txt"), "untracked").unwrap();
+    
+    // Untracked work is only thrown away when forced
+    assert!(git_repo.remove_worktree(&worktree_path, false).is_err());
+    assert!(worktree_path.join("notes.txt").exists());
+    
+    git_repo.remove_worktree(&worktree_path, true).unwrap();
+    assert!(!worktree_path.exists());
+}
+
+#[test]
+fn test_worktree_transaction_rollback_removes_new_branch() {
+    let temp_dir = setup_test_repo();
+    let repo_path = temp_dir.path();
+    let worktree_path = repo_path.join("worktrees").join("doomed");
+    
+    std::env::set_current_dir(repo_path).unwrap();
+    let git_repo = GitRepository::find().unwrap();
+    let mut transaction = git_repo.begin_worktree_creation();
+    transaction.create_worktree_and_branch("doomed", &worktree_path, None).unwrap();
+    
+    assert_eq!(transaction.steps(), &[
+        CreationStep::CreatedBranch("doomed".to_string()),
+        CreationStep::CreatedDirectory(worktree_path.clone()),
+        CreationStep::RegisteredWorktree(worktree_path.clone()),
+    ]);
+    
+    // Simulate a clone that failed after wiping the checkout
+    fs::remove_dir_all(&worktree_path).unwrap();
+    
+    let report = transaction.rollback();
+    assert!(report.is_clean(), "failed steps: {:?}", report.failed);
+    assert_eq!(report.undone.len(), 3);
+    
+    assert!(!worktree_path.exists());
+    assert!(!git_repo.branch_exists("doomed").unwrap());
+    assert_eq!(git_repo.list_worktrees().unwrap().len(), 1);
+}
+
+#[test]
+fn test_worktree_transaction_keeps_existing_branch() {
+    let temp_dir = setup_test_repo();
+    let repo_path = temp_dir.path();
+    let worktree_path = repo_path.join("worktrees").join("existing");
+    
+    Command::new("git")
+        .args(["branch", "existing"])
+        .current_dir(repo_path)
+        .output()
+        .unwrap();
+    
+    std::env::set_current_dir(repo_path).unwrap();
+    let git_repo = GitRepository::find().unwrap();
+    let mut transaction = git_repo.begin_worktree_creation();
+    transaction.create_worktree_and_branch("existing", &worktree_path, None).unwrap();
+    assert!(!transaction.steps().contains(&CreationStep::CreatedBranch("existing".to_string())));
+    
+    let report = transaction.rollback();
+    assert!(report.is_clean());
+    
+    assert!(!worktree_path.exists());
+    assert!(git_repo.branch_exists("existing").unwrap());
+}
+
+#[test]
+fn test_worktree_transaction_drop_and_commit() {
+    let temp_dir = setup_test_repo();
+    let repo_path = temp_dir.path();
+    let dropped_path = repo_path.join("worktrees").join("dropped");
+    let kept_path = repo_path.join("worktrees").join("kept");
+    
+    std::env::set_current_dir(repo_path).unwrap();
+    let git_repo = GitRepository::find().unwrap();
+    
+    // An uncommitted transaction rolls back when dropped
+    {
+        let mut transaction = git_repo.begin_worktree_creation();
+        transaction.create_worktree_and_branch("dropped", &dropped_path, None).unwrap();
+    }
+    assert!(!dropped_path.exists());
+    assert!(!git_repo.branch_exists("dropped").unwrap());
+    
+    let mut transaction = git_repo.begin_worktree_creation();
+    transaction.create_worktree_and_branch("kept", &kept_path, None).unwrap();
+    transaction.commit();
+    assert!(kept_path.exists());
+    assert!(git_repo.branch_exists("kept").unwrap());
+}
+
+#[test]
+fn test_lock_and_unlock_worktree() {
+    let temp_dir = setup_test_repo();
+    let repo_path = temp_dir.path();
+    let worktree_path = repo_path.join("worktrees").join("agent");
+    
+    std::env::set_current_dir(repo_path).unwrap();
+    let git_repo = GitRepository::find().unwrap();
+    git_repo.create_worktree_and_branch("agent", &worktree_path, None).unwrap();
+    
+    git_repo.lock_worktree(&worktree_path, Some("long-running agent")).unwrap();
+    let worktree = git_repo.find_worktree("agent").unwrap();
+    assert_eq!(worktree.locked.as_deref(), Some("long-running agent"));
+    
+    // Locked worktrees can't be removed until they are unlocked
+    assert!(git_repo.remove_worktree(&worktree_path, false).is_err());
+    
+    git_repo.unlock_worktree(&worktree_path).unwrap();
+    assert!(git_repo.find_worktree("agent").unwrap().locked.is_none());
+    
+    // A lock without a reason still counts
+    git_repo.lock_worktree(&worktree_path, None).unwrap();
+    assert_eq!(git_repo.find_worktree("agent").unwrap().locked.as_deref(), Some(""));
+    
+    assert!(git_repo.find_worktree("missing").is_err());
+}
+
+#[test]
+fn test_move_worktree_with_rename() {
+    let temp_dir = setup_test_repo();
+    let repo_path = temp_dir.path();
+    let old_path = repo_path.join("worktrees").join("old-name");
+    let new_path = repo_path.join("moved").join("new-name");
+    
+    std::env::set_current_dir(repo_path).unwrap();
+    let git_repo = GitRepository::find().unwrap();
+    git_repo.create_worktree_and_branch("old-name", &old_path, None).unwrap();
+    
+    // An untracked file that remembers where the worktree used to live
+    fs::write(old_path.join("env.sh"), format!("export ROOT={}/bin", old_path.display())).unwrap();
+    
+    git_repo.move_worktree(&old_path, &new_path, Some("new-name")).unwrap();
+    
+    assert!(!old_path.exists());
+    assert!(!git_repo.branch_exists("old-name").unwrap());
+    let worktree = git_repo.find_worktree("new-name").unwrap();
+    assert_eq!(worktree.path, new_path);
+    
+    let env = fs::read_to_string(new_path.join("env.sh")).unwrap();
+    assert_eq!(env, format!("export ROOT={}/bin", new_path.display()));
+    
+    // The moved worktree still works with git
+    let output = Command::new("git")
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
+        .current_dir(&new_path)
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "new-name");
+}
+
+#[test]
+fn test_failed_move_is_rolled_back() {
+    use git_warp::journal::{self, Journal};
+    use git_warp::rewrite::PathRewriter;
+    
+    let temp_dir = setup_test_repo();
+    let repo_path = temp_dir.path();
+    let old_path = repo_path.join("worktrees").join("feature");
+    let new_path = repo_path.join("moved").join("feature");
+    
+    std::env::set_current_dir(repo_path).unwrap();
+    let git_repo = GitRepository::find().unwrap();
+    git_repo.create_worktree_and_branch("feature", &old_path, None).unwrap();
+    
+    // Rewritten once already, as a cloned worktree would have been
+    let cloned_from = "/checkouts/main";
+    fs::write(old_path.join("env.sh"), format!("export ROOT={}/bin", cloned_from)).unwrap();
+    PathRewriter::new(cloned_from, &old_path).rewrite_paths().unwrap();
+    let rewritten = fs::read_to_string(old_path.join("env.sh")).unwrap();
+    let journal_before = serde_json::to_string(&Journal::load(&old_path).unwrap().unwrap().entries).unwrap();
+    
+    // git refuses the branch name only after the worktree has moved
+    let error = git_repo.move_worktree(&old_path, &new_path, Some("bad..name")).unwrap_err().to_string();
+    assert!(error.contains("moved the worktree back"), "unexpected error: {}", error);
+    
+    assert!(!new_path.exists());
+    assert_eq!(fs::read_to_string(old_path.join("env.sh")).unwrap(), rewritten);
+    assert_eq!(git_repo.find_worktree("feature").unwrap().path, old_path);
+    let journal_after = serde_json::to_string(&Journal::load(&old_path).unwrap().unwrap().entries).unwrap();
+    assert_eq!(journal_after, journal_before);
+    
+    // A move that succeeds joins the journal, and undo still goes back to the first original
+    git_repo.move_worktree(&old_path, &new_path, None).unwrap();
+    assert_eq!(fs::read_to_string(new_path.join("env.sh")).unwrap(), format!("export ROOT={}/bin", new_path.display()));
+    assert_eq!(Journal::load(&new_path).unwrap().unwrap().entries.len(), 1);
+    let journal_dir = repo_path.join(".git/worktrees/feature/warp");
+    let names: Vec<_> = fs::read_dir(&journal_dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    assert!(names.iter().all(|name| !name.to_string_lossy().ends_with(".aside")), "{:?}", names);
+    
+    journal::undo(&new_path).unwrap();
+    assert_eq!(fs::read_to_string(new_path.join("env.sh")).unwrap(), format!("export ROOT={}/bin", cloned_from));
+}
+
+#[test]
+fn test_move_worktree_refusals() {
+    let temp_dir = setup_test_repo();
+    let repo_path = temp_dir.path();
+    let worktree_path = repo_path.join("worktrees").join("busy");
+    let new_path = repo_path.join("worktrees").join("elsewhere");
+    
+    std::env::set_current_dir(repo_path).unwrap();
+    let git_repo = GitRepository::find().unwrap();
+    git_repo.create_worktree_and_branch("busy", &worktree_path, None).unwrap();
+    
+    // The main worktree stays put
+    assert!(git_repo.move_worktree(repo_path, &new_path, None).is_err());
+    
+    // Renaming onto an existing branch is refused before anything moves
+    assert!(git_repo.move_worktree(&worktree_path, &new_path, Some("main")).is_err());
+    assert!(worktree_path.exists());
+    
+    // So is moving a locked worktree
+    git_repo.lock_worktree(&worktree_path, Some("agent running")).unwrap();
+    assert!(git_repo.move_worktree(&worktree_path, &new_path, None).is_err());
+    git_repo.unlock_worktree(&worktree_path).unwrap();
+    
+    // And one with a process still running in it
+    let mut child = Command::new("sleep")
+        .arg("30")
+        .current_dir(&worktree_path)
+        .spawn()
+        .unwrap();
+    let result = git_repo.move_worktree(&worktree_path, &new_path, None);
+    child.kill().unwrap();
+    child.wait().unwrap();
+    
+    let error = result.unwrap_err().to_string();
+    assert!(error.contains("still running"), "unexpected error: {}", error);
+    assert!(worktree_path.exists());
+    assert!(!new_path.exists());
+}
+
 #[test]
 fn test_fetch_branches() {
     let temp_dir = setup_test_repo();
@@ -162,20 +459,20 @@ fn test_analyze_branches_for_cleanup() {
     
     // Create some branches
     Command::new("git")
-        .args(&["checkout", "-b", "feature-1"])
+        .args(["checkout", "-b", "feature-1"])
         .current_dir(repo_path)
         .output()
         .unwrap();
     
     Command::new("git")
-        .args(&["checkout", "-b", "feature-2"])
+        .args(["checkout", "-b", "feature-2"])
         .current_dir(repo_path)
         .output()
         .unwrap();
     
     // Go back to main
     Command::new("git")
-        .args(&["checkout", "main"])
+        .args(["checkout", "main"])
         .current_dir(repo_path)
         .output()
         .unwrap();
@@ -216,13 +513,13 @@ fn test_delete_branch() {
     
     // Create a branch
     Command::new("git")
-        .args(&["checkout", "-b", "test-branch"])
+        .args(["checkout", "-b", "test-branch"])
         .current_dir(repo_path)
         .output()
         .unwrap();
     
     Command::new("git")
-        .args(&["checkout", "main"])
+        .args(["checkout", "main"])
         .current_dir(repo_path)
         .output()
         .unwrap();
@@ -233,7 +530,7 @@ fn test_delete_branch() {
     
     // Verify branch is gone
     let output = Command::new("git")
-        .args(&["branch", "--list", "test-branch"])
+        .args(["branch", "--list", "test-branch"])
         .current_dir(repo_path)
         .output()
         .unwrap();
@@ -261,7 +558,7 @@ fn test_uncommitted_changes_detection() {
     
     // Stage the changes
     Command::new("git")
-        .args(&["add", "."])
+        .args(["add", "."])
         .current_dir(repo_path)
         .output()
         .unwrap();
@@ -271,7 +568,7 @@ fn test_uncommitted_changes_detection() {
     
     // Commit the changes
     Command::new("git")
-        .args(&["commit", "-m", "Add new file"])
+        .args(["commit", "-m", "Add new file"])
         .current_dir(repo_path)
         .output()
         .unwrap();
@@ -288,7 +585,7 @@ fn test_branch_merge_detection() {
     
     // Create a feature branch with some changes
     Command::new("git")
-        .args(&["checkout", "-b", "feature"])
+        .args(["checkout", "-b", "feature"])
         .current_dir(repo_path)
         .output()
         .unwrap();
@@ -296,26 +593,26 @@ fn test_branch_merge_detection() {
     fs::write(repo_path.join("feature.txt"), "Feature content").unwrap();
     
     Command::new("git")
-        .args(&["add", "."])
+        .args(["add", "."])
         .current_dir(repo_path)
         .output()
         .unwrap();
     
     Command::new("git")
-        .args(&["commit", "-m", "Add feature"])
+        .args(["commit", "-m", "Add feature"])
         .current_dir(repo_path)
         .output()
         .unwrap();
     
     // Go back to main and merge
     Command::new("git")
-        .args(&["checkout", "main"])
+        .args(["checkout", "main"])
         .current_dir(repo_path)
         .output()
         .unwrap();
     
     Command::new("git")
-        .args(&["merge", "feature"])
+        .args(["merge", "feature"])
         .current_dir(repo_path)
         .output()
         .unwrap();
@@ -328,7 +625,7 @@ fn test_branch_merge_detection() {
     
     // Test with non-merged branch
     Command::new("git")
-        .args(&["checkout", "-b", "unmerged"])
+        .args(["checkout", "-b", "unmerged"])
         .current_dir(repo_path)
         .output()
         .unwrap();
@@ -336,19 +633,19 @@ fn test_branch_merge_detection() {
     fs::write(repo_path.join("unmerged.txt"), "Unmerged content").unwrap();
     
     Command::new("git")
-        .args(&["add", "."])
+        .args(["add", "."])
         .current_dir(repo_path)
         .output()
         .unwrap();
     
     Command::new("git")
-        .args(&["commit", "-m", "Add unmerged feature"])
+        .args(["commit", "-m", "Add unmerged feature"])
         .current_dir(repo_path)
         .output()
         .unwrap();
     
     Command::new("git")
-        .args(&["checkout", "main"])
+        .args(["checkout", "main"])
         .current_dir(repo_path)
         .output()
         .unwrap();
@@ -357,6 +654,109 @@ fn test_branch_merge_detection() {
     assert!(!is_merged);
 }
 
+#[test]
+fn test_merge_method_detection() {
+    let temp_dir = setup_test_repo();
+    let repo_path = temp_dir.path();
+    std::env::set_current_dir(repo_path).unwrap();
+    
+    let git = |args: &[&str]| {
+        let output = Command::new("git").args(args).current_dir(repo_path).output().unwrap();
+        assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    };
+    let commit_file = |name: &str, content: &str| {
+        fs::write(repo_path.join(name), content).unwrap();
+        git(&["add", name]);
+        git(&["commit", "-m", &format!("Write {}", name)]);
+    };
+    
+    // Each branch gets two commits of its own
+    for branch in ["merged", "rebased", "squashed", "reshaped", "unmerged"] {
+        git(&["checkout", "-q", "-b", branch, "main"]);
+        commit_file(&format!("{}-1.txt", branch), "one");
+        commit_file(&format!("{}-2.txt", branch), "two");
+    }
+    // The most common squash merge: a branch with a single commit
+    git(&["checkout", "-q", "-b", "squashed-one", "main"]);
+    commit_file("squashed-one.txt", "one");
+    git(&["checkout", "-q", "main"]);
+    
+    git(&["merge", "--no-ff", "-m", "Merge", "merged"]);
+    git(&["cherry-pick", "main..rebased"]);
+    git(&["merge", "--squash", "squashed"]);
+    git(&["commit", "-m", "Squash"]);
+    git(&["merge", "--squash", "squashed-one"]);
+    git(&["commit", "-m", "Squash one"]);
+    
+    // The same changes landed folded into a bigger commit, so no patch id matches
+    fs::write(repo_path.join("reshaped-1.txt"), "one").unwrap();
+    fs::write(repo_path.join("reshaped-2.txt"), "two").unwrap();
+    fs::write(repo_path.join("unrelated.txt"), "noise").unwrap();
+    git(&["add", "."]);
+    git(&["commit", "-m", "Land reshaped work"]);
+    
+    let git_repo = GitRepository::find().unwrap();
+    let method = |branch: &str| git_repo.merge_method(branch, "main").unwrap();
+    
+    assert_eq!(method("merged"), Some(MergeMethod::Ancestor));
+    assert_eq!(method("rebased"), Some(MergeMethod::Rebase));
+    assert_eq!(method("squashed"), Some(MergeMethod::Squash));
+    assert_eq!(method("squashed-one"), Some(MergeMethod::Squash));
+    assert_eq!(method("reshaped"), Some(MergeMethod::IdenticalTree));
+    assert_eq!(method("unmerged"), None);
+    assert_eq!(git_repo.merge_method("merged", "no-such-branch").unwrap(), None);
+    
+    // Cleanup analysis reports the method for each worktree
+    let worktree_path = repo_path.join("worktrees").join("squashed");
+    git_repo.create_worktree_and_branch("squashed", &worktree_path, None).unwrap();
+    let worktrees = git_repo.list_worktrees().unwrap();
+    let statuses = git_repo.analyze_branches_for_cleanup(&worktrees).unwrap();
+    assert_eq!(statuses.len(), 1);
+    assert!(statuses[0].is_merged);
+    assert_eq!(statuses[0].merged_via, Some(MergeMethod::Squash));
+}
+
+#[test]
+fn test_cleanup_finds_ancestors_past_the_history_cutoff() {
+    let temp_dir = setup_test_repo();
+    let repo_path = temp_dir.path();
+    std::env::set_current_dir(repo_path).unwrap();
+    
+    let commit_at = |name: &str, days_ago: i64| {
+        fs::write(repo_path.join(name), name).unwrap();
+        let date = format!("{} +0000", chrono::Utc::now().timestamp() - days_ago * 24 * 60 * 60);
+        for args in [vec!["add", name], vec!["commit", "-q", "-m", name]] {
+            let output = Command::new("git")
+                .args(&args)
+                .env("GIT_AUTHOR_DATE", &date)
+                .env("GIT_COMMITTER_DATE", &date)
+                .current_dir(repo_path)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+        }
+    };
+    
+    // main reaches the old branch's tip only through a commit dated well before it
+    commit_at("old.txt", 10);
+    Command::new("git").args(["branch", "old"]).current_dir(repo_path).output().unwrap();
+    commit_at("imported.txt", 30);
+    commit_at("latest.txt", 0);
+    
+    let git_repo = GitRepository::find().unwrap();
+    let worktree_path = repo_path.join("worktrees").join("old");
+    Command::new("git")
+        .args(["worktree", "add", "-q", worktree_path.to_str().unwrap(), "old"])
+        .current_dir(repo_path)
+        .output()
+        .unwrap();
+    
+    let worktrees = git_repo.list_worktrees().unwrap();
+    let statuses = git_repo.analyze_branches_for_cleanup(&worktrees).unwrap();
+    assert_eq!(statuses.len(), 1);
+    assert_eq!(statuses[0].merged_via, Some(MergeMethod::Ancestor));
+}
+
 #[test]
 fn test_worktree_path_generation() {
     let temp_dir = setup_test_repo();
@@ -369,5 +769,316 @@ fn test_worktree_path_generation() {
     
     // Should sanitize branch name and create path
     assert!(worktree_path.to_string_lossy().contains("feature-awesome-feature"));
-    assert!(worktree_path.parent().unwrap().ends_with("worktrees"));
-}
\ No newline at end of file
+    assert!(worktree_path.parent().unwrap().parent().unwrap().ends_with("worktrees"));
+}
+
+#[test]
+fn test_worktree_path_templates_and_collisions() {
+    use git_warp::layout::WorktreeLayout;
+    
+    let temp_dir = setup_test_repo();
+    let repo_path = temp_dir.path();
+    std::env::set_current_dir(repo_path).unwrap();
+    
+    let worktrees_dir = tempdir().unwrap();
+    let layout = WorktreeLayout::new(
+        Some(worktrees_dir.path().to_path_buf()),
+        "{worktrees_path}/{repo}/{branch_slug}",
+    )
+    .unwrap();
+    let git_repo = GitRepository::find().unwrap().with_layout(layout);
+    // Temp dirs start with a dot, which sanitizing drops
+    let repo_name = repo_path.file_name().unwrap().to_string_lossy().trim_start_matches('.').to_string();
+    
+    let path = git_repo.get_worktree_path("feature/x");
+    assert_eq!(path, worktrees_dir.path().join(repo_name).join("feature-x"));
+    
+    // A directory that's in the way gets a stable suffix
+    fs::create_dir_all(&path).unwrap();
+    let suffixed = git_repo.get_worktree_path("feature/x");
+    assert_ne!(suffixed, path);
+    assert!(suffixed.file_name().unwrap().to_string_lossy().starts_with("feature-x-"));
+    assert_eq!(git_repo.get_worktree_path("feature/x"), suffixed);
+    
+    // Once created, the branch's worktree is found where it is
+    git_repo.create_worktree_and_branch("feature/x", &suffixed, None).unwrap();
+    assert_eq!(git_repo.get_worktree_path("feature/x"), suffixed);
+}
+#[test]
+fn test_integration_target_resolution() {
+    use git_warp::config::GitConfig;
+    
+    let temp_dir = setup_test_repo();
+    let repo_path = temp_dir.path();
+    std::env::set_current_dir(repo_path).unwrap();
+    
+    let git = |args: &[&str]| {
+        let output = Command::new("git").args(args).current_dir(repo_path).output().unwrap();
+        assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    };
+    
+    // Only a local master: falls back to it
+    git(&["branch", "-M", "master"]);
+    let git_repo = GitRepository::find().unwrap();
+    let target = git_repo.integration_target().unwrap();
+    assert_eq!(target.branch, "master");
+    assert_eq!(target.reference, "master");
+    
+    // origin/HEAD wins over the local fallback
+    let origin_dir = tempdir().unwrap();
+    let origin = origin_dir.path().join("origin.git");
+    git(&["branch", "-m", "master", "trunk"]);
+    git(&["clone", "-q", "--bare", ".", origin.to_str().unwrap()]);
+    git(&["branch", "main"]);
+    git(&["remote", "add", "origin", origin.to_str().unwrap()]);
+    git(&["fetch", "-q", "origin"]);
+    git(&["remote", "set-head", "origin", "trunk"]);
+    let target = git_repo.integration_target().unwrap();
+    assert_eq!(target.branch, "trunk");
+    assert_eq!(target.reference, "origin/trunk");
+    
+    // Configured branch wins over everything, and can compare locally
+    let mut config = GitConfig {
+        default_branch: "main".to_string(),
+        compare_remote: false,
+        ..GitConfig::default()
+    };
+    let git_repo = git_repo.with_config(&config);
+    let target = git_repo.integration_target().unwrap();
+    assert_eq!(target.branch, "main");
+    assert_eq!(target.reference, "main");
+    
+    // A configured branch that doesn't exist is ignored
+    config.default_branch = "no-such-branch".to_string();
+    let git_repo = git_repo.with_config(&config);
+    assert_eq!(git_repo.integration_target().unwrap().branch, "trunk");
+}
+
+#[test]
+fn test_cleanup_compares_against_remote_branch() {
+    use git_warp::config::GitConfig;
+    
+    let temp_dir = setup_test_repo();
+    let repo_path = temp_dir.path();
+    std::env::set_current_dir(repo_path).unwrap();
+    
+    let git = |args: &[&str]| {
+        let output = Command::new("git").args(args).current_dir(repo_path).output().unwrap();
+        assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    };
+    
+    let origin_dir = tempdir().unwrap();
+    let origin = origin_dir.path().join("origin.git");
+    git(&["branch", "-M", "main"]);
+    git(&["clone", "-q", "--bare", ".", origin.to_str().unwrap()]);
+    git(&["remote", "add", "origin", origin.to_str().unwrap()]);
+    
+    // The feature was merged on the remote, but the local main is stale
+    git(&["checkout", "-q", "-b", "feature"]);
+    fs::write(repo_path.join("feature.txt"), "done").unwrap();
+    git(&["add", "feature.txt"]);
+    git(&["commit", "-m", "Add feature"]);
+    git(&["push", "-q", "origin", "feature:main"]);
+    git(&["checkout", "-q", "main"]);
+    git(&["fetch", "-q", "origin"]);
+    git(&["remote", "set-head", "origin", "--auto"]);
+    
+    let git_repo = GitRepository::find().unwrap();
+    git_repo.create_worktree_and_branch("feature", repo_path.join("worktrees").join("feature"), None).unwrap();
+    let worktrees = git_repo.list_worktrees().unwrap();
+    
+    let statuses = git_repo.analyze_branches_for_cleanup(&worktrees).unwrap();
+    assert_eq!(statuses.len(), 1);
+    assert!(statuses[0].is_merged);
+    
+    let local_only = GitConfig {
+        compare_remote: false,
+        ..GitConfig::default()
+    };
+    let statuses = git_repo.with_config(&local_only).analyze_branches_for_cleanup(&worktrees).unwrap();
+    assert!(!statuses[0].is_merged);
+}
+
+#[test]
+fn test_worktree_details() {
+    let temp_dir = setup_test_repo();
+    let repo_path = temp_dir.path();
+    std::env::set_current_dir(repo_path).unwrap();
+    
+    let git = |args: &[&str]| {
+        let output = Command::new("git").args(args).current_dir(repo_path).output().unwrap();
+        assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    };
+    
+    let origin_dir = tempdir().unwrap();
+    let origin = origin_dir.path().join("origin.git");
+    git(&["branch", "-M", "main"]);
+    git(&["clone", "-q", "--bare", ".", origin.to_str().unwrap()]);
+    git(&["remote", "add", "origin", origin.to_str().unwrap()]);
+    git(&["fetch", "-q", "origin"]);
+    git(&["branch", "-q", "-u", "origin/main"]);
+    
+    // One local commit ahead of origin, plus two dirty paths
+    fs::write(repo_path.join("README.md"), "# Changed").unwrap();
+    git(&["commit", "-q", "-am", "Update readme"]);
+    fs::write(repo_path.join("README.md"), "# Changed again").unwrap();
+    fs::write(repo_path.join("notes.txt"), "untracked").unwrap();
+    
+    let git_repo = GitRepository::find().unwrap();
+    let worktrees_dir = tempdir().unwrap();
+    let feature_path = worktrees_dir.path().join("feature");
+    git_repo.create_worktree_and_branch("feature", &feature_path, None).unwrap();
+    
+    let worktrees = git_repo.list_worktrees().unwrap();
+    let details = git_repo.worktree_details(&worktrees);
+    assert_eq!(details.len(), worktrees.len());
+    
+    let main = &details[worktrees.iter().position(|wt| wt.is_main).unwrap()];
+    assert_eq!(main.upstream.as_deref(), Some("origin/main"));
+    assert_eq!(main.upstream_status.map(|s| (s.ahead, s.behind)), Some((1, 0)));
+    assert_eq!(main.dirty_files, 2);
+    let commit = main.last_commit.as_ref().unwrap();
+    assert_eq!(commit.subject, "Update readme");
+    assert_eq!(commit.author, "Test User");
+    assert!(commit.timestamp > 0);
+    
+    let feature = &details[worktrees.iter().position(|wt| wt.branch == "feature").unwrap()];
+    assert_eq!(feature.upstream, None);
+    assert_eq!(feature.upstream_status, None);
+    assert_eq!(feature.dirty_files, 0);
+    // The feature branch starts from the local main, one commit past origin/main
+    assert_eq!(feature.default_status.map(|s| (s.ahead, s.behind)), Some((1, 0)));
+}
+
+#[test]
+fn test_worktree_info_serialization() {
+    use git_warp::git::{BranchStatus, WorktreeInfo};
+    
+    let info = WorktreeInfo {
+        path: "/repo".into(),
+        branch: "main".to_string(),
+        is_main: true,
+        locked: Some(String::new()),
+        ..Default::default()
+    };
+    let value = serde_json::to_value(&info).unwrap();
+    assert_eq!(value["path"], "/repo");
+    assert_eq!(value["branch"], "main");
+    assert_eq!(value["is_main"], true);
+    assert_eq!(value["locked"], "");
+    assert!(value["prunable"].is_null());
+    
+    let status = BranchStatus {
+        branch: "feature".to_string(),
+        path: "/repo/feature".into(),
+        has_remote: false,
+        upstream_gone: false,
+        is_merged: true,
+        merged_via: Some(MergeMethod::IdenticalTree),
+        is_identical: false,
+        has_uncommitted_changes: false,
+        locked: None,
+    };
+    let value = serde_json::to_value(&status).unwrap();
+    assert_eq!(value["merged_via"], "identical_tree");
+}
+
+#[test]
+fn test_upstream_gone_detection() {
+    let temp_dir = setup_test_repo();
+    let repo_path = temp_dir.path();
+    std::env::set_current_dir(repo_path).unwrap();
+    
+    let git = |args: &[&str]| {
+        let output = Command::new("git").args(args).current_dir(repo_path).output().unwrap();
+        assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    };
+    
+    let origin_dir = tempdir().unwrap();
+    let origin = origin_dir.path().join("origin.git");
+    git(&["branch", "-M", "main"]);
+    git(&["clone", "-q", "--bare", ".", origin.to_str().unwrap()]);
+    git(&["remote", "add", "origin", origin.to_str().unwrap()]);
+    
+    let git_repo = GitRepository::find().unwrap();
+    let worktrees_dir = tempdir().unwrap();
+    for branch in ["deleted", "pushed", "local"] {
+        git_repo.create_worktree_and_branch(branch, worktrees_dir.path().join(branch), None).unwrap();
+    }
+    git(&["push", "-q", "-u", "origin", "deleted", "pushed"]);
+    
+    // The remote branch is deleted, e.g. after merging a pull request
+    let output = Command::new("git")
+        .args(["branch", "-D", "deleted"])
+        .current_dir(&origin)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(git_repo.fetch_branches().unwrap());
+    
+    let worktrees = git_repo.list_worktrees().unwrap();
+    let statuses = git_repo.analyze_branches_for_cleanup(&worktrees).unwrap();
+    let status = |branch: &str| statuses.iter().find(|status| status.branch == branch).unwrap();
+    
+    assert!(status("deleted").has_remote);
+    assert!(status("deleted").upstream_gone);
+    assert!(status("pushed").has_remote);
+    assert!(!status("pushed").upstream_gone);
+    assert!(!status("local").has_remote);
+    assert!(!status("local").upstream_gone);
+}
+
+#[test]
+fn test_worktrees_from_revisions() {
+    use git_warp::git::WorktreeHead;
+    
+    let temp_dir = setup_test_repo();
+    let repo_path = temp_dir.path();
+    std::env::set_current_dir(repo_path).unwrap();
+    
+    let git = |args: &[&str]| {
+        let output = Command::new("git").args(args).current_dir(repo_path).output().unwrap();
+        assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    };
+    
+    git(&["branch", "-M", "main"]);
+    git(&["tag", "v1"]);
+    let tagged = git(&["rev-parse", "v1^{commit}"]);
+    fs::write(repo_path.join("later.txt"), "later").unwrap();
+    git(&["add", "."]);
+    git(&["commit", "-m", "Later"]);
+    
+    // A branch that only exists on the remote
+    let origin_dir = tempdir().unwrap();
+    let origin = origin_dir.path().join("origin.git");
+    git(&["clone", "-q", "--bare", ".", origin.to_str().unwrap()]);
+    git(&["remote", "add", "origin", origin.to_str().unwrap()]);
+    git(&["branch", "remote-only", "v1"]);
+    git(&["push", "-q", "origin", "remote-only"]);
+    git(&["branch", "-D", "remote-only"]);
+    git(&["fetch", "-q", "origin"]);
+    
+    let git_repo = GitRepository::find().unwrap();
+    let worktrees_dir = tempdir().unwrap();
+    let path = |name: &str| worktrees_dir.path().join(name);
+    
+    git_repo.create_worktree_and_branch("from-tag", path("from-tag"), Some("v1")).unwrap();
+    git_repo.create_worktree_and_branch("remote-only", path("remote-only"), None).unwrap();
+    git_repo.create_worktree(WorktreeHead::Detached("v1"), path("detached")).unwrap();
+    
+    let worktrees = git_repo.list_worktrees().unwrap();
+    let find = |name: &str| worktrees.iter().find(|wt| wt.path.ends_with(name)).unwrap();
+    assert_eq!(find("from-tag").branch, "from-tag");
+    assert_eq!(find("from-tag").head, tagged);
+    assert_eq!(find("remote-only").head, tagged);
+    assert_eq!(git(&["rev-parse", "--abbrev-ref", "remote-only@{upstream}"]), "origin/remote-only");
+    assert!(find("detached").is_detached);
+    assert!(find("detached").branch.is_empty());
+    assert_eq!(find("detached").head, tagged);
+    
+    // A start point for a branch that already exists is refused rather than ignored
+    let err = git_repo.create_worktree_and_branch("from-tag", path("again"), Some("main")).unwrap_err();
+    assert!(err.to_string().contains("already exists"));
+    assert!(!path("again").exists());
+}
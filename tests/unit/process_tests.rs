@@ -83,7 +83,7 @@ sleep 30
     
     // On some systems, the process might not be detected immediately
     if !sleep_processes.is_empty() {
-        assert!(sleep_processes.len() > 0);
+        assert!(!sleep_processes.is_empty());
         println!("Successfully detected running process in directory");
     } else {
         println!("Process detection test skipped (timing-dependent)");
@@ -100,7 +100,7 @@ fn test_process_termination() {
     
     let pid = child.id();
     
-    let mut manager = ProcessManager::new();
+    let manager = ProcessManager::new();
     
     // Create a mock ProcessInfo for the child process
     let process_info = ProcessInfo {
@@ -108,7 +108,7 @@ fn test_process_termination() {
         name: "sleep".to_string(),
         cpu_usage: 0.0,
         memory_usage: 1024,
-        working_dir: std::env::current_dir().unwrap(),
+        working_dir: std::env::temp_dir(),
         cmd: "sleep 30".to_string(),
         start_time: 0, // Not used in termination
     };
@@ -144,7 +144,7 @@ fn test_process_termination_nonexistent() {
         name: "fake_process".to_string(),
         cpu_usage: 0.0,
         memory_usage: 1024,
-        working_dir: std::env::current_dir().unwrap(),
+        working_dir: std::env::temp_dir(),
         cmd: "fake command".to_string(),
         start_time: 0,
     };
@@ -280,7 +280,7 @@ fn test_graceful_vs_force_termination() {
         name: "sleep".to_string(),
         cpu_usage: 0.0,
         memory_usage: 1024,
-        working_dir: std::env::current_dir().unwrap(),
+        working_dir: std::env::temp_dir(),
         cmd: "sleep 30".to_string(),
         start_time: 0,
     };
@@ -306,12 +306,12 @@ fn test_graceful_vs_force_termination() {
 #[cfg(unix)]
 #[test]
 fn test_signal_handling() {
-    use std::os::unix::process::ExitStatusExt;
     
     // Start a process that handles SIGTERM
     let script_content = r#"#!/bin/bash
 trap 'echo "Received SIGTERM"; exit 0' TERM
-sleep 30
+sleep 30 &
+wait
 "#;
     
     let temp_dir = tempdir().unwrap();
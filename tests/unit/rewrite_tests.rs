@@ -1,14 +1,16 @@
-use git_warp::rewrite::PathRewriter;
+use git_warp::progress::{is_cancelled, NoProgress, Progress, ProgressObserver};
+use git_warp::rewrite::{edit_file, BinaryFormat, Entry, FileOutcome, PathMap, PathRewriter, RewriteScope, Rewriter};
 use tempfile::tempdir;
 use std::fs;
 use std::path::Path;
+use std::sync::Mutex;
 
 #[test]
 fn test_path_rewriter_creation() {
     let src_dir = "/original/project";
     let dst_dir = "/cloned/project";
     
-    let rewriter = PathRewriter::new(src_dir, dst_dir);
+    let _rewriter = PathRewriter::new(src_dir, dst_dir);
     
     // PathRewriter should be created successfully
     println!("PathRewriter created for {} -> {}", src_dir, dst_dir);
@@ -104,7 +106,7 @@ SOURCE_DIR = "{}"
 "#, src_dir.display(), src_dir.display());
     fs::write(build_dir.join("build.conf"), build_config_content).unwrap();
     
-    let rewriter = PathRewriter::new(&src_dir, &dst_dir);
+    let rewriter = PathRewriter::new(&src_dir, &dst_dir).with_scope(RewriteScope::IgnoredOnly);
     let result = rewriter.rewrite_paths();
     
     match result {
@@ -361,7 +363,7 @@ vendor/
         ("package.json", false),
     ];
     
-    for (file_path, should_match) in test_files {
+    for (file_path, _should_match) in test_files {
         let full_path = dst_dir.join(file_path);
         
         if let Some(parent) = full_path.parent() {
@@ -472,4 +474,533 @@ special_chars = "{}/àáâãäå"
             println!("Unicode path test failed: {}", e);
         }
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_rewrite_progress_reporting() {
+    let temp_dir = tempdir().unwrap();
+    let src_dir = temp_dir.path().join("source");
+    let dst_dir = temp_dir.path().join("destination");
+    
+    fs::create_dir_all(&src_dir).unwrap();
+    fs::create_dir_all(dst_dir.join("nested")).unwrap();
+    fs::write(dst_dir.join("a.conf"), format!("root = \"{}\"", src_dir.display())).unwrap();
+    fs::write(dst_dir.join("nested").join("b.conf"), "nothing to rewrite").unwrap();
+    
+    let updates = Mutex::new(Vec::new());
+    let observer = |progress: &Progress| updates.lock().unwrap().push(progress.clone());
+    
+    let rewriter = PathRewriter::new(&src_dir, &dst_dir);
+    rewriter.rewrite_paths_with_progress(&observer).unwrap();
+    
+    let updates = updates.into_inner().unwrap();
+    assert_eq!(updates.len(), 2);
+    assert!(updates.iter().any(|progress| progress.files_done == 2 && progress.files_total == 2));
+    assert!(updates.iter().any(|progress| progress.current == Path::new("nested").join("b.conf")));
+}
+
+#[test]
+fn test_rewrite_cancellation() {
+    struct Cancelled;
+    
+    impl ProgressObserver for Cancelled {
+        fn on_progress(&self, _progress: &Progress) {}
+        
+        fn is_cancelled(&self) -> bool {
+            true
+        }
+    }
+    
+    let temp_dir = tempdir().unwrap();
+    let src_dir = temp_dir.path().join("source");
+    let dst_dir = temp_dir.path().join("destination");
+    
+    fs::create_dir_all(&dst_dir).unwrap();
+    let content = format!("root = \"{}\"", src_dir.display());
+    fs::write(dst_dir.join("a.conf"), &content).unwrap();
+    
+    let rewriter = PathRewriter::new(&src_dir, &dst_dir);
+    let result = rewriter.rewrite_paths_with_progress(&Cancelled);
+    
+    assert!(is_cancelled(&result.unwrap_err()));
+    assert_eq!(fs::read_to_string(dst_dir.join("a.conf")).unwrap(), content);
+}
+
+#[test]
+fn test_non_utf8_file_rewriting() {
+    let temp_dir = tempdir().unwrap();
+    let src_dir = temp_dir.path().join("source");
+    let dst_dir = temp_dir.path().join("destination");
+    fs::create_dir_all(&dst_dir).unwrap();
+    
+    // Latin-1 text around the path
+    let mut content = b"# caf\xe9\nroot = ".to_vec();
+    content.extend_from_slice(src_dir.to_string_lossy().as_bytes());
+    content.extend_from_slice(b"\n# na\xefve\n");
+    fs::write(dst_dir.join("latin1.conf"), &content).unwrap();
+    
+    let report = PathRewriter::new(&src_dir, &dst_dir)
+        .rewrite_paths_with_progress(&NoProgress)
+        .unwrap();
+    
+    let mut expected = b"# caf\xe9\nroot = ".to_vec();
+    expected.extend_from_slice(dst_dir.to_string_lossy().as_bytes());
+    expected.extend_from_slice(b"\n# na\xefve\n");
+    assert_eq!(fs::read(dst_dir.join("latin1.conf")).unwrap(), expected);
+    assert_eq!(report.rewritten, vec![Path::new("latin1.conf")]);
+}
+
+#[test]
+fn test_binary_formats_are_reported_not_rewritten() {
+    let temp_dir = tempdir().unwrap();
+    let src_dir = temp_dir.path().join("source");
+    let dst_dir = temp_dir.path().join("destination");
+    fs::create_dir_all(dst_dir.join("__pycache__")).unwrap();
+    
+    let with_path = |magic: &[u8]| {
+        let mut content = magic.to_vec();
+        content.extend_from_slice(b"\x00\x00\x00\x00");
+        content.extend_from_slice(src_dir.to_string_lossy().as_bytes());
+        content
+    };
+    let elf = with_path(b"\x7fELF\x02\x01\x01");
+    let sqlite = with_path(b"SQLite format 3\x00");
+    let pyc = with_path(b"\xa7\x0d\x0d\x0a");
+    fs::write(dst_dir.join("tool"), &elf).unwrap();
+    fs::write(dst_dir.join("cache.db"), &sqlite).unwrap();
+    fs::write(dst_dir.join("__pycache__").join("mod.cpython-311.pyc"), &pyc).unwrap();
+    
+    let report = PathRewriter::new(&src_dir, &dst_dir)
+        .rewrite_paths_with_progress(&NoProgress)
+        .unwrap();
+    
+    assert_eq!(fs::read(dst_dir.join("tool")).unwrap(), elf);
+    assert_eq!(fs::read(dst_dir.join("cache.db")).unwrap(), sqlite);
+    assert_eq!(fs::read(dst_dir.join("__pycache__").join("mod.cpython-311.pyc")).unwrap(), pyc);
+    assert!(report.rewritten.is_empty());
+    
+    let mut binaries = report.binaries;
+    binaries.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(binaries, vec![
+        (Path::new("__pycache__").join("mod.cpython-311.pyc"), BinaryFormat::Pyc),
+        (Path::new("cache.db").to_path_buf(), BinaryFormat::Sqlite),
+        (Path::new("tool").to_path_buf(), BinaryFormat::Elf),
+    ]);
+}
+
+#[test]
+fn test_large_file_rewriting() {
+    let temp_dir = tempdir().unwrap();
+    let src_dir = temp_dir.path().join("source");
+    let dst_dir = temp_dir.path().join("destination");
+    fs::create_dir_all(&dst_dir).unwrap();
+    
+    // Big enough to be memory-mapped, with the path at both ends
+    let line = format!("{}/lib/module.js\n", src_dir.display());
+    let filler = "x".repeat(2 << 20);
+    fs::write(dst_dir.join("bundle.js.map"), format!("{}{}{}", line, filler, line)).unwrap();
+    
+    PathRewriter::new(&src_dir, &dst_dir).rewrite_paths().unwrap();
+    
+    let expected_line = format!("{}/lib/module.js\n", dst_dir.display());
+    let rewritten = fs::read_to_string(dst_dir.join("bundle.js.map")).unwrap();
+    assert_eq!(rewritten, format!("{}{}{}", expected_line, filler, expected_line));
+}
+
+/// A source and destination pair under a fresh temp dir
+fn source_and_destination() -> (tempfile::TempDir, std::path::PathBuf, std::path::PathBuf) {
+    let temp_dir = tempdir().unwrap();
+    let src_dir = temp_dir.path().join("source");
+    let dst_dir = temp_dir.path().join("destination");
+    fs::create_dir_all(&src_dir).unwrap();
+    fs::create_dir_all(&dst_dir).unwrap();
+    (temp_dir, src_dir, dst_dir)
+}
+
+#[test]
+fn test_venv_config_activate_and_pth_rewriting() {
+    let (_temp_dir, src_dir, dst_dir) = source_and_destination();
+    let src = src_dir.display().to_string();
+    let dst = dst_dir.display().to_string();
+    let venv = dst_dir.join(".venv");
+    let site_packages = venv.join("lib/python3.11/site-packages");
+    fs::create_dir_all(venv.join("bin")).unwrap();
+    fs::create_dir_all(&site_packages).unwrap();
+    
+    fs::write(venv.join("pyvenv.cfg"), format!("home = /usr/bin\ncommand = /usr/bin/python3 -m venv {src}/.venv\n")).unwrap();
+    fs::write(venv.join("bin/activate"), format!("VIRTUAL_ENV=\"{src}/.venv\"\nexport VIRTUAL_ENV\n")).unwrap();
+    fs::write(venv.join("bin/activate.fish"), format!("set -gx VIRTUAL_ENV '{src}/.venv'\n")).unwrap();
+    fs::write(
+        site_packages.join("__editable__.app-0.1.pth"),
+        format!("{src}/src\nimport sys; sys.path.append('{src}/extra')\n{src}-other/lib\n/usr/lib/python3/dist-packages\n"),
+    )
+    .unwrap();
+    
+    PathRewriter::new(&src_dir, &dst_dir).rewrite_paths().unwrap();
+    
+    assert_eq!(
+        fs::read_to_string(venv.join("pyvenv.cfg")).unwrap(),
+        format!("home = /usr/bin\ncommand = /usr/bin/python3 -m venv {dst}/.venv\n")
+    );
+    assert_eq!(fs::read_to_string(venv.join("bin/activate")).unwrap(), format!("VIRTUAL_ENV=\"{dst}/.venv\"\nexport VIRTUAL_ENV\n"));
+    assert_eq!(fs::read_to_string(venv.join("bin/activate.fish")).unwrap(), format!("set -gx VIRTUAL_ENV '{dst}/.venv'\n"));
+    
+    // Path lines move only when they lie under the source; a sibling sharing its prefix stays put
+    assert_eq!(
+        fs::read_to_string(site_packages.join("__editable__.app-0.1.pth")).unwrap(),
+        format!("{dst}/src\nimport sys; sys.path.append('{dst}/extra')\n{src}-other/lib\n/usr/lib/python3/dist-packages\n")
+    );
+}
+
+#[test]
+fn test_venv_console_script_shebangs() {
+    let (temp_dir, src_dir, _) = source_and_destination();
+    let src = src_dir.display().to_string();
+    let body = "\n# -*- coding: utf-8 -*-\nimport sys\nfrom black import patched_main\nsys.exit(patched_main())\n";
+    
+    for dst_dir in [temp_dir.path().join("short"), temp_dir.path().join("x".repeat(120)), temp_dir.path().join("with space")] {
+        let bin = dst_dir.join("venv/bin");
+        fs::create_dir_all(&bin).unwrap();
+        fs::write(dst_dir.join("venv/pyvenv.cfg"), "home = /usr/bin\n").unwrap();
+        fs::write(bin.join("black"), format!("#!{src}/venv/bin/python{body}")).unwrap();
+        
+        PathRewriter::new(&src_dir, &dst_dir).rewrite_paths().unwrap();
+        
+        let python = format!("{}/venv/bin/python", dst_dir.display());
+        let script = fs::read_to_string(bin.join("black")).unwrap();
+        if python.len() + 2 <= 127 && !python.contains(' ') {
+            assert_eq!(script, format!("#!{python}{body}"));
+        } else {
+            // pip's trampoline for interpreters the kernel can't exec from a shebang
+            assert_eq!(script, format!("#!/bin/sh\n'''exec' \"{python}\" \"$0\" \"$@\"\n' '''{body}"));
+        }
+    }
+}
+
+#[test]
+fn test_venv_direct_url_rewriting() {
+    let temp_dir = tempdir().unwrap();
+    let src_dir = temp_dir.path().join("my project");
+    let dst_dir = temp_dir.path().join("my worktree");
+    let dist_info = dst_dir.join("venv/lib/python3.11/site-packages/app-0.1.dist-info");
+    fs::create_dir_all(&dist_info).unwrap();
+    fs::write(dst_dir.join("venv/pyvenv.cfg"), "home = /usr/bin\n").unwrap();
+    
+    let url = |dir: &Path| format!("file://{}", dir.display().to_string().replace(' ', "%20"));
+    fs::write(
+        dist_info.join("direct_url.json"),
+        format!("{{\"dir_info\": {{\"editable\": true}}, \"url\": \"{}\"}}", url(&src_dir)),
+    )
+    .unwrap();
+    
+    PathRewriter::new(&src_dir, &dst_dir).rewrite_paths().unwrap();
+    
+    assert_eq!(
+        fs::read_to_string(dist_info.join("direct_url.json")).unwrap(),
+        format!("{{\"dir_info\": {{\"editable\": true}}, \"url\": \"{}\"}}", url(&dst_dir))
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_node_modules_bin_symlink_rewriting() {
+    let (_temp_dir, src_dir, dst_dir) = source_and_destination();
+    let bin = dst_dir.join("node_modules/.bin");
+    fs::create_dir_all(&bin).unwrap();
+    fs::create_dir_all(dst_dir.join("node_modules/tsc/bin")).unwrap();
+    
+    std::os::unix::fs::symlink(src_dir.join("node_modules/tsc/bin/tsc"), bin.join("tsc")).unwrap();
+    std::os::unix::fs::symlink("../eslint/bin/eslint.js", bin.join("eslint")).unwrap();
+    std::os::unix::fs::symlink("/usr/bin/node", bin.join("node")).unwrap();
+    
+    let report = PathRewriter::new(&src_dir, &dst_dir)
+        .rewrite_paths_with_progress(&NoProgress)
+        .unwrap();
+    
+    assert_eq!(fs::read_link(bin.join("tsc")).unwrap(), dst_dir.join("node_modules/tsc/bin/tsc"));
+    assert_eq!(fs::read_link(bin.join("eslint")).unwrap(), Path::new("../eslint/bin/eslint.js"));
+    assert_eq!(fs::read_link(bin.join("node")).unwrap(), Path::new("/usr/bin/node"));
+    assert_eq!(report.rewritten, vec![Path::new("node_modules/.bin/tsc")]);
+    assert_eq!(fs::read_dir(&bin).unwrap().count(), 3);
+}
+
+#[test]
+fn test_cmake_cache_rewriting() {
+    let (_temp_dir, src_dir, dst_dir) = source_and_destination();
+    let src = src_dir.display().to_string();
+    let dst = dst_dir.display().to_string();
+    fs::create_dir_all(dst_dir.join("build")).unwrap();
+    
+    let cache = |root: &str| format!(
+        "# This is the CMakeCache file.\n\
+         # For build in directory: {root}/build\n\
+         //Value Computed by CMake, was {src}/build\n\
+         CMAKE_CACHEFILE_DIR:INTERNAL={root}/build\n\
+         app_SOURCE_DIR:STATIC={root}\n\
+         \"OPT=X\":PATH={root}/opt;{root}/opt2\n\
+         CMAKE_C_COMPILER:FILEPATH=/usr/bin/cc\n"
+    );
+    fs::write(dst_dir.join("build/CMakeCache.txt"), cache(&src)).unwrap();
+    
+    PathRewriter::new(&src_dir, &dst_dir).rewrite_paths().unwrap();
+    
+    assert_eq!(fs::read_to_string(dst_dir.join("build/CMakeCache.txt")).unwrap(), cache(&dst));
+}
+
+#[test]
+fn test_compile_commands_rewriting() {
+    let temp_dir = tempdir().unwrap();
+    let src_dir = temp_dir.path().join("src \"tree\"");
+    let dst_dir = temp_dir.path().join("dst tree");
+    fs::create_dir_all(dst_dir.join("build")).unwrap();
+    
+    // Paths are JSON strings, and shell-escaped inside `command`
+    let entry = |root: &Path| {
+        let root = root.display().to_string();
+        let escaped = root.replace('"', "\\\"").replace(' ', "\\ ");
+        serde_json::json!([{
+            "directory": format!("{root}/build"),
+            "command": format!("/usr/bin/cc -I{escaped}/include -o main.o -c {escaped}/main.c"),
+            "file": format!("{root}/main.c"),
+            "output": "main.o",
+        }, {
+            "directory": format!("{root}/build"),
+            "arguments": ["/usr/bin/cc", "-c", format!("{root}/util.c")],
+            "file": "../util.c",
+        }])
+    };
+    fs::write(dst_dir.join("build/compile_commands.json"), entry(&src_dir).to_string()).unwrap();
+    
+    PathRewriter::new(&src_dir, &dst_dir).rewrite_paths().unwrap();
+    
+    let rewritten = fs::read_to_string(dst_dir.join("build/compile_commands.json")).unwrap();
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&rewritten).unwrap(), entry(&dst_dir));
+}
+
+#[test]
+fn test_cargo_dep_info_rewriting() {
+    let temp_dir = tempdir().unwrap();
+    let src_dir = temp_dir.path().join("source");
+    let dst_dir = temp_dir.path().join("dest with space");
+    let deps = dst_dir.join("target/debug/deps");
+    fs::create_dir_all(&deps).unwrap();
+    
+    let dep_info = |root: &Path| {
+        let root = root.display().to_string().replace(' ', "\\ ");
+        format!("{root}/target/debug/deps/app-1234.d: src/main.rs {root}/build/gen.rs\n\nsrc/main.rs:\n{root}/build/gen.rs:\n")
+    };
+    let file = deps.join("app-1234.d");
+    fs::write(&file, dep_info(&src_dir)).unwrap();
+    let modified = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+    fs::File::options().write(true).open(&file).unwrap().set_modified(modified).unwrap();
+    
+    // A dry run doesn't touch the file, not even to restore its timestamp
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let changed = |path: &Path| {
+            let metadata = fs::metadata(path).unwrap();
+            (metadata.ctime(), metadata.ctime_nsec())
+        };
+        
+        let before = changed(&file);
+        let report = PathRewriter::new(&src_dir, &dst_dir)
+            .dry_run(true)
+            .rewrite_paths_with_progress(&NoProgress)
+            .unwrap();
+        assert_eq!(report.rewritten, vec![Path::new("target/debug/deps/app-1234.d")]);
+        assert_eq!(fs::read_to_string(&file).unwrap(), dep_info(&src_dir));
+        assert_eq!(changed(&file), before);
+    }
+    
+    PathRewriter::new(&src_dir, &dst_dir).rewrite_paths().unwrap();
+    
+    assert_eq!(fs::read_to_string(&file).unwrap(), dep_info(&dst_dir));
+    assert_eq!(fs::metadata(&file).unwrap().modified().unwrap(), modified);
+}
+
+#[test]
+fn test_cloned_dep_info_keeps_source_mtime() {
+    use git_warp::cow::{clone_directory_with, is_cow_supported, CloneSpec};
+    
+    let temp_dir = tempdir().unwrap();
+    let src_dir = temp_dir.path().join("source");
+    let dst_dir = temp_dir.path().join("destination");
+    let dep_info = Path::new("target/debug/app.d");
+    fs::create_dir_all(src_dir.join("target/debug")).unwrap();
+    fs::write(src_dir.join(dep_info), format!("{0}/target/debug/app: {0}/src/main.rs\n", src_dir.display())).unwrap();
+    let modified = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+    fs::File::options().write(true).open(src_dir.join(dep_info)).unwrap().set_modified(modified).unwrap();
+    
+    // Whatever strategy this filesystem gets, as `warp switch` would pick it
+    let strategy = is_cow_supported(&src_dir).unwrap().best_strategy();
+    clone_directory_with(&src_dir, &dst_dir, &CloneSpec::new(), strategy).unwrap();
+    PathRewriter::new(&src_dir, &dst_dir).rewrite_paths().unwrap();
+    
+    let rewritten = fs::read_to_string(dst_dir.join(dep_info)).unwrap();
+    assert_eq!(rewritten, format!("{0}/target/debug/app: {0}/src/main.rs\n", dst_dir.display()));
+    assert_eq!(fs::metadata(dst_dir.join(dep_info)).unwrap().modified().unwrap(), modified);
+    assert_eq!(fs::metadata(src_dir.join(dep_info)).unwrap().modified().unwrap(), modified);
+}
+
+#[test]
+fn test_cargo_fingerprint_dep_info_rewriting() {
+    let (_temp_dir, src_dir, dst_dir) = source_and_destination();
+    let fingerprint = dst_dir.join("target/debug/.fingerprint/app-1234");
+    fs::create_dir_all(&fingerprint).unwrap();
+    
+    // Cargo's versioned encoding: header, then length-prefixed paths with checksum flags, then env
+    let encode = |root: &Path| {
+        let mut bytes = vec![1, 0, 0, 0, 0xff, 1];
+        let put = |bytes: &mut Vec<u8>, data: &[u8]| {
+            bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(data);
+        };
+        bytes.extend_from_slice(&2u32.to_le_bytes());
+        bytes.push(0);
+        put(&mut bytes, b"src/main.rs");
+        bytes.push(0);
+        bytes.push(0);
+        put(&mut bytes, format!("{}/vendor/lib.rs", root.display()).as_bytes());
+        bytes.push(0);
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        put(&mut bytes, b"OUT_DIR");
+        bytes.push(1);
+        put(&mut bytes, format!("{}/target/debug/build/app-5678/out", root.display()).as_bytes());
+        bytes
+    };
+    fs::write(fingerprint.join("dep-bin-app"), encode(&src_dir)).unwrap();
+    
+    // Something that only looks like one is reported rather than guessed at
+    let mut truncated = encode(&src_dir);
+    truncated.truncate(truncated.len() - 3);
+    fs::write(fingerprint.join("dep-lib-app"), &truncated).unwrap();
+    
+    let report = PathRewriter::new(&src_dir, &dst_dir)
+        .rewrite_paths_with_progress(&NoProgress)
+        .unwrap();
+    
+    assert_eq!(fs::read(fingerprint.join("dep-bin-app")).unwrap(), encode(&dst_dir));
+    assert_eq!(fs::read(fingerprint.join("dep-lib-app")).unwrap(), truncated);
+    assert_eq!(
+        report.binaries,
+        vec![(Path::new("target/debug/.fingerprint/app-1234/dep-lib-app").to_path_buf(), BinaryFormat::Data)]
+    );
+}
+
+#[test]
+fn test_custom_rewriter_takes_precedence() {
+    struct Uppercase;
+    
+    impl Rewriter for Uppercase {
+        fn name(&self) -> &'static str {
+            "uppercase"
+        }
+        
+        fn handles(&self, entry: &Entry) -> bool {
+            entry.relative.extension().is_some_and(|ext| ext == "shout")
+        }
+        
+        fn rewrite(&self, entry: &Entry, paths: &PathMap) -> git_warp::Result<FileOutcome> {
+            edit_file(entry, |content| {
+                Ok(paths.replace(content).map(|content| content.to_ascii_uppercase()))
+            })
+        }
+    }
+    
+    let (_temp_dir, src_dir, dst_dir) = source_and_destination();
+    fs::write(dst_dir.join("a.shout"), format!("at {}", src_dir.display())).unwrap();
+    fs::write(dst_dir.join("b.txt"), format!("at {}", src_dir.display())).unwrap();
+    
+    PathRewriter::new(&src_dir, &dst_dir).with_rewriter(Uppercase).rewrite_paths().unwrap();
+    
+    assert_eq!(fs::read_to_string(dst_dir.join("a.shout")).unwrap(), format!("AT {}", dst_dir.display()).to_ascii_uppercase());
+    assert_eq!(fs::read_to_string(dst_dir.join("b.txt")).unwrap(), format!("at {}", dst_dir.display()));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_absolute_symlink_retargeting() {
+    use std::os::unix::fs::symlink;
+    
+    let (_temp_dir, src_dir, dst_dir) = source_and_destination();
+    fs::create_dir_all(dst_dir.join("build/include")).unwrap();
+    fs::write(dst_dir.join("build/config.h"), "#define X 1\n").unwrap();
+    
+    symlink(src_dir.join("build/config.h"), dst_dir.join("config.h")).unwrap();
+    symlink(src_dir.join("build/include"), dst_dir.join("include")).unwrap();
+    symlink("build/config.h", dst_dir.join("relative.h")).unwrap();
+    symlink("/usr/include/stdio.h", dst_dir.join("stdio.h")).unwrap();
+    symlink(src_dir.join("build/missing.h"), dst_dir.join("missing.h")).unwrap();
+    
+    let report = PathRewriter::new(&src_dir, &dst_dir)
+        .rewrite_paths_with_progress(&NoProgress)
+        .unwrap();
+    
+    assert_eq!(fs::read_link(dst_dir.join("config.h")).unwrap(), dst_dir.join("build/config.h"));
+    assert_eq!(fs::read_link(dst_dir.join("include")).unwrap(), dst_dir.join("build/include"));
+    assert_eq!(fs::read_link(dst_dir.join("relative.h")).unwrap(), Path::new("build/config.h"));
+    assert_eq!(fs::read_link(dst_dir.join("stdio.h")).unwrap(), Path::new("/usr/include/stdio.h"));
+    assert_eq!(fs::read_to_string(dst_dir.join("config.h")).unwrap(), "#define X 1\n");
+    
+    // Retargeted into the destination, but nothing is there
+    assert_eq!(fs::read_link(dst_dir.join("missing.h")).unwrap(), dst_dir.join("build/missing.h"));
+    assert_eq!(report.dangling, vec![(Path::new("missing.h").to_path_buf(), dst_dir.join("build/missing.h"))]);
+    
+    let mut rewritten = report.rewritten;
+    rewritten.sort();
+    assert_eq!(rewritten, vec![Path::new("config.h"), Path::new("include"), Path::new("missing.h")]);
+    
+    // No temporary links left behind
+    let names: Vec<_> = fs::read_dir(&dst_dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    assert!(names.iter().all(|name| !name.to_string_lossy().contains("warp-tmp")), "{:?}", names);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_dry_run_reports_links_that_would_dangle() {
+    use std::os::unix::fs::symlink;
+    
+    let (_temp_dir, src_dir, dst_dir) = source_and_destination();
+    fs::create_dir_all(src_dir.join("build")).unwrap();
+    fs::create_dir_all(dst_dir.join("build")).unwrap();
+    fs::write(src_dir.join("build/config.h"), "#define X 1\n").unwrap();
+    fs::write(dst_dir.join("build/config.h"), "#define X 1\n").unwrap();
+    // Resolves today, but the destination has no generated.h to retarget it to
+    fs::write(src_dir.join("build/generated.h"), "#define Y 2\n").unwrap();
+    
+    symlink(src_dir.join("build/config.h"), dst_dir.join("config.h")).unwrap();
+    symlink(src_dir.join("build/generated.h"), dst_dir.join("generated.h")).unwrap();
+    
+    let report = PathRewriter::new(&src_dir, &dst_dir)
+        .dry_run(true)
+        .rewrite_paths_with_progress(&NoProgress)
+        .unwrap();
+    
+    assert_eq!(fs::read_link(dst_dir.join("generated.h")).unwrap(), src_dir.join("build/generated.h"));
+    assert_eq!(report.dangling, vec![(Path::new("generated.h").to_path_buf(), dst_dir.join("build/generated.h"))]);
+}
+
+#[test]
+fn test_dry_run_reports_diff_without_writing() {
+    let (_temp_dir, src_dir, dst_dir) = source_and_destination();
+    let original = format!("name = demo\nroot = {}/lib\nversion = 1\n", src_dir.display());
+    fs::write(dst_dir.join("settings.ini"), &original).unwrap();
+    fs::write(dst_dir.join("unrelated.txt"), "nothing to see\n").unwrap();
+    
+    let report = PathRewriter::new(&src_dir, &dst_dir)
+        .dry_run(true)
+        .rewrite_paths_with_progress(&NoProgress)
+        .unwrap();
+    
+    assert_eq!(fs::read_to_string(dst_dir.join("settings.ini")).unwrap(), original);
+    assert_eq!(report.rewritten, vec![Path::new("settings.ini")]);
+    assert!(report.journal.is_none());
+    
+    let expected = format!(
+        "--- a/settings.ini\n+++ b/settings.ini\n@@ -1,3 +1,3 @@\n name = demo\n-root = {}/lib\n+root = {}/lib\n version = 1\n",
+        src_dir.display(),
+        dst_dir.display(),
+    );
+    assert_eq!(report.diff, expected);
+}
@@ -1,4 +1,4 @@
-use git_warp::terminal::{TerminalManager, Terminal, TerminalMode};
+use git_warp::terminal::{TerminalManager, TerminalMode};
 use tempfile::tempdir;
 
 #[test]
@@ -75,7 +75,7 @@ fn test_apple_terminal_detection() {
 fn test_terminal_commands_generation() {
     // Test that we can generate appropriate terminal commands
     let temp_dir = tempdir().unwrap();
-    let path = temp_dir.path();
+    let _path = temp_dir.path();
     
     #[cfg(target_os = "macos")]
     {
@@ -103,8 +103,8 @@ fn test_terminal_commands_generation() {
 
 #[test]
 fn test_session_id_handling() {
-    let session_id = Some("test-session-123");
-    let path = tempdir().unwrap().path().to_path_buf();
+    let _session_id = Some("test-session-123");
+    let _path = tempdir().unwrap().path().to_path_buf();
     
     // Test that session IDs are handled properly in method calls
     #[cfg(target_os = "macos")]
@@ -124,8 +124,8 @@ fn test_session_id_handling() {
 
 #[test]
 fn test_init_script_handling() {
-    let init_script = Some("npm install && npm start");
-    let path = tempdir().unwrap().path().to_path_buf();
+    let _init_script = Some("npm install && npm start");
+    let _path = tempdir().unwrap().path().to_path_buf();
     
     #[cfg(target_os = "macos")]
     {
@@ -168,15 +168,15 @@ fn test_terminal_error_handling() {
         assert!(result.is_err());
         
         // Verify error message is descriptive
-        let error = result.unwrap_err();
+        let error = result.err().unwrap();
         println!("Expected error on unsupported platform: {}", error);
     }
 }
 
 #[test]
 fn test_branch_name_in_session() {
-    let branch_name = "feature/awesome-new-feature";
-    let path = tempdir().unwrap().path().to_path_buf();
+    let _branch_name = "feature/awesome-new-feature";
+    let _path = tempdir().unwrap().path().to_path_buf();
     
     #[cfg(target_os = "macos")]
     {
@@ -198,7 +198,7 @@ fn test_branch_name_in_session() {
 
 #[test]
 fn test_terminal_modes() {
-    let path = tempdir().unwrap().path().to_path_buf();
+    let _path = tempdir().unwrap().path().to_path_buf();
     
     #[cfg(target_os = "macos")]
     {
@@ -218,7 +218,7 @@ fn test_terminal_modes() {
 
 #[test]
 fn test_concurrent_terminal_operations() {
-    use std::thread;
+    
     
     #[cfg(target_os = "macos")]
     {
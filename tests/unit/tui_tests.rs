@@ -59,7 +59,7 @@ fn test_agent_activity_without_file() {
 
 #[test]
 fn test_agents_dashboard_creation() {
-    let dashboard = AgentsDashboard::new();
+    let _dashboard = AgentsDashboard::new();
     
     // Dashboard should be created successfully
     // In a real implementation, this would initialize the dashboard state
@@ -68,7 +68,7 @@ fn test_agents_dashboard_creation() {
 
 #[test]
 fn test_multiple_agent_activities() {
-    let activities = vec![
+    let activities = [
         AgentActivity {
             timestamp: "14:35:00".to_string(),
             agent_name: "Claude-Code".to_string(),
@@ -139,7 +139,7 @@ fn test_agent_status_transitions() {
 #[test]
 fn test_file_path_handling() {
     // Test various file path scenarios
-    let activities = vec![
+    let activities = [
         AgentActivity {
             timestamp: "14:30:00".to_string(),
             agent_name: "Claude-Code".to_string(),
@@ -180,7 +180,7 @@ fn test_file_path_handling() {
 
 #[test]
 fn test_agent_dashboard_statistics() {
-    let activities = vec![
+    let activities = [
         AgentActivity {
             timestamp: "14:30:00".to_string(),
             agent_name: "Claude-Code".to_string(),
@@ -310,7 +310,7 @@ fn test_worktree_monitoring() {
 fn test_cleanup_tui() {
     use git_warp::tui::CleanupTui;
     
-    let cleanup_tui = CleanupTui::new();
+    let _cleanup_tui = CleanupTui::new();
     
     // Test that cleanup TUI can be created
     println!("CleanupTui created successfully");
@@ -323,7 +323,7 @@ fn test_cleanup_tui() {
 fn test_config_tui() {
     use git_warp::tui::ConfigTui;
     
-    let config_tui = ConfigTui::new();
+    let _config_tui = ConfigTui::new();
     
     // Test that config TUI can be created
     println!("ConfigTui created successfully");